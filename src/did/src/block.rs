@@ -267,6 +267,18 @@ pub fn calculate_block_hash<T>(block: &Block<T>) -> H256 {
     keccak256(block.header_rlp_encoded()).into()
 }
 
+/// Calculate the root of the trie of the transactions of a block,
+/// keyed by the RLP encoded index of each transaction
+pub fn calculate_transactions_root(transactions: &[Transaction]) -> Result<H256, EvmError> {
+    let transactions = transactions
+        .iter()
+        .cloned()
+        .map(alloy::consensus::TxEnvelope::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(alloy::consensus::proofs::calculate_transaction_root(&transactions).into())
+}
+
 /// Calculate the size of a block in bytes considering all of its transactions
 pub fn calculate_block_size<'a>(
     block: &Block<H256>,
@@ -605,6 +617,32 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_calculate_transactions_root() {
+        assert_eq!(calculate_transactions_root(&[]).unwrap(), KECCAK_NULL_RLP);
+
+        let mut tx = Transaction {
+            gas_price: Some(U256::from(1u64)),
+            transaction_type: Some(0u64.into()),
+            v: 27u64.into(),
+            r: U256::from(1u64),
+            s: U256::from(1u64),
+            ..Default::default()
+        };
+        tx.hash = tx.slow_hash().unwrap().0;
+
+        // A trie with a single leaf node: the RLP encoded index 0 is the key, the transaction the value
+        let encoded_tx = tx.rlp_encoded_2718().unwrap();
+        let leaf: [&[u8]; 2] = [&[0x20, 0x80], &encoded_tx];
+        let mut node = vec![];
+        encode_list::<_, [u8]>(&leaf, &mut node);
+
+        assert_eq!(
+            calculate_transactions_root(&[tx]).unwrap(),
+            H256::from(keccak256(&node))
+        );
+    }
+
     fn create_transaction(gas_price: Option<U256>, chain_id: u64) -> Transaction {
         let mut tx = Transaction {
             from: alloy::primitives::Address::from_slice(&[0u8; 20]).into(),
@@ -1,5 +1,24 @@
-use candid::CandidType;
+use std::borrow::Cow;
+
+use candid::{CandidType, Principal};
+use ic_certification::{Certificate, HashTree, LookupResult};
 use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+use crate::{Block, H256};
+
+/// DER encoded public key of the IC mainnet, which signs the certificates of all the subnets
+pub const IC_ROOT_KEY: &[u8; 133] = b"\x30\x81\x82\x30\x1d\x06\x0d\x2b\x06\x01\x04\x01\x82\xdc\x7c\x05\x03\x01\x02\x01\x06\x0c\x2b\x06\x01\x04\x01\x82\xdc\x7c\x05\x03\x02\x01\x03\x61\x00\x81\x4c\x0e\x6e\xc7\x1f\xab\x58\x3b\x08\xbd\x81\x37\x3c\x25\x5c\x3c\x37\x1b\x2e\x84\x86\x3c\x98\xa4\xf1\xe0\x8b\x74\x23\x5d\x14\xfb\x5d\x9c\x0c\xd5\x46\xd9\x68\x5f\x91\x3a\x0c\x0b\x2c\xc5\x34\x15\x83\xbf\x4b\x43\x92\xe4\x67\xdb\x96\xd6\x5b\x9b\xb4\xa7\x17\x11\x2f\x84\x72\xe0\xd5\xa4\xd1\x45\x05\xff\xd7\x48\x4b\x01\x29\x10\x91\xc5\xf8\x7b\x98\x88\x34\x63\xf9\x80\x91\xa0\xba\xaa\xae";
+
+/// Label of the witness leaf holding the hash of the certified block
+pub const LAST_BLOCK_HASH_LABEL: &[u8] = b"last_block_hash";
+
+/// DER prefix of a BLS12-381 public key used by the IC
+const DER_PREFIX: &[u8; 37] = b"\x30\x81\x82\x30\x1d\x06\x0d\x2b\x06\x01\x04\x01\x82\xdc\x7c\x05\x03\x01\x02\x01\x06\x0c\x2b\x06\x01\x04\x01\x82\xdc\x7c\x05\x03\x02\x01\x03\x61\x00";
+/// Length of a raw BLS12-381 public key
+const BLS_KEY_LENGTH: usize = 96;
+/// Domain separator of the messages signed by the IC certificates
+const IC_STATE_ROOT_DOMAIN_SEPARATOR: &[u8; 14] = b"\x0Dic-state-root";
 
 #[derive(Serialize, Deserialize, CandidType, Clone, PartialEq, Eq, Debug)]
 pub struct CertifiedResult<T> {
@@ -7,3 +26,321 @@ pub struct CertifiedResult<T> {
     pub witness: Vec<u8>,
     pub certificate: Vec<u8>,
 }
+
+/// Reasons why a certified result is not valid
+#[derive(Debug, thiserror::Error, PartialEq, Eq, Clone)]
+pub enum CertificateError {
+    #[error("malformed certificate: {0}")]
+    MalformedCertificate(String),
+    #[error("malformed witness: {0}")]
+    MalformedWitness(String),
+    #[error("invalid public key")]
+    InvalidPublicKey,
+    #[error("invalid certificate signature")]
+    InvalidSignature,
+    #[error("the certificate delegation is not valid for canister {0}")]
+    InvalidDelegation(Principal),
+    #[error("the certificate has no certified data for canister {0}")]
+    MissingCertifiedData(Principal),
+    #[error("the witness does not match the certified data")]
+    WitnessMismatch,
+    #[error("the witness does not commit to the block hash {0}")]
+    BlockHashMismatch(H256),
+}
+
+impl<T> CertifiedResult<T> {
+    /// Verifies that the certificate is signed by the IC with the given DER encoded root key,
+    /// and that the certified data of `canister_id` is the root hash of the witness.
+    /// Returns the verified witness tree.
+    pub fn verify_certificate(
+        &self,
+        ic_root_key: &[u8],
+        canister_id: &Principal,
+    ) -> Result<HashTree, CertificateError> {
+        let certificate: Certificate = serde_cbor::from_slice(&self.certificate)
+            .map_err(|e| CertificateError::MalformedCertificate(e.to_string()))?;
+        verify_certificate_signature(&certificate, ic_root_key, canister_id)?;
+
+        let certified_data = lookup(
+            &certificate.tree,
+            [
+                b"canister".as_slice(),
+                canister_id.as_slice(),
+                b"certified_data".as_slice(),
+            ],
+        )
+        .ok_or(CertificateError::MissingCertifiedData(*canister_id))?;
+
+        let witness: HashTree = serde_cbor::from_slice(&self.witness)
+            .map_err(|e| CertificateError::MalformedWitness(e.to_string()))?;
+        if witness.digest().as_slice() != certified_data {
+            return Err(CertificateError::WitnessMismatch);
+        }
+
+        Ok(witness)
+    }
+}
+
+impl CertifiedResult<Block<H256>> {
+    /// Verifies the certificate, as [`CertifiedResult::verify_certificate`] does,
+    /// and that the witness commits to the hash of the block.
+    pub fn verify(
+        &self,
+        ic_root_key: &[u8],
+        canister_id: &Principal,
+    ) -> Result<(), CertificateError> {
+        let witness = self.verify_certificate(ic_root_key, canister_id)?;
+
+        match lookup(&witness, [LAST_BLOCK_HASH_LABEL]) {
+            Some(hash) if hash == self.data.hash.0.as_slice() => Ok(()),
+            _ => Err(CertificateError::BlockHashMismatch(self.data.hash.clone())),
+        }
+    }
+}
+
+/// Verifies the signature of the certificate, and of its delegation if any
+fn verify_certificate_signature(
+    certificate: &Certificate,
+    ic_root_key: &[u8],
+    canister_id: &Principal,
+) -> Result<(), CertificateError> {
+    let public_key = match &certificate.delegation {
+        Some(delegation) => {
+            let delegation_certificate: Certificate =
+                serde_cbor::from_slice(&delegation.certificate)
+                    .map_err(|e| CertificateError::MalformedCertificate(e.to_string()))?;
+            // Delegations are one level deep
+            if delegation_certificate.delegation.is_some() {
+                return Err(CertificateError::InvalidDelegation(*canister_id));
+            }
+            verify_certificate_signature(&delegation_certificate, ic_root_key, canister_id)?;
+
+            let subnet_id = delegation.subnet_id.as_slice();
+            let canister_ranges = lookup(
+                &delegation_certificate.tree,
+                [
+                    b"subnet".as_slice(),
+                    subnet_id,
+                    b"canister_ranges".as_slice(),
+                ],
+            )
+            .ok_or(CertificateError::InvalidDelegation(*canister_id))?;
+            let canister_ranges: Vec<(ByteBuf, ByteBuf)> = serde_cbor::from_slice(canister_ranges)
+                .map_err(|e| CertificateError::MalformedCertificate(e.to_string()))?;
+            let canister_id_bytes = canister_id.as_slice();
+            if !canister_ranges.iter().any(|(low, high)| {
+                low.as_slice() <= canister_id_bytes && canister_id_bytes <= high.as_slice()
+            }) {
+                return Err(CertificateError::InvalidDelegation(*canister_id));
+            }
+
+            let public_key = lookup(
+                &delegation_certificate.tree,
+                [b"subnet".as_slice(), subnet_id, b"public_key".as_slice()],
+            )
+            .ok_or(CertificateError::InvalidDelegation(*canister_id))?;
+            Cow::Owned(public_key.to_vec())
+        }
+        None => Cow::Borrowed(ic_root_key),
+    };
+
+    let public_key = public_key
+        .strip_prefix(DER_PREFIX.as_slice())
+        .filter(|key| key.len() == BLS_KEY_LENGTH)
+        .ok_or(CertificateError::InvalidPublicKey)?;
+
+    let mut message = IC_STATE_ROOT_DOMAIN_SEPARATOR.to_vec();
+    message.extend_from_slice(&certificate.tree.digest());
+
+    ic_verify_bls_signature::verify_bls_signature(&certificate.signature, &message, public_key)
+        .map_err(|_| CertificateError::InvalidSignature)
+}
+
+/// Returns the value of the leaf at the given path, if the tree has it
+fn lookup<'a, P>(tree: &'a HashTree, path: P) -> Option<&'a [u8]>
+where
+    P: IntoIterator,
+    P::Item: AsRef<[u8]>,
+{
+    match tree.lookup_path(path) {
+        LookupResult::Found(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Certificates signed by a local root key, to test the certificate verification
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils {
+    use ic_certification::{fork, labeled, leaf};
+    use ic_verify_bls_signature::PrivateKey;
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    /// A root key which signs certificates without delegation
+    #[derive(Clone)]
+    pub struct LocalRootKey {
+        private_key: PrivateKey,
+    }
+
+    impl LocalRootKey {
+        /// Derives a root key from the given seed
+        pub fn from_seed(seed: &[u8]) -> Self {
+            let mut key_bytes: [u8; 32] = Sha256::digest(seed).into();
+            // Keeps the key below the order of the BLS12-381 scalar field
+            key_bytes[0] &= 0x3f;
+
+            Self {
+                private_key: PrivateKey::deserialize(&key_bytes)
+                    .expect("the key is in the scalar field"),
+            }
+        }
+
+        /// Returns the DER encoded public key, to be used as the IC root key
+        pub fn public_key(&self) -> Vec<u8> {
+            let mut public_key = DER_PREFIX.to_vec();
+            public_key.extend_from_slice(&self.private_key.public_key().serialize());
+            public_key
+        }
+
+        /// Returns the CBOR encoded certificate of the certified data of a canister
+        pub fn certify(&self, canister_id: &Principal, certified_data: &[u8]) -> Vec<u8> {
+            let tree = fork(
+                labeled(
+                    b"canister",
+                    labeled(
+                        canister_id.as_slice(),
+                        labeled(b"certified_data", leaf(certified_data)),
+                    ),
+                ),
+                labeled(b"time", leaf(vec![0])),
+            );
+
+            let mut message = IC_STATE_ROOT_DOMAIN_SEPARATOR.to_vec();
+            message.extend_from_slice(&tree.digest());
+            let signature = self.private_key.sign(&message).serialize().to_vec();
+
+            serde_cbor::to_vec(&Certificate {
+                tree,
+                signature,
+                delegation: None,
+            })
+            .expect("the certificate is serializable")
+        }
+
+        /// Certifies the block as the EVM canister does: the witness holds the
+        /// block hash and its root hash is the certified data of the canister.
+        pub fn certify_block(
+            &self,
+            canister_id: &Principal,
+            block: Block<H256>,
+        ) -> CertifiedResult<Block<H256>> {
+            let witness = labeled(LAST_BLOCK_HASH_LABEL, leaf(block.hash.0.as_slice()));
+            let certificate = self.certify(canister_id, &witness.digest());
+
+            CertifiedResult {
+                data: block,
+                witness: serde_cbor::to_vec(&witness).expect("the witness is serializable"),
+                certificate,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::LocalRootKey;
+    use super::*;
+
+    fn canister_id() -> Principal {
+        Principal::from_slice(&[0, 0, 0, 0, 1, 48, 0, 4, 1, 1])
+    }
+
+    fn block() -> Block<H256> {
+        Block {
+            number: 42_u64.into(),
+            hash: H256::from_slice(&[7; 32]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_ic_root_key_is_a_bls_key() {
+        assert_eq!(&IC_ROOT_KEY[..DER_PREFIX.len()], DER_PREFIX.as_slice());
+        assert_eq!(IC_ROOT_KEY.len(), DER_PREFIX.len() + BLS_KEY_LENGTH);
+    }
+
+    #[test]
+    fn test_verify_certified_block() {
+        let root_key = LocalRootKey::from_seed(b"test");
+        let certified_block = root_key.certify_block(&canister_id(), block());
+
+        assert_eq!(
+            certified_block.verify(&root_key.public_key(), &canister_id()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_verify_fails_with_other_root_key() {
+        let root_key = LocalRootKey::from_seed(b"test");
+        let certified_block = root_key.certify_block(&canister_id(), block());
+
+        assert_eq!(
+            certified_block.verify(IC_ROOT_KEY, &canister_id()),
+            Err(CertificateError::InvalidSignature)
+        );
+        assert_eq!(
+            certified_block.verify(
+                &LocalRootKey::from_seed(b"other").public_key(),
+                &canister_id()
+            ),
+            Err(CertificateError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_verify_fails_for_other_canister() {
+        let root_key = LocalRootKey::from_seed(b"test");
+        let certified_block = root_key.certify_block(&canister_id(), block());
+        let other_canister = Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1]);
+
+        assert_eq!(
+            certified_block.verify(&root_key.public_key(), &other_canister),
+            Err(CertificateError::MissingCertifiedData(other_canister))
+        );
+    }
+
+    #[test]
+    fn test_verify_fails_if_witness_does_not_commit_to_block() {
+        let root_key = LocalRootKey::from_seed(b"test");
+        let mut certified_block = root_key.certify_block(&canister_id(), block());
+        certified_block.data.hash = H256::from_slice(&[8; 32]);
+
+        assert_eq!(
+            certified_block.verify(&root_key.public_key(), &canister_id()),
+            Err(CertificateError::BlockHashMismatch(
+                certified_block.data.hash.clone()
+            ))
+        );
+
+        let other_block = root_key.certify_block(&canister_id(), certified_block.data.clone());
+        certified_block.witness = other_block.witness;
+        assert_eq!(
+            certified_block.verify(&root_key.public_key(), &canister_id()),
+            Err(CertificateError::WitnessMismatch)
+        );
+    }
+
+    #[test]
+    fn test_verify_fails_on_malformed_certificate() {
+        let root_key = LocalRootKey::from_seed(b"test");
+        let mut certified_block = root_key.certify_block(&canister_id(), block());
+        certified_block.certificate = vec![1, 2, 3];
+
+        assert!(matches!(
+            certified_block.verify(&root_key.public_key(), &canister_id()),
+            Err(CertificateError::MalformedCertificate(_))
+        ));
+    }
+}
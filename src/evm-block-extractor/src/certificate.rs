@@ -0,0 +1,29 @@
+use candid::Principal;
+use did::certified::CertificateError;
+
+use crate::database::CertifiedBlock;
+
+/// Verifies the IC certificates of the blocks certified by the EVM canister
+#[derive(Debug, Clone)]
+pub struct CertificateVerifier {
+    /// DER encoded public key of the IC
+    ic_root_key: Vec<u8>,
+    evm_canister_id: Principal,
+}
+
+impl CertificateVerifier {
+    pub fn new(ic_root_key: Vec<u8>, evm_canister_id: Principal) -> Self {
+        Self {
+            ic_root_key,
+            evm_canister_id,
+        }
+    }
+
+    /// Verifies that the certificate is signed by the IC for the EVM canister,
+    /// and that its witness commits to the block hash.
+    /// The time of the certificate is not checked, so a valid certificate is accepted
+    /// however old it is.
+    pub fn verify(&self, certified_block: &CertifiedBlock) -> Result<(), CertificateError> {
+        certified_block.verify(&self.ic_root_key, &self.evm_canister_id)
+    }
+}
@@ -1,40 +1,163 @@
+use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
-use clap::{Parser, Subcommand};
-use sqlx::PgPool;
+use candid::Principal;
+use clap::error::ErrorKind;
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand};
+use did::certified::IC_ROOT_KEY;
+use ethereum_json_rpc_client::EthJsonRpcClient;
+use ethereum_json_rpc_client::reqwest::{ReqwestClient, reqwest};
+use sqlx::pool::PoolOptions;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
+use sqlx::{Postgres, Sqlite};
 
+use crate::certificate::CertificateVerifier;
+use crate::database::DatabaseClient;
+use crate::database::any_db_client::AnyDbClient;
+use crate::database::in_memory_db_client::InMemoryDbClient;
 use crate::database::postgres_db_client::PostgresDbClient;
+use crate::database::sqlite_db_client::SqliteDbClient;
+use crate::failover::{Endpoint, FailoverClient};
+use crate::metrics::MeteredClient;
+use crate::task::archive::ArchiveFormat;
+use crate::task::retention::RetentionPolicy;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-/// Simple CLI parser for the EVM block extractor
+/// The environment variable setting the path of the configuration file
+const CONFIG_ENV: &str = "EVM_BLOCK_EXTRACTOR_CONFIG";
+
+/// Names of the database subcommands
+const DATABASE_COMMANDS: [&str; 3] = ["--postgres", "--sqlite", "--in-memory"];
+
+/// Simple CLI parser for the EVM block extractor.
+/// Each argument can also be set by an environment variable or by the TOML configuration file,
+/// see [`ExtractorArgs::load`].
 #[derive(Parser, Debug, Clone)]
 #[clap(
     version = VERSION,
-    about = "A tool to extract EVM blocks and transactions and serve them through JSON RPC endpoints"
+    about = "A tool to extract EVM blocks and transactions and serve them through JSON RPC endpoints",
+    args_override_self = true
 )]
 pub struct ExtractorArgs {
+    /// The TOML configuration file. Its keys are the names of the arguments,
+    /// and the arguments of the database are in a `[postgres]` or `[sqlite]` table.
+    /// Command line arguments and environment variables take precedence over it.
+    #[arg(long, env = CONFIG_ENV)]
+    pub config: Option<PathBuf>,
+
     /// The server address to bind to serve JSON RPC requests
-    #[arg(long = "server-address", short('s'), default_value = "0.0.0.0:8080")]
+    #[arg(
+        long = "server-address",
+        env = "EVM_BLOCK_EXTRACTOR_SERVER_ADDRESS",
+        short('s'),
+        default_value = "0.0.0.0:8080"
+    )]
     pub server_address: String,
 
-    /// The JSON-RPC URL of the remote EVMC instance from which to extract blocks.
-    /// If missing or empty the block extracting task won't start.
-    #[arg(long = "rpc-url", short('u'))]
-    pub remote_rpc_url: String,
+    /// The maximum number of EVMC blocks not stored yet for the server to be ready.
+    /// Beyond this lag the `/ready` route fails.
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_MAX_SYNC_LAG_BLOCKS",
+        default_value = "100"
+    )]
+    pub max_sync_lag_blocks: u64,
+
+    /// The address to bind to serve the Prometheus metrics.
+    /// If missing the metrics are not collected.
+    #[arg(long = "metrics-address", env = "EVM_BLOCK_EXTRACTOR_METRICS_ADDRESS")]
+    pub metrics_address: Option<SocketAddr>,
+
+    /// Comma separated JSON-RPC URLs of the remote EVMC instances from which to extract blocks.
+    /// The first one is used until it fails; then the requests are sent to the next one
+    /// reporting the same chain id and block hashes.
+    #[arg(
+        long = "rpc-url",
+        env = "EVM_BLOCK_EXTRACTOR_RPC_URL",
+        short('u'),
+        value_delimiter = ',',
+        action = ArgAction::Set,
+        required = true
+    )]
+    pub remote_rpc_urls: Vec<String>,
+
+    /// Time in seconds a failed EVMC endpoint is not selected again
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_RPC_FAILOVER_COOLDOWN_SECS",
+        default_value = "30"
+    )]
+    pub rpc_failover_cooldown_secs: u64,
+
+    /// The id of the EVM canister, used to verify the certificates of the certified blocks.
+    /// If missing the certificates are not verified.
+    #[arg(long, env = "EVM_BLOCK_EXTRACTOR_EVM_CANISTER_ID")]
+    pub evm_canister_id: Option<Principal>,
+
+    /// The hex encoded DER public key of the IC, used to verify the certificates of the
+    /// certified blocks. Defaults to the IC mainnet root key; local replicas have their own.
+    #[arg(long, env = "EVM_BLOCK_EXTRACTOR_IC_ROOT_KEY")]
+    pub ic_root_key: Option<String>,
+
+    /// Whether to recompute the hash and the transactions root of the fetched blocks
+    /// from their content. Blocks not matching their content are not stored.
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_VERIFY_BLOCK_INTEGRITY",
+        default_value = "false"
+    )]
+    pub verify_block_integrity: bool,
+
+    /// The maximum number of stored blocks discarded by a chain reorganization.
+    /// If the stored and the EVMC blockchains have no common ancestor within this depth,
+    /// the extraction fails instead of discarding the stored blocks.
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_MAX_REORG_DEPTH",
+        default_value = "1000"
+    )]
+    pub max_reorg_depth: u64,
+
+    /// Comma separated JSON-RPC methods not served by the extractor to forward to the EVMC,
+    /// e.g. `eth_call,eth_estimateGas,eth_sendRawTransaction`.
+    /// If missing no method is forwarded.
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_PROXY_METHODS",
+        value_delimiter = ',',
+        action = ArgAction::Set
+    )]
+    pub proxy_methods: Vec<String>,
 
     /// Time in seconds to wait for a response from the EVMC
-    #[arg(long, default_value = "60")]
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_REQUEST_TIME_OUT_SECS",
+        default_value = "60"
+    )]
     pub request_time_out_secs: u64,
 
-    #[arg(long, default_value = "10")]
+    #[arg(long, env = "EVM_BLOCK_EXTRACTOR_RPC_BATCH_SIZE", default_value = "10")]
     pub rpc_batch_size: usize,
 
+    /// The maximum number of block batches fetched concurrently from the EVMC.
+    /// The batches are still validated and stored in order.
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_MAX_PARALLEL_BATCHES",
+        default_value = "1"
+    )]
+    pub max_parallel_batches: usize,
+
     /// Sets the logger [`EnvFilter`].
     /// Valid values: trace, debug, info, warn, error
     /// Example of a valid filter: "warn,my_crate=info,my_crate::my_mod=debug,[my_span]=trace".
-    #[arg(long, default_value = "info")]
+    #[arg(long, env = "EVM_BLOCK_EXTRACTOR_LOG_FILTER", default_value = "info")]
     pub log_filter: String,
 
     #[command(subcommand)]
@@ -42,50 +165,505 @@ pub struct ExtractorArgs {
 
     /// Whether to reset the database when the blockchain state changes.
     /// This is useful for testing environments, but should not be used in production.
-    #[arg(long, default_value = "false")]
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_RESET_DB_ON_STATE_CHANGE",
+        default_value = "false"
+    )]
     pub reset_db_on_state_change: bool,
 
     /// The interval in seconds at which the block extractor job should run
-    #[arg(long, default_value = "120")]
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_BLOCK_EXTRACTOR_JOB_INTERVAL_SECONDS",
+        default_value = "120"
+    )]
     pub block_extractor_job_interval_seconds: u64,
+
+    /// Keep following the head of the chain with a single extractor
+    /// instead of running the block extractor job at fixed intervals
+    #[arg(long, env = "EVM_BLOCK_EXTRACTOR_FOLLOW", default_value = "false")]
+    pub follow: bool,
+
+    /// The shortest interval in milliseconds between two polls of the EVMC in follow mode
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_FOLLOW_MIN_POLL_INTERVAL_MILLIS",
+        default_value = "500"
+    )]
+    pub follow_min_poll_interval_millis: u64,
+
+    /// The longest interval in milliseconds between two polls of the EVMC in follow mode,
+    /// reached when no new blocks are produced
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_FOLLOW_MAX_POLL_INTERVAL_MILLIS",
+        default_value = "5000"
+    )]
+    pub follow_max_poll_interval_millis: u64,
+
+    /// The longest delay in seconds before retrying after an error in follow mode
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_FOLLOW_MAX_ERROR_BACKOFF_SECS",
+        default_value = "60"
+    )]
+    pub follow_max_error_backoff_secs: u64,
+
+    /// Keep only the latest N blocks in the database, pruning the older ones.
+    /// If both this and `--retention-days` are missing, no block is pruned.
+    #[arg(long, env = "EVM_BLOCK_EXTRACTOR_RETENTION_BLOCKS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub retention_blocks: Option<u64>,
+
+    /// Keep only the blocks of the last N days in the database, pruning the older ones.
+    /// If both this and `--retention-blocks` are set, the blocks kept by either are not pruned.
+    #[arg(long, env = "EVM_BLOCK_EXTRACTOR_RETENTION_DAYS")]
+    pub retention_days: Option<u64>,
+
+    /// Keep the headers of the pruned blocks, deleting only their transactions, receipts and logs
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_RETENTION_HEADERS_ONLY",
+        default_value = "false"
+    )]
+    pub retention_headers_only: bool,
+
+    /// The maximum number of blocks pruned at once by the retention job
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_RETENTION_BATCH_SIZE",
+        default_value = "1000"
+    )]
+    pub retention_batch_size: u64,
+
+    /// The interval in seconds at which the retention job should run
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_RETENTION_JOB_INTERVAL_SECONDS",
+        default_value = "3600"
+    )]
+    pub retention_job_interval_seconds: u64,
+}
+
+impl ExtractorArgs {
+    /// Parses the arguments from the command line, the environment variables
+    /// and the configuration file, in this order of precedence
+    pub fn load() -> Result<Self, clap::Error> {
+        Self::load_from(std::env::args_os())
+    }
+
+    /// Parses the arguments from `args`, the environment variables
+    /// and the configuration file, in this order of precedence
+    pub fn load_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+
+        let args = match config_file_path(&args) {
+            Some(path) => merge_config_file(&path, args)?,
+            None => args,
+        };
+
+        Self::try_parse_from(args)
+    }
+
+    /// Returns the client of the EVMC endpoints.
+    /// When an endpoint fails, the client switches to the next one serving the blockchain
+    /// stored in `db_client`.
+    pub fn evm_client<DB: DatabaseClient + 'static>(
+        &self,
+        db_client: Arc<DB>,
+    ) -> anyhow::Result<EthJsonRpcClient<FailoverClient<MeteredClient<ReqwestClient>, DB>>> {
+        // Endpoints not answering in time fail, so that the client switches to the next one
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(self.request_time_out_secs))
+            .build()?;
+
+        let endpoints = self
+            .remote_rpc_urls
+            .iter()
+            .filter(|url| !url.is_empty())
+            .map(|url| Endpoint {
+                url: url.clone(),
+                client: MeteredClient::new(ReqwestClient::new_with_client(
+                    url.clone(),
+                    http_client.clone(),
+                )),
+            })
+            .collect();
+
+        let client = FailoverClient::new(
+            endpoints,
+            db_client,
+            Duration::from_secs(self.rpc_failover_cooldown_secs),
+        )?;
+
+        Ok(EthJsonRpcClient::new(client))
+    }
+
+    /// Returns the verifier of the certified blocks, if the EVM canister id is set
+    pub fn certificate_verifier(&self) -> anyhow::Result<Option<CertificateVerifier>> {
+        let Some(evm_canister_id) = self.evm_canister_id else {
+            return Ok(None);
+        };
+
+        let ic_root_key = match &self.ic_root_key {
+            Some(ic_root_key) => alloy::hex::decode(ic_root_key)
+                .map_err(|e| anyhow::anyhow!("Invalid IC root key {ic_root_key}: {e}"))?,
+            None => IC_ROOT_KEY.to_vec(),
+        };
+
+        Ok(Some(CertificateVerifier::new(ic_root_key, evm_canister_id)))
+    }
+
+    /// Returns the retention policy of the blocks, if the blocks should be pruned
+    pub fn retention_policy(&self) -> Option<RetentionPolicy> {
+        if self.retention_blocks.is_none() && self.retention_days.is_none() {
+            return None;
+        }
+
+        Some(RetentionPolicy {
+            keep_blocks: self.retention_blocks,
+            keep_days: self.retention_days,
+            headers_only: self.retention_headers_only,
+            batch_size: self.retention_batch_size,
+        })
+    }
+}
+
+/// Returns the path of the configuration file, set on the command line or by its environment variable
+fn config_file_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+        // The arguments of the database follow
+        if DATABASE_COMMANDS.contains(&arg.as_ref()) {
+            break;
+        }
+    }
+
+    std::env::var_os(CONFIG_ENV).map(PathBuf::from)
+}
+
+/// Inserts the arguments set by the configuration file before the ones on the command line,
+/// so that the latter override them
+fn merge_config_file(path: &Path, args: Vec<OsString>) -> Result<Vec<OsString>, clap::Error> {
+    let mut command = ExtractorArgs::command();
+
+    let mut config: toml::Table = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| content.parse().map_err(|e: toml::de::Error| e.to_string()))
+        .map_err(|e| {
+            command.error(
+                ErrorKind::Io,
+                format!("Invalid configuration file {}: {e}", path.display()),
+            )
+        })?;
+
+    let mut database_configs = DATABASE_COMMANDS
+        .iter()
+        .filter_map(|name| {
+            let key = name.trim_start_matches('-').replace('-', "_");
+            config.remove(&key).map(|value| (*name, value))
+        })
+        .collect::<Vec<_>>();
+    if database_configs.len() > 1 {
+        return Err(command.error(
+            ErrorKind::ArgumentConflict,
+            "The configuration file sets more than one database",
+        ));
+    }
+
+    let database_position = args
+        .iter()
+        .position(|arg| DATABASE_COMMANDS.iter().any(|name| arg == name))
+        .unwrap_or(args.len());
+    let (global_args, database_args) = args.split_at(database_position);
+
+    let mut merged = global_args[..1].to_vec();
+    merged.extend(config_args(&mut command, &config)?);
+    merged.extend_from_slice(&global_args[1..]);
+
+    // The database on the command line replaces the one in the configuration file
+    let database = database_args
+        .first()
+        .map(|name| name.to_string_lossy().into_owned())
+        .or_else(|| database_configs.first().map(|(name, _)| name.to_string()));
+    if let Some(database) = database {
+        merged.push(database.clone().into());
+        if let Some((_, value)) = database_configs.pop().filter(|(name, _)| *name == database) {
+            let toml::Value::Table(database_config) = value else {
+                return Err(command.error(
+                    ErrorKind::InvalidValue,
+                    format!("The configuration of {database} must be a table"),
+                ));
+            };
+            let mut subcommand = command
+                .find_subcommand(&database)
+                .cloned()
+                .expect("database subcommands are defined");
+            merged.extend(config_args(&mut subcommand, &database_config)?);
+        }
+        merged.extend(database_args.iter().skip(1).cloned());
+    }
+
+    Ok(merged)
+}
+
+/// Converts the configuration keys into the arguments of the command.
+/// The keys set by an environment variable are skipped, since the environment takes precedence.
+fn config_args(
+    command: &mut clap::Command,
+    config: &toml::Table,
+) -> Result<Vec<OsString>, clap::Error> {
+    let mut args = vec![];
+    for (key, value) in config {
+        let long = key.replace('_', "-");
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()) && long != "config")
+        else {
+            return Err(command.error(
+                ErrorKind::UnknownArgument,
+                format!("Unknown configuration key {key}"),
+            ));
+        };
+
+        if arg
+            .get_env()
+            .is_some_and(|env| std::env::var_os(env).is_some())
+        {
+            continue;
+        }
+
+        let flag = format!("--{long}");
+        match value {
+            // Flags without a value are set by `true`
+            toml::Value::Boolean(value) if !arg.get_action().takes_values() => {
+                if *value {
+                    args.push(flag.into());
+                }
+            }
+            toml::Value::String(value) => {
+                args.push(flag.into());
+                args.push(value.into());
+            }
+            toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                args.push(flag.into());
+                args.push(value.to_string().into());
+            }
+            // Lists are set as a single delimited value
+            toml::Value::Array(values) => {
+                let values = values
+                    .iter()
+                    .map(toml::Value::as_str)
+                    .collect::<Option<Vec<_>>>();
+                let (Some(delimiter), Some(values)) = (arg.get_value_delimiter(), values) else {
+                    return Err(command.error(
+                        ErrorKind::InvalidValue,
+                        format!("Invalid value of the configuration key {key}"),
+                    ));
+                };
+                args.push(flag.into());
+                args.push(values.join(delimiter.to_string().as_str()).into());
+            }
+            _ => {
+                return Err(command.error(
+                    ErrorKind::InvalidValue,
+                    format!("Invalid value of the configuration key {key}"),
+                ));
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// Sizing of the database connection pool
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct PoolArgs {
+    /// The maximum number of connections to the database
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_DB_MAX_CONNECTIONS",
+        default_value = "10"
+    )]
+    pub max_connections: u32,
+    /// The minimum number of connections to the database kept open
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_DB_MIN_CONNECTIONS",
+        default_value = "0"
+    )]
+    pub min_connections: u32,
+    /// Time in seconds to wait for a free connection to the database
+    #[arg(
+        long,
+        env = "EVM_BLOCK_EXTRACTOR_DB_ACQUIRE_TIMEOUT_SECS",
+        default_value = "30"
+    )]
+    pub acquire_timeout_secs: u64,
+}
+
+impl Default for PoolArgs {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+        }
+    }
+}
+
+impl PoolArgs {
+    /// Returns the options of a connection pool with this sizing
+    fn pool_options<DB: sqlx::Database>(&self) -> PoolOptions<DB> {
+        PoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(Duration::from_secs(self.acquire_timeout_secs))
+    }
+}
+
+/// Reads a secret from a file, dropping the trailing newline
+fn read_secret(path: &Path) -> anyhow::Result<String> {
+    let secret = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Error reading secret file {}: {e}", path.display()))?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_owned())
 }
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum Database {
-    #[command(name = "--postgres")]
+    #[command(name = "--postgres", args_override_self = true)]
     Postgres {
         /// The username of the Postgres database
-        #[arg(long)]
+        #[arg(long, env = "EVM_BLOCK_EXTRACTOR_POSTGRES_USERNAME")]
         username: String,
-        /// The password of the Postgres database
-        #[arg(long)]
-        password: String,
+        /// The password of the Postgres database.
+        /// Prefer the environment variable or `--password-file`, since command line arguments
+        /// are visible to the other users of the host.
+        #[arg(
+            long,
+            env = "EVM_BLOCK_EXTRACTOR_POSTGRES_PASSWORD",
+            hide_env_values = true
+        )]
+        password: Option<String>,
+        /// The file holding the password of the Postgres database, used if the password is not set
+        #[arg(long, env = "EVM_BLOCK_EXTRACTOR_POSTGRES_PASSWORD_FILE")]
+        password_file: Option<PathBuf>,
         /// The name of the Postgres database
-        #[arg(long)]
+        #[arg(long, env = "EVM_BLOCK_EXTRACTOR_POSTGRES_DATABASE_NAME")]
         database_name: String,
         /// The host of the Postgres database
-        #[arg(long)]
+        #[arg(long, env = "EVM_BLOCK_EXTRACTOR_POSTGRES_DATABASE_URL")]
         database_url: String,
         /// The port of the Postgres database
-        #[arg(long, default_value = "5432")]
+        #[arg(
+            long,
+            env = "EVM_BLOCK_EXTRACTOR_POSTGRES_DATABASE_PORT",
+            default_value = "5432"
+        )]
         database_port: u16,
         /// Demand SSL connection
-        #[arg(long, default_value = "false")]
+        #[arg(
+            long,
+            env = "EVM_BLOCK_EXTRACTOR_POSTGRES_REQUIRE_SSL",
+            default_value = "false"
+        )]
         require_ssl: bool,
+        #[command(flatten)]
+        pool: PoolArgs,
+        /// Maintenance task to run instead of extracting blocks
+        #[command(subcommand)]
+        action: Option<Action>,
+    },
+    #[command(name = "--sqlite", args_override_self = true)]
+    Sqlite {
+        /// The path of the SQLite database file; it is created if missing
+        #[arg(long, env = "EVM_BLOCK_EXTRACTOR_SQLITE_DATABASE_PATH")]
+        database_path: PathBuf,
+        #[command(flatten)]
+        pool: PoolArgs,
+        /// Maintenance task to run instead of extracting blocks
+        #[command(subcommand)]
+        action: Option<Action>,
+    },
+    /// Keep all the data in memory; it is lost when the process exits
+    #[command(name = "--in-memory")]
+    InMemory,
+}
+
+/// Maintenance tasks on the database; the process exits once the task is completed
+#[derive(Subcommand, Debug, Clone)]
+pub enum Action {
+    /// Scan the database for missing blocks and for transactions without a block,
+    /// then refetch the affected blocks from the remote EVM
+    Backfill {
+        /// The first block to scan; defaults to the earliest block in the database
+        #[arg(long)]
+        from_block: Option<u64>,
+        /// The last block to scan; defaults to the latest block in the database
+        #[arg(long)]
+        to_block: Option<u64>,
+    },
+    /// Export the blocks in the database to gzip compressed archive files,
+    /// along with a manifest describing the archive
+    Export {
+        /// The first block to export; defaults to the earliest block in the database
+        #[arg(long)]
+        from_block: Option<u64>,
+        /// The last block to export; defaults to the latest block in the database
+        #[arg(long)]
+        to_block: Option<u64>,
+        /// The directory the archive files are written to; it is created if missing
+        #[arg(long)]
+        output_dir: PathBuf,
+        /// The format of the exported blocks
+        #[arg(long, value_enum, default_value = "jsonl")]
+        format: ArchiveFormat,
+        /// The number of blocks in each archive file
+        #[arg(long, default_value = "10000")]
+        blocks_per_file: u64,
+    },
+    /// Import the blocks of an archive written by the export, resuming from the
+    /// block following the latest one in the database
+    Import {
+        /// The directory holding the archive files and their manifest
+        #[arg(long)]
+        input_dir: PathBuf,
     },
 }
 
 impl Database {
+    /// Returns the maintenance task to run, if any
+    pub fn action(&self) -> Option<&Action> {
+        match self {
+            Database::Postgres { action, .. } | Database::Sqlite { action, .. } => action.as_ref(),
+            Database::InMemory => None,
+        }
+    }
+
     /// Build a database client based on the database type
-    pub async fn build_client(self) -> anyhow::Result<Arc<PostgresDbClient>> {
+    pub async fn build_client(self) -> anyhow::Result<Arc<AnyDbClient>> {
         match self {
             Database::Postgres {
                 username,
                 password,
+                password_file,
                 database_name: database,
                 database_url: host,
                 database_port: port,
                 require_ssl,
+                pool,
+                action: _,
             } => {
                 log::info!("Use Postgres database");
                 log::info!("- username: {}", username);
@@ -93,6 +671,15 @@ impl Database {
                 log::info!("- host: {}", host);
                 log::info!("- port: {}", port);
                 log::info!("- require-ssl: {}", require_ssl);
+                log::info!("- pool: {:?}", pool);
+
+                let password = match (password, password_file) {
+                    (Some(password), _) => password,
+                    (None, Some(password_file)) => read_secret(&password_file)?,
+                    (None, None) => anyhow::bail!(
+                        "Missing Postgres password: set --password-file or the EVM_BLOCK_EXTRACTOR_POSTGRES_PASSWORD environment variable"
+                    ),
+                };
 
                 let ssl_mode = if require_ssl {
                     PgSslMode::Require
@@ -108,8 +695,32 @@ impl Database {
                     .port(port)
                     .ssl_mode(ssl_mode);
 
-                let pool = PgPool::connect_with(options).await?;
-                Ok(Arc::new(PostgresDbClient::new(pool)))
+                let pool = pool
+                    .pool_options::<Postgres>()
+                    .connect_with(options)
+                    .await?;
+                Ok(Arc::new(PostgresDbClient::new(pool).into()))
+            }
+            Database::Sqlite {
+                database_path,
+                pool,
+                action: _,
+            } => {
+                log::info!("Use SQLite database");
+                log::info!("- path: {}", database_path.display());
+                log::info!("- pool: {:?}", pool);
+
+                let options = SqliteConnectOptions::new()
+                    .filename(&database_path)
+                    .create_if_missing(true)
+                    .journal_mode(SqliteJournalMode::Wal);
+
+                let pool = pool.pool_options::<Sqlite>().connect_with(options).await?;
+                Ok(Arc::new(SqliteDbClient::new(pool).into()))
+            }
+            Database::InMemory => {
+                log::info!("Use in-memory database");
+                Ok(Arc::new(InMemoryDbClient::new().into()))
             }
         }
     }
@@ -0,0 +1,257 @@
+use std::ops::RangeInclusive;
+
+use chrono::{DateTime, Utc};
+use did::transaction::TransactionReceiptLog;
+use did::{Block, BlockchainBlockInfo, H256, Transaction, TransactionReceipt};
+
+use super::in_memory_db_client::InMemoryDbClient;
+use super::postgres_db_client::PostgresDbClient;
+use super::sqlite_db_client::SqliteDbClient;
+use super::{
+    AccountBalance, AddressTransactionsQuery, CertifiedBlock, ChainReorg, DatabaseClient,
+    DiscardedBlock, LogsQuery,
+};
+
+/// Calls the same method on whichever backend the client wraps
+macro_rules! dispatch {
+    ($self:ident, $client:ident => $call:expr) => {
+        match $self {
+            AnyDbClient::Postgres($client) => $call.await,
+            AnyDbClient::Sqlite($client) => $call.await,
+            AnyDbClient::InMemory($client) => $call.await,
+        }
+    };
+}
+
+/// A blockchain client for any of the supported database backends,
+/// selected at runtime from the configuration
+#[derive(Clone)]
+pub enum AnyDbClient {
+    Postgres(PostgresDbClient),
+    Sqlite(SqliteDbClient),
+    InMemory(InMemoryDbClient),
+}
+
+impl From<PostgresDbClient> for AnyDbClient {
+    fn from(client: PostgresDbClient) -> Self {
+        Self::Postgres(client)
+    }
+}
+
+impl From<SqliteDbClient> for AnyDbClient {
+    fn from(client: SqliteDbClient) -> Self {
+        Self::Sqlite(client)
+    }
+}
+
+impl From<InMemoryDbClient> for AnyDbClient {
+    fn from(client: InMemoryDbClient) -> Self {
+        Self::InMemory(client)
+    }
+}
+
+impl DatabaseClient for AnyDbClient {
+    async fn init(&self, block: Option<Block<H256>>, reset_database: bool) -> anyhow::Result<()> {
+        dispatch!(self, client => client.init(block, reset_database))
+    }
+
+    async fn clear(&self) -> anyhow::Result<()> {
+        dispatch!(self, client => client.clear())
+    }
+
+    async fn get_block_by_number(&self, block_number: u64) -> anyhow::Result<Block<H256>> {
+        dispatch!(self, client => client.get_block_by_number(block_number))
+    }
+
+    async fn find_block_by_hash(&self, block_hash: H256) -> anyhow::Result<Option<Block<H256>>> {
+        dispatch!(self, client => client.find_block_by_hash(block_hash))
+    }
+
+    async fn find_first_block_from(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Option<Block<H256>>> {
+        dispatch!(self, client => client.find_first_block_from(block_number))
+    }
+
+    async fn get_full_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Block<Transaction>> {
+        dispatch!(self, client => client.get_full_block_by_number(block_number))
+    }
+
+    async fn get_blocks_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<Block<H256>>> {
+        dispatch!(self, client => client.get_blocks_by_range(from_block, to_block))
+    }
+
+    async fn get_full_blocks_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<Block<Transaction>>> {
+        dispatch!(self, client => client.get_full_blocks_by_range(from_block, to_block))
+    }
+
+    async fn insert_block_data(
+        &self,
+        blocks: &[Block<H256>],
+        transactions: &[Transaction],
+        receipts: &[TransactionReceipt],
+    ) -> anyhow::Result<()> {
+        dispatch!(self, client => client.insert_block_data(blocks, transactions, receipts))
+    }
+
+    async fn insert_certified_block_data(&self, response: CertifiedBlock) -> anyhow::Result<()> {
+        dispatch!(self, client => client.insert_certified_block_data(response))
+    }
+
+    async fn get_last_certified_block_data(&self) -> anyhow::Result<CertifiedBlock> {
+        dispatch!(self, client => client.get_last_certified_block_data())
+    }
+
+    async fn get_genesis_balances(&self) -> anyhow::Result<Option<Vec<AccountBalance>>> {
+        dispatch!(self, client => client.get_genesis_balances())
+    }
+
+    async fn insert_genesis_balances(
+        &self,
+        genesis_balances: &[AccountBalance],
+    ) -> anyhow::Result<()> {
+        dispatch!(self, client => client.insert_genesis_balances(genesis_balances))
+    }
+
+    async fn get_chain_id(&self) -> anyhow::Result<Option<u64>> {
+        dispatch!(self, client => client.get_chain_id())
+    }
+
+    async fn insert_chain_id(&self, chain_id: u64) -> anyhow::Result<()> {
+        dispatch!(self, client => client.insert_chain_id(chain_id))
+    }
+
+    async fn get_genesis_block_hash(&self) -> anyhow::Result<Option<H256>> {
+        dispatch!(self, client => client.get_genesis_block_hash())
+    }
+
+    async fn set_genesis_block_hash(&self, hash: H256) -> anyhow::Result<()> {
+        dispatch!(self, client => client.set_genesis_block_hash(hash))
+    }
+
+    async fn find_transaction(&self, tx_hash: H256) -> anyhow::Result<Option<Transaction>> {
+        dispatch!(self, client => client.find_transaction(tx_hash))
+    }
+
+    async fn find_transaction_receipt(
+        &self,
+        tx_hash: H256,
+    ) -> anyhow::Result<Option<TransactionReceipt>> {
+        dispatch!(self, client => client.find_transaction_receipt(tx_hash))
+    }
+
+    async fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Vec<TransactionReceipt>> {
+        dispatch!(self, client => client.get_block_receipts(block_number))
+    }
+
+    async fn get_receipts_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<TransactionReceipt>> {
+        dispatch!(self, client => client.get_receipts_by_range(from_block, to_block))
+    }
+
+    async fn get_logs(&self, query: &LogsQuery) -> anyhow::Result<Vec<TransactionReceiptLog>> {
+        dispatch!(self, client => client.get_logs(query))
+    }
+
+    async fn get_transactions_by_address(
+        &self,
+        query: &AddressTransactionsQuery,
+    ) -> anyhow::Result<Vec<Transaction>> {
+        dispatch!(self, client => client.get_transactions_by_address(query))
+    }
+
+    async fn get_latest_block_number(&self) -> anyhow::Result<Option<u64>> {
+        dispatch!(self, client => client.get_latest_block_number())
+    }
+
+    async fn get_earliest_block_number(&self) -> anyhow::Result<u64> {
+        dispatch!(self, client => client.get_earliest_block_number())
+    }
+
+    async fn get_missing_block_ranges(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<RangeInclusive<u64>>> {
+        dispatch!(self, client => client.get_missing_block_ranges(from_block, to_block))
+    }
+
+    async fn get_orphan_transaction_block_numbers(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<u64>> {
+        dispatch!(self, client => client.get_orphan_transaction_block_numbers(from_block, to_block))
+    }
+
+    async fn delete_block_transactions(&self, block_numbers: &[u64]) -> anyhow::Result<()> {
+        dispatch!(self, client => client.delete_block_transactions(block_numbers))
+    }
+
+    async fn discard_blocks_from(&self, start_from: u64, reason: &str) -> anyhow::Result<()> {
+        dispatch!(self, client => client.discard_blocks_from(start_from, reason))
+    }
+
+    async fn discard_reorged_blocks(&self, reorg: &ChainReorg) -> anyhow::Result<()> {
+        dispatch!(self, client => client.discard_reorged_blocks(reorg))
+    }
+
+    async fn find_discarded_block_by_hash(
+        &self,
+        block_hash: H256,
+    ) -> anyhow::Result<Option<DiscardedBlock>> {
+        dispatch!(self, client => client.find_discarded_block_by_hash(block_hash))
+    }
+
+    async fn get_discarded_blocks(
+        &self,
+        since: DateTime<Utc>,
+        after: Option<H256>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<DiscardedBlock>> {
+        dispatch!(self, client => client.get_discarded_blocks(since, after, limit))
+    }
+
+    async fn prune_blocks_before(
+        &self,
+        before_block: u64,
+        limit: u64,
+        headers_only: bool,
+    ) -> anyhow::Result<Option<u64>> {
+        dispatch!(self, client => client.prune_blocks_before(before_block, limit, headers_only))
+    }
+
+    async fn prune_discarded_blocks(
+        &self,
+        discarded_before: DateTime<Utc>,
+        limit: u64,
+    ) -> anyhow::Result<u64> {
+        dispatch!(self, client => client.prune_discarded_blocks(discarded_before, limit))
+    }
+
+    async fn get_block_info(&self) -> anyhow::Result<Option<BlockchainBlockInfo>> {
+        dispatch!(self, client => client.get_block_info())
+    }
+
+    async fn set_block_info(&self, info: BlockchainBlockInfo) -> anyhow::Result<()> {
+        dispatch!(self, client => client.set_block_info(info))
+    }
+}
@@ -0,0 +1,732 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::{DateTime, Utc};
+use did::transaction::TransactionReceiptLog;
+use did::{Block, BlockchainBlockInfo, H256, Transaction, TransactionReceipt};
+
+use super::{
+    AccountBalance, AddressTransactionsQuery, CertifiedBlock, ChainReorg, DatabaseClient,
+    DiscardedBlock, LogsBlockFilter, LogsQuery, REORG_DISCARD_REASON, TransactionDirection,
+    TransactionPosition, created_contract_address,
+};
+
+/// A blockchain client which keeps all the data in memory.
+/// The data is lost when the last clone of the client is dropped.
+#[derive(Clone, Default)]
+pub struct InMemoryDbClient {
+    state: Arc<RwLock<InMemoryDbState>>,
+}
+
+#[derive(Default)]
+struct InMemoryDbState {
+    blocks: BTreeMap<u64, Block<H256>>,
+    block_numbers_by_hash: HashMap<H256, u64>,
+    transactions: HashMap<H256, Transaction>,
+    transactions_by_block: BTreeMap<u64, Vec<H256>>,
+    receipts: HashMap<H256, TransactionReceipt>,
+    receipts_by_block: BTreeMap<u64, Vec<H256>>,
+    certified_blocks: BTreeMap<u64, CertifiedBlock>,
+    discarded_blocks: HashMap<H256, DiscardedBlock>,
+    genesis_balances: Option<Vec<AccountBalance>>,
+    chain_id: Option<u64>,
+    genesis_block_hash: Option<H256>,
+    block_info: Option<BlockchainBlockInfo>,
+}
+
+impl InMemoryDbClient {
+    /// Create a new empty in-memory blockchain client
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, InMemoryDbState> {
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, InMemoryDbState> {
+        self.state.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Delete the blocks starting with `start_from` and move them to the discarded blocks,
+    /// along with the reorganization details if they were replaced by a reorganization
+    fn discard_tail(&self, start_from: u64, reason: &str, reorg: Option<&ChainReorg>) {
+        log::warn!("Discarding blocks starting with {start_from}");
+
+        let mut state = self.write();
+
+        let blocks = state.blocks.split_off(&start_from);
+        let tx_hashes = state.transactions_by_block.split_off(&start_from);
+        let receipt_hashes = state.receipts_by_block.split_off(&start_from);
+        state
+            .certified_blocks
+            .retain(|number, _| *number < start_from);
+
+        for hash in receipt_hashes.into_values().flatten() {
+            state.receipts.remove(&hash);
+        }
+
+        let tx_by_hash: HashMap<_, _> = tx_hashes
+            .into_values()
+            .flatten()
+            .filter_map(|hash| state.transactions.remove_entry(&hash))
+            .collect();
+
+        let timestamp = Utc::now();
+        for block in blocks.into_values() {
+            state.block_numbers_by_hash.remove(&block.hash);
+            let block_number = block.number.as_u64();
+
+            let txs = block
+                .transactions
+                .iter()
+                .filter_map(|h| tx_by_hash.get(h).cloned())
+                .collect();
+            let Some(block) = block
+                .into_full_block(txs)
+                .inspect_err(|e| {
+                    log::warn!("failed to build full block from txs while discarding: {e}")
+                })
+                .ok()
+            else {
+                continue;
+            };
+
+            state.discarded_blocks.insert(
+                block.hash.clone(),
+                DiscardedBlock {
+                    block,
+                    reason: reason.to_owned(),
+                    timestamp,
+                    reorg_depth: reorg.map(|reorg| reorg.depth),
+                    new_hash: reorg
+                        .and_then(|reorg| reorg.new_hashes.get(&block_number))
+                        .cloned(),
+                },
+            );
+        }
+    }
+}
+
+impl InMemoryDbState {
+    /// Returns the receipts of the block ordered by transaction index
+    fn block_receipts(&self, block_number: u64) -> Vec<&TransactionReceipt> {
+        let mut receipts: Vec<_> = self
+            .receipts_by_block
+            .get(&block_number)
+            .into_iter()
+            .flatten()
+            .filter_map(|hash| self.receipts.get(hash))
+            .collect();
+
+        receipts.sort_by_key(|receipt| receipt.transaction_index.as_u64());
+        receipts
+    }
+}
+
+impl DatabaseClient for InMemoryDbClient {
+    async fn init(&self, block: Option<Block<H256>>, reset_database: bool) -> anyhow::Result<()> {
+        if let Some(_latest_block_number) = self.get_latest_block_number().await? {
+            if let Some(block) = &block {
+                if !self.check_if_same_genesis_block_hash(block).await? {
+                    if reset_database {
+                        self.clear().await?;
+                    } else {
+                        return Err(anyhow::anyhow!(
+                            "The block hash in the database is different from the one in the block"
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Some(block) = block {
+            self.set_genesis_block_hash(block.hash).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn clear(&self) -> anyhow::Result<()> {
+        log::warn!("In-memory tables are being cleared");
+
+        let mut state = self.write();
+        *state = InMemoryDbState {
+            discarded_blocks: std::mem::take(&mut state.discarded_blocks),
+            ..Default::default()
+        };
+
+        Ok(())
+    }
+
+    async fn get_block_by_number(&self, block: u64) -> anyhow::Result<Block<H256>> {
+        self.read()
+            .blocks
+            .get(&block)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Error getting block {}: not found", block))
+    }
+
+    async fn find_block_by_hash(&self, block_hash: H256) -> anyhow::Result<Option<Block<H256>>> {
+        let state = self.read();
+        Ok(state
+            .block_numbers_by_hash
+            .get(&block_hash)
+            .and_then(|number| state.blocks.get(number))
+            .cloned())
+    }
+
+    async fn find_first_block_from(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Option<Block<H256>>> {
+        Ok(self
+            .read()
+            .blocks
+            .range(block_number..)
+            .next()
+            .map(|(_, block)| block.clone()))
+    }
+
+    async fn get_full_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Block<Transaction>> {
+        let block = self.get_block_by_number(block_number).await?;
+
+        let transactions = {
+            let state = self.read();
+            state
+                .transactions_by_block
+                .get(&block_number)
+                .into_iter()
+                .flatten()
+                .filter_map(|hash| state.transactions.get(hash))
+                .cloned()
+                .collect()
+        };
+
+        Ok(block.into_full_block(transactions)?)
+    }
+
+    async fn get_blocks_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<Block<H256>>> {
+        if from_block > to_block {
+            return Ok(vec![]);
+        }
+
+        Ok(self
+            .read()
+            .blocks
+            .range(from_block..=to_block)
+            .map(|(_, block)| block.clone())
+            .collect())
+    }
+
+    async fn get_full_blocks_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<Block<Transaction>>> {
+        if from_block > to_block {
+            return Ok(vec![]);
+        }
+
+        let state = self.read();
+        let mut blocks = vec![];
+        for (block_number, block) in state.blocks.range(from_block..=to_block) {
+            let transactions = state
+                .transactions_by_block
+                .get(block_number)
+                .into_iter()
+                .flatten()
+                .filter_map(|hash| state.transactions.get(hash))
+                .cloned()
+                .collect();
+            blocks.push(block.clone().into_full_block(transactions)?);
+        }
+
+        Ok(blocks)
+    }
+
+    async fn insert_block_data(
+        &self,
+        blocks: &[Block<H256>],
+        transactions: &[Transaction],
+        receipts: &[TransactionReceipt],
+    ) -> anyhow::Result<()> {
+        if !blocks.is_empty() {
+            log::info!(
+                "Insert block data for blocks in range {} to {}",
+                blocks[0].number,
+                blocks[blocks.len() - 1].number
+            );
+        };
+
+        let mut state = self.write();
+
+        // Check everything before the first write, so that a failed insertion leaves no partial data
+        for block in blocks {
+            let block_id = block.number.as_u64();
+            if state.blocks.contains_key(&block_id)
+                || state.block_numbers_by_hash.contains_key(&block.hash)
+            {
+                anyhow::bail!("Error inserting block {}: already exists", block_id);
+            }
+        }
+        for txn in transactions {
+            if txn.block_number.is_none() {
+                anyhow::bail!(
+                    "Error inserting transaction {}: block number not found",
+                    txn.hash
+                );
+            }
+            if state.transactions.contains_key(&txn.hash) {
+                anyhow::bail!("Error inserting transaction {}: already exists", txn.hash);
+            }
+        }
+        for receipt in receipts {
+            if state.receipts.contains_key(&receipt.transaction_hash) {
+                anyhow::bail!(
+                    "Error inserting receipt {}: already exists",
+                    receipt.transaction_hash
+                );
+            }
+        }
+
+        for block in blocks {
+            let block_id = block.number.as_u64();
+            state
+                .block_numbers_by_hash
+                .insert(block.hash.clone(), block_id);
+            state.blocks.insert(block_id, block.clone());
+        }
+
+        for txn in transactions {
+            let block_number = txn.block_number.map(|n| n.as_u64()).unwrap_or_default();
+            state
+                .transactions_by_block
+                .entry(block_number)
+                .or_default()
+                .push(txn.hash.clone());
+            state.transactions.insert(txn.hash.clone(), txn.clone());
+        }
+
+        for receipt in receipts {
+            state
+                .receipts_by_block
+                .entry(receipt.block_number.as_u64())
+                .or_default()
+                .push(receipt.transaction_hash.clone());
+            state
+                .receipts
+                .insert(receipt.transaction_hash.clone(), receipt.clone());
+        }
+
+        Ok(())
+    }
+
+    async fn insert_certified_block_data(&self, response: CertifiedBlock) -> anyhow::Result<()> {
+        let block_id = response.data.number.as_u64();
+        self.write().certified_blocks.insert(block_id, response);
+
+        Ok(())
+    }
+
+    async fn get_last_certified_block_data(&self) -> anyhow::Result<CertifiedBlock> {
+        self.read()
+            .certified_blocks
+            .last_key_value()
+            .map(|(_, certified_block)| certified_block.clone())
+            .ok_or_else(|| anyhow::anyhow!("Error getting last certified block: not found"))
+    }
+
+    async fn get_latest_block_number(&self) -> anyhow::Result<Option<u64>> {
+        Ok(self
+            .read()
+            .blocks
+            .last_key_value()
+            .map(|(number, _)| *number))
+    }
+
+    async fn get_earliest_block_number(&self) -> anyhow::Result<u64> {
+        self.read()
+            .blocks
+            .first_key_value()
+            .map(|(number, _)| *number)
+            .ok_or_else(|| anyhow::anyhow!("Error getting earliest block number: no blocks"))
+    }
+
+    async fn get_missing_block_ranges(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<RangeInclusive<u64>>> {
+        if from_block > to_block {
+            return Ok(vec![]);
+        }
+
+        let state = self.read();
+        let mut ranges = vec![];
+        let mut next_expected = from_block;
+        for number in state.blocks.range(from_block..=to_block).map(|(n, _)| *n) {
+            if number > next_expected {
+                ranges.push(next_expected..=number - 1);
+            }
+            next_expected = number + 1;
+        }
+        if next_expected <= to_block {
+            ranges.push(next_expected..=to_block);
+        }
+
+        Ok(ranges)
+    }
+
+    async fn get_orphan_transaction_block_numbers(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<u64>> {
+        if from_block > to_block {
+            return Ok(vec![]);
+        }
+
+        let state = self.read();
+        Ok(state
+            .transactions_by_block
+            .range(from_block..=to_block)
+            .map(|(number, _)| *number)
+            .filter(|number| !state.blocks.contains_key(number))
+            .collect())
+    }
+
+    async fn delete_block_transactions(&self, block_numbers: &[u64]) -> anyhow::Result<()> {
+        let mut state = self.write();
+        for block_number in block_numbers {
+            for hash in state
+                .transactions_by_block
+                .remove(block_number)
+                .unwrap_or_default()
+            {
+                state.transactions.remove(&hash);
+            }
+            for hash in state
+                .receipts_by_block
+                .remove(block_number)
+                .unwrap_or_default()
+            {
+                state.receipts.remove(&hash);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_genesis_balances(&self) -> anyhow::Result<Option<Vec<AccountBalance>>> {
+        Ok(self.read().genesis_balances.clone())
+    }
+
+    async fn insert_genesis_balances(
+        &self,
+        genesis_balances: &[AccountBalance],
+    ) -> anyhow::Result<()> {
+        let mut state = self.write();
+        if state.genesis_balances.is_some() {
+            anyhow::bail!("Error inserting genesis balances: already exist");
+        }
+        state.genesis_balances = Some(genesis_balances.to_vec());
+
+        Ok(())
+    }
+
+    async fn get_chain_id(&self) -> anyhow::Result<Option<u64>> {
+        Ok(self.read().chain_id)
+    }
+
+    async fn insert_chain_id(&self, chain_id: u64) -> anyhow::Result<()> {
+        let mut state = self.write();
+        if state.chain_id.is_some() {
+            anyhow::bail!("Error inserting chain id: already exists");
+        }
+        state.chain_id = Some(chain_id);
+
+        Ok(())
+    }
+
+    async fn get_genesis_block_hash(&self) -> anyhow::Result<Option<H256>> {
+        Ok(self.read().genesis_block_hash.clone())
+    }
+
+    async fn set_genesis_block_hash(&self, hash: H256) -> anyhow::Result<()> {
+        self.write().genesis_block_hash = Some(hash);
+        Ok(())
+    }
+
+    async fn find_transaction(&self, tx_hash: H256) -> anyhow::Result<Option<Transaction>> {
+        Ok(self.read().transactions.get(&tx_hash).cloned())
+    }
+
+    async fn find_transaction_receipt(
+        &self,
+        tx_hash: H256,
+    ) -> anyhow::Result<Option<TransactionReceipt>> {
+        Ok(self.read().receipts.get(&tx_hash).cloned())
+    }
+
+    async fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Vec<TransactionReceipt>> {
+        Ok(self
+            .read()
+            .block_receipts(block_number)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    async fn get_receipts_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<TransactionReceipt>> {
+        if from_block > to_block {
+            return Ok(vec![]);
+        }
+
+        let state = self.read();
+        Ok(state
+            .receipts_by_block
+            .range(from_block..=to_block)
+            .flat_map(|(block_number, _)| state.block_receipts(*block_number))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_logs(&self, query: &LogsQuery) -> anyhow::Result<Vec<TransactionReceiptLog>> {
+        let state = self.read();
+
+        let block_numbers: Vec<u64> = match &query.block_filter {
+            LogsBlockFilter::Range { from, to } if from <= to => state
+                .receipts_by_block
+                .range(from..=to)
+                .map(|(n, _)| *n)
+                .collect(),
+            LogsBlockFilter::Range { .. } => vec![],
+            LogsBlockFilter::Hash(_) => state.receipts_by_block.keys().copied().collect(),
+        };
+
+        let matches = |log: &TransactionReceiptLog| {
+            (query.addresses.is_empty() || query.addresses.contains(&log.address))
+                && query.topics.iter().enumerate().all(|(position, topics)| {
+                    topics.is_empty()
+                        || log
+                            .topics
+                            .get(position)
+                            .is_some_and(|topic| topics.contains(topic))
+                })
+        };
+
+        let logs = block_numbers
+            .into_iter()
+            .flat_map(|block_number| state.block_receipts(block_number))
+            .filter(|receipt| match &query.block_filter {
+                LogsBlockFilter::Hash(hash) => &receipt.block_hash == hash,
+                LogsBlockFilter::Range { .. } => true,
+            })
+            .flat_map(|receipt| receipt.logs.iter())
+            .filter(|log| matches(log))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+
+        Ok(logs)
+    }
+
+    async fn get_transactions_by_address(
+        &self,
+        query: &AddressTransactionsQuery,
+    ) -> anyhow::Result<Vec<Transaction>> {
+        if query.from_block > query.to_block {
+            return Ok(vec![]);
+        }
+
+        let state = self.read();
+
+        let matches = |tx: &Transaction| {
+            let from = tx.from == query.address;
+            let to = tx.to.as_ref() == Some(&query.address)
+                || created_contract_address(tx).as_ref() == Some(&query.address);
+            match query.direction {
+                TransactionDirection::Any => from || to,
+                TransactionDirection::From => from,
+                TransactionDirection::To => to,
+            }
+        };
+        let is_after = |tx: &Transaction| match query.after {
+            Some(after) if query.descending => TransactionPosition::of(tx) < after,
+            Some(after) => TransactionPosition::of(tx) > after,
+            None => true,
+        };
+
+        let mut transactions: Vec<_> = state
+            .transactions_by_block
+            .range(query.from_block..=query.to_block)
+            .flat_map(|(_, hashes)| hashes)
+            .filter_map(|hash| state.transactions.get(hash))
+            .filter(|tx| matches(tx) && is_after(tx))
+            .collect();
+
+        transactions.sort_by_key(|tx| TransactionPosition::of(tx));
+        if query.descending {
+            transactions.reverse();
+        }
+
+        Ok(transactions
+            .into_iter()
+            .take(query.limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn discard_blocks_from(&self, start_from: u64, reason: &str) -> anyhow::Result<()> {
+        self.discard_tail(start_from, reason, None);
+
+        Ok(())
+    }
+
+    async fn discard_reorged_blocks(&self, reorg: &ChainReorg) -> anyhow::Result<()> {
+        self.discard_tail(
+            reorg.first_discarded_block,
+            REORG_DISCARD_REASON,
+            Some(reorg),
+        );
+
+        Ok(())
+    }
+
+    async fn find_discarded_block_by_hash(
+        &self,
+        block_hash: H256,
+    ) -> anyhow::Result<Option<DiscardedBlock>> {
+        Ok(self.read().discarded_blocks.get(&block_hash).cloned())
+    }
+
+    async fn get_discarded_blocks(
+        &self,
+        since: DateTime<Utc>,
+        after: Option<H256>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<DiscardedBlock>> {
+        let state = self.read();
+        let mut discarded_blocks = state
+            .discarded_blocks
+            .values()
+            .filter(|discarded| match &after {
+                Some(after) => (discarded.timestamp, &discarded.block.hash) > (since, after),
+                None => discarded.timestamp >= since,
+            })
+            .collect::<Vec<_>>();
+        discarded_blocks
+            .sort_by(|a, b| (a.timestamp, &a.block.hash).cmp(&(b.timestamp, &b.block.hash)));
+
+        Ok(discarded_blocks.into_iter().take(limit).cloned().collect())
+    }
+
+    async fn prune_blocks_before(
+        &self,
+        before_block: u64,
+        limit: u64,
+        headers_only: bool,
+    ) -> anyhow::Result<Option<u64>> {
+        let mut state = self.write();
+
+        // With headers only, the pruned blocks are the oldest ones still holding transactions
+        let last_block = if headers_only {
+            state
+                .transactions_by_block
+                .range(..before_block)
+                .take(limit as usize)
+                .last()
+                .map(|(number, _)| *number)
+        } else {
+            state
+                .blocks
+                .range(..before_block)
+                .take(limit as usize)
+                .last()
+                .map(|(number, _)| *number)
+        };
+
+        let Some(last_block) = last_block else {
+            return Ok(None);
+        };
+
+        log::info!("Pruning blocks up to {last_block}");
+
+        let kept = state.transactions_by_block.split_off(&(last_block + 1));
+        let pruned = std::mem::replace(&mut state.transactions_by_block, kept);
+        for hash in pruned.into_values().flatten() {
+            state.transactions.remove(&hash);
+        }
+
+        let kept = state.receipts_by_block.split_off(&(last_block + 1));
+        let pruned = std::mem::replace(&mut state.receipts_by_block, kept);
+        for hash in pruned.into_values().flatten() {
+            state.receipts.remove(&hash);
+        }
+
+        if headers_only {
+            for block in state
+                .blocks
+                .range_mut(..=last_block)
+                .map(|(_, block)| block)
+            {
+                block.transactions.clear();
+            }
+        } else {
+            let kept = state.blocks.split_off(&(last_block + 1));
+            let pruned = std::mem::replace(&mut state.blocks, kept);
+            for block in pruned.into_values() {
+                state.block_numbers_by_hash.remove(&block.hash);
+            }
+        }
+
+        Ok(Some(last_block))
+    }
+
+    async fn prune_discarded_blocks(
+        &self,
+        discarded_before: DateTime<Utc>,
+        limit: u64,
+    ) -> anyhow::Result<u64> {
+        let mut state = self.write();
+
+        let pruned: Vec<_> = state
+            .discarded_blocks
+            .iter()
+            .filter(|(_, discarded)| discarded.timestamp < discarded_before)
+            .map(|(hash, _)| hash.clone())
+            .take(limit as usize)
+            .collect();
+        for hash in &pruned {
+            state.discarded_blocks.remove(hash);
+        }
+
+        Ok(pruned.len() as u64)
+    }
+
+    async fn get_block_info(&self) -> anyhow::Result<Option<BlockchainBlockInfo>> {
+        Ok(self.read().block_info.clone())
+    }
+
+    async fn set_block_info(&self, info: BlockchainBlockInfo) -> anyhow::Result<()> {
+        self.write().block_info = Some(info);
+
+        Ok(())
+    }
+}
@@ -1,8 +1,16 @@
+pub mod any_db_client;
+pub mod in_memory_db_client;
 pub mod postgres_db_client;
+pub mod sql_db_client;
+pub mod sqlite_db_client;
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 
 use chrono::{DateTime, Utc};
 use did::certified::CertifiedResult;
-use did::{Block, BlockchainBlockInfo, H160, H256, Transaction, U256};
+use did::transaction::TransactionReceiptLog;
+use did::{Block, BlockchainBlockInfo, H160, H256, Transaction, TransactionReceipt, U256};
 use serde::{Deserialize, Serialize};
 
 /// Account balance
@@ -24,10 +32,91 @@ impl<D> DataContainer<D> {
     }
 }
 
+/// Block filter of a logs query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogsBlockFilter {
+    /// Inclusive range of block numbers
+    Range { from: u64, to: u64 },
+    /// The block with the given hash
+    Hash(H256),
+}
+
+/// Query for the logs stored in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogsQuery {
+    pub block_filter: LogsBlockFilter,
+    /// Addresses of the contracts that emitted the logs; empty matches any address
+    pub addresses: Vec<H160>,
+    /// Accepted topics by position; an empty position matches any topic
+    pub topics: Vec<Vec<H256>>,
+    /// Maximum number of logs to return; all the matching logs are returned if missing
+    pub limit: Option<usize>,
+}
+
+/// Direction of the transactions of an address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransactionDirection {
+    /// Transactions sent from or to the address, or creating a contract at it
+    #[default]
+    Any,
+    /// Transactions sent from the address
+    From,
+    /// Transactions sent to the address, or creating a contract at it
+    To,
+}
+
+/// Position of a transaction in the blockchain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionPosition {
+    pub block_number: u64,
+    pub transaction_index: u64,
+}
+
+impl TransactionPosition {
+    /// Returns the position of a mined transaction
+    pub fn of(transaction: &Transaction) -> Self {
+        Self {
+            block_number: transaction.block_number.unwrap_or_default().as_u64(),
+            transaction_index: transaction.transaction_index.unwrap_or_default().as_u64(),
+        }
+    }
+}
+
+/// Query for the transactions of an address stored in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressTransactionsQuery {
+    pub address: H160,
+    pub direction: TransactionDirection,
+    /// Inclusive range of block numbers
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Whether the transactions are returned from the newest to the oldest
+    pub descending: bool,
+    /// Position of the last transaction of the previous page; the transactions
+    /// up to it, in the query order, are skipped
+    pub after: Option<TransactionPosition>,
+    /// Maximum number of transactions to return
+    pub limit: usize,
+}
+
+/// Returns the address of the contract created by the transaction, if it is a contract creation
+pub fn created_contract_address(transaction: &Transaction) -> Option<H160> {
+    if transaction.to.is_some() {
+        return None;
+    }
+
+    let sender = alloy::primitives::Address::from(transaction.from.clone());
+    Some(sender.create(transaction.nonce.0.saturating_to()).into())
+}
+
 /// The genesis balances key in the key value store
 const GENESIS_BALANCES_KEY: &str = "genesis_balances";
 /// The chain id key in the key value store
 const CHAIN_ID_KEY: &str = "chain_id";
+/// The genesis block hash key in the key value store
+const GENESIS_BLOCK_HASH_KEY: &str = "genesis_block_hash";
 /// The blockchain block info key in the key value store
 const BLOCKCHAIN_BLOCK_INFO_KEY: &str = "blockchain_block_info";
 
@@ -58,23 +147,90 @@ pub trait DatabaseClient: Send + Sync {
         }
     }
 
+    /// Returns whether the genesis block hash corresponds to the one in the db.
+    /// The hash recorded by `init` is compared first, as the genesis block may have been pruned.
+    fn check_if_same_genesis_block_hash(
+        &self,
+        genesis_block: &Block<H256>,
+    ) -> impl Future<Output = anyhow::Result<bool>> + Send {
+        async {
+            match self.get_genesis_block_hash().await? {
+                Some(hash) => Ok(genesis_block.hash == hash),
+                None => self.check_if_same_block_hash(genesis_block).await,
+            }
+        }
+    }
+
     /// Get a block from the database
     fn get_block_by_number(
         &self,
         block_number: u64,
     ) -> impl Future<Output = anyhow::Result<Block<H256>>> + Send;
 
+    /// Get a block from the database, if present
+    fn find_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> impl Future<Output = anyhow::Result<Option<Block<H256>>>> + Send {
+        async move {
+            let blocks = self.get_blocks_by_range(block_number, block_number).await?;
+            Ok(blocks.into_iter().next())
+        }
+    }
+
+    /// Get a block by its hash from the database, if present
+    fn find_block_by_hash(
+        &self,
+        block_hash: H256,
+    ) -> impl Future<Output = anyhow::Result<Option<Block<H256>>>> + Send;
+
+    /// Get the first block with a number not lower than `block_number` from the database, if any
+    fn find_first_block_from(
+        &self,
+        block_number: u64,
+    ) -> impl Future<Output = anyhow::Result<Option<Block<H256>>>> + Send;
+
+    /// Get a block by its hash from the database
+    fn get_block_by_hash(
+        &self,
+        block_hash: H256,
+    ) -> impl Future<Output = anyhow::Result<Block<H256>>> + Send {
+        async move {
+            let hex_block_hash = block_hash.to_hex_str();
+            self.find_block_by_hash(block_hash)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Block {} not found", hex_block_hash))
+        }
+    }
+
     /// Get a block from the database
     fn get_full_block_by_number(
         &self,
         block_number: u64,
     ) -> impl Future<Output = anyhow::Result<Block<Transaction>>> + Send;
 
-    /// Insert block data; this includes transactions and the blocks
+    /// Get the blocks with number between `from_block` and `to_block` (inclusive),
+    /// ordered by number; blocks missing from the database are skipped
+    fn get_blocks_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> impl Future<Output = anyhow::Result<Vec<Block<H256>>>> + Send;
+
+    /// Get the blocks with number between `from_block` and `to_block` (inclusive)
+    /// together with their transactions, ordered by number; blocks missing from the database are skipped
+    fn get_full_blocks_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> impl Future<Output = anyhow::Result<Vec<Block<Transaction>>>> + Send;
+
+    /// Insert block data; this includes transactions, their receipts and the blocks
     fn insert_block_data(
         &self,
         blocks: &[Block<H256>],
         transactions: &[Transaction],
+        receipts: &[TransactionReceipt],
     ) -> impl Future<Output = anyhow::Result<()>> + Send;
 
     /// Insert certified block data
@@ -105,11 +261,64 @@ pub trait DatabaseClient: Send + Sync {
     /// Insert chain_id
     fn insert_chain_id(&self, chain_id: u64) -> impl Future<Output = anyhow::Result<()>> + Send;
 
+    /// Get the hash of the genesis block the database was initialized with
+    fn get_genesis_block_hash(&self) -> impl Future<Output = anyhow::Result<Option<H256>>> + Send;
+
+    /// Set the hash of the genesis block the database was initialized with
+    fn set_genesis_block_hash(&self, hash: H256)
+    -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Get a transaction from the database, if present
+    fn find_transaction(
+        &self,
+        tx_hash: H256,
+    ) -> impl Future<Output = anyhow::Result<Option<Transaction>>> + Send;
+
     /// Get a transaction from the database
     fn get_transaction(
         &self,
         tx_hash: H256,
-    ) -> impl Future<Output = anyhow::Result<Transaction>> + Send;
+    ) -> impl Future<Output = anyhow::Result<Transaction>> + Send {
+        async move {
+            let hex_tx_hash = tx_hash.to_hex_str();
+            self.find_transaction(tx_hash)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Transaction {} not found", hex_tx_hash))
+        }
+    }
+
+    /// Get a transaction receipt from the database, if present
+    fn find_transaction_receipt(
+        &self,
+        tx_hash: H256,
+    ) -> impl Future<Output = anyhow::Result<Option<TransactionReceipt>>> + Send;
+
+    /// Get all the transaction receipts of a block, ordered by transaction index
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> impl Future<Output = anyhow::Result<Vec<TransactionReceipt>>> + Send;
+
+    /// Get the transaction receipts of the blocks with number between `from_block` and `to_block`
+    /// (inclusive), ordered by block number and transaction index
+    fn get_receipts_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> impl Future<Output = anyhow::Result<Vec<TransactionReceipt>>> + Send;
+
+    /// Get the logs matching the query, ordered by block number, transaction index and log index
+    fn get_logs(
+        &self,
+        query: &LogsQuery,
+    ) -> impl Future<Output = anyhow::Result<Vec<TransactionReceiptLog>>> + Send;
+
+    /// Get the transactions of an address matching the query,
+    /// ordered by block number and transaction index
+    fn get_transactions_by_address(
+        &self,
+        query: &AddressTransactionsQuery,
+    ) -> impl Future<Output = anyhow::Result<Vec<Transaction>>> + Send;
 
     /// Get the latest block number
     fn get_latest_block_number(&self) -> impl Future<Output = anyhow::Result<Option<u64>>> + Send;
@@ -117,7 +326,29 @@ pub trait DatabaseClient: Send + Sync {
     /// Get earliest block number
     fn get_earliest_block_number(&self) -> impl Future<Output = anyhow::Result<u64>> + Send;
 
-    /// Delete latest blocks starting with `start_from`, and related transactions.
+    /// Returns the inclusive ranges of block numbers between `from_block` and `to_block`
+    /// (inclusive) which have no block in the database, in ascending order
+    fn get_missing_block_ranges(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> impl Future<Output = anyhow::Result<Vec<RangeInclusive<u64>>>> + Send;
+
+    /// Returns the distinct block numbers, between `from_block` and `to_block` (inclusive),
+    /// of the transactions whose block is not in the database, in ascending order
+    fn get_orphan_transaction_block_numbers(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> impl Future<Output = anyhow::Result<Vec<u64>>> + Send;
+
+    /// Delete the transactions, receipts and logs of the given block numbers
+    fn delete_block_transactions(
+        &self,
+        block_numbers: &[u64],
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Delete latest blocks starting with `start_from`, and related transactions, receipts and logs.
     /// Deleted blocks and transactions will be preserved in 'discarded' table with
     /// the given 'reason' and timestamp.
     fn discard_blocks_from(
@@ -126,11 +357,62 @@ pub trait DatabaseClient: Send + Sync {
         reason: &str,
     ) -> impl Future<Output = anyhow::Result<()>> + Send;
 
+    /// Delete the blocks replaced by a reorganization of the source blockchain,
+    /// and related transactions, receipts and logs, as done by `discard_blocks_from`.
+    /// The discarded blocks are preserved with the reorganization depth
+    /// and the hashes of the blocks replacing them.
+    fn discard_reorged_blocks(
+        &self,
+        reorg: &ChainReorg,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Returns a discarded block by its hash, if present.
+    fn find_discarded_block_by_hash(
+        &self,
+        block_hash: H256,
+    ) -> impl Future<Output = anyhow::Result<Option<DiscardedBlock>>> + Send;
+
+    /// Returns at most `limit` discarded blocks, ordered by discard time and hash,
+    /// starting with the blocks discarded at `since`.
+    /// If `after` is set, the blocks discarded at `since` with a hash up to `after` are skipped.
+    fn get_discarded_blocks(
+        &self,
+        since: DateTime<Utc>,
+        after: Option<H256>,
+        limit: usize,
+    ) -> impl Future<Output = anyhow::Result<Vec<DiscardedBlock>>> + Send;
+
     /// Returns a discarded block by its hash.
     fn get_discarded_block_by_hash(
         &self,
         block_hash: H256,
-    ) -> impl Future<Output = anyhow::Result<DiscardedBlock>> + Send;
+    ) -> impl Future<Output = anyhow::Result<DiscardedBlock>> + Send {
+        async move {
+            let hex_block_hash = block_hash.to_hex_str();
+            self.find_discarded_block_by_hash(block_hash)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Discarded block {} not found", hex_block_hash))
+        }
+    }
+
+    /// Prunes at most `limit` blocks with number lower than `before_block`, starting from the oldest one,
+    /// deleting their transactions, receipts and logs.
+    /// Unless `headers_only` the blocks are deleted too; otherwise their transaction hashes are cleared.
+    /// Returns the number of the last pruned block, `None` if there is nothing left to prune.
+    fn prune_blocks_before(
+        &self,
+        before_block: u64,
+        limit: u64,
+        headers_only: bool,
+    ) -> impl Future<Output = anyhow::Result<Option<u64>>> + Send;
+
+    /// Deletes at most `limit` blocks discarded before `discarded_before`.
+    /// Returns the number of deleted blocks.
+    fn prune_discarded_blocks(
+        &self,
+        discarded_before: DateTime<Utc>,
+        limit: u64,
+    ) -> impl Future<Output = anyhow::Result<u64>> + Send;
 
     /// Returns block info from storage.
     ///
@@ -154,9 +436,30 @@ pub trait DatabaseClient: Send + Sync {
 }
 
 /// Discarded block with metadata.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DiscardedBlock {
     pub block: Block<Transaction>,
     pub reason: String,
     pub timestamp: DateTime<Utc>,
+    /// Number of blocks replaced by the reorganization which discarded the block
+    pub reorg_depth: Option<u64>,
+    /// Hash of the block replacing this one in the source blockchain, if known
+    pub new_hash: Option<H256>,
+}
+
+/// Reason of the blocks discarded by a reorganization of the source blockchain
+pub const REORG_DISCARD_REASON: &str = "reorg";
+
+/// Reorganization of the source blockchain, relative to the blocks in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainReorg {
+    /// The first stored block which is not part of the source blockchain;
+    /// all the stored blocks starting with this one are discarded
+    pub first_discarded_block: u64,
+    /// Number of stored blocks which are not part of the source blockchain
+    pub depth: u64,
+    /// Hashes of the source blockchain blocks by number, for the discarded block numbers
+    /// the source blockchain has already reached
+    pub new_hashes: BTreeMap<u64, H256>,
 }
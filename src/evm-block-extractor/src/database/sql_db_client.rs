@@ -0,0 +1,1052 @@
+//! The [`DatabaseClient`] implementation shared by the SQL backends.
+//!
+//! Postgres and SQLite run the same queries, apart from the few statements listed by
+//! [`SqlDialect`], so a single implementation generic over the database serves both.
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use chrono::{DateTime, Utc};
+use did::transaction::TransactionReceiptLog;
+use did::{Block, BlockchainBlockInfo, H256, Transaction, TransactionReceipt};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{
+    ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Pool, QueryBuilder, Row, Type,
+};
+
+use super::{
+    AccountBalance, AddressTransactionsQuery, BLOCKCHAIN_BLOCK_INFO_KEY, CHAIN_ID_KEY,
+    CertifiedBlock, ChainReorg, DataContainer, DatabaseClient, DiscardedBlock,
+    GENESIS_BALANCES_KEY, GENESIS_BLOCK_HASH_KEY, LogsBlockFilter, LogsQuery, REORG_DISCARD_REASON,
+    TransactionDirection, created_contract_address,
+};
+
+/// The parts of a SQL backend which differ between the databases
+pub trait SqlDialect: Database {
+    /// Name of the database, used in the logs
+    const NAME: &'static str;
+
+    /// Statements emptying every table, run in a single transaction
+    const CLEAR: &'static [&'static str];
+
+    /// Statement emptying the transactions of the blocks up to `$1` which still hold them,
+    /// written with the JSON functions of the database
+    const CLEAR_BLOCK_TRANSACTIONS: &'static str;
+
+    /// Migrations creating the schema of the database
+    fn migrator() -> &'static Migrator;
+
+    /// Number of rows changed by a statement
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+}
+
+/// A blockchain client for a SQL database
+pub struct SqlDbClient<DB: Database> {
+    pool: Pool<DB>,
+}
+
+// Not derived, as the database types do not implement `Clone`
+impl<DB: Database> Clone for SqlDbClient<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<DB: Database> SqlDbClient<DB> {
+    /// Create a new blockchain client over the given pool
+    pub fn new(pool: Pool<DB>) -> Self {
+        Self { pool }
+    }
+}
+
+impl<DB> SqlDbClient<DB>
+where
+    DB: SqlDialect,
+    DB::Connection: Migrate,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    usize: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q, 'r> i64: Encode<'q, DB> + Decode<'r, DB> + Type<DB>,
+    for<'q, 'r> String: Encode<'q, DB> + Decode<'r, DB> + Type<DB>,
+    for<'q, 'r> serde_json::Value: Encode<'q, DB> + Decode<'r, DB> + Type<DB>,
+    for<'q, 'r> DateTime<Utc>: Encode<'q, DB> + Decode<'r, DB> + Type<DB>,
+{
+    async fn fetch_key_value_data<D: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> anyhow::Result<Option<D>> {
+        let row = sqlx::query("SELECT data FROM EVM_KEY_VALUE_DATA WHERE KEY = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting value data for key {}: {:?}", key, e))?;
+
+        if let Some(row) = row {
+            from_row_value(&row, 0).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn insert_key_value_data<D: Serialize>(&self, key: &str, data: D) -> anyhow::Result<()> {
+        sqlx::query("INSERT INTO EVM_KEY_VALUE_DATA (key, data) VALUES ($1, $2)")
+            .bind(key)
+            .bind(serde_json::to_value(data)?)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error inserting value data for key {}: {:?}", key, e))
+            .map(|_| ())
+    }
+
+    async fn override_key_value_data<D: Serialize>(
+        &self,
+        key: &str,
+        data: D,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO EVM_KEY_VALUE_DATA (key, data) VALUES ($1, $2)
+            ON CONFLICT (key) DO UPDATE SET data = $2",
+        )
+        .bind(key)
+        .bind(serde_json::to_value(data)?)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error inserting value data for key {}: {:?}", key, e))
+        .map(|_| ())
+    }
+
+    /// Delete the blocks starting with `start_from` and move them to the discarded
+    /// blocks, along with the reorganization details if they were replaced by a
+    /// reorganization
+    async fn discard_tail(
+        &self,
+        start_from: u64,
+        reason: &str,
+        reorg: Option<&ChainReorg>,
+    ) -> anyhow::Result<()> {
+        log::warn!("Discarding blocks starting with {start_from}");
+
+        let mut tx = self.pool.begin().await?;
+
+        let block_rows = sqlx::query("DELETE FROM evm_block WHERE id >= $1 RETURNING data")
+            .bind(start_from as i64)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to discard tail: {e}"))?;
+
+        let blocks_with_hashes = from_rows_value::<did::Block<did::H256>, _>(&block_rows, 0)?;
+
+        let tx_rows =
+            sqlx::query("DELETE FROM evm_transaction WHERE block_number >= $1 RETURNING data")
+                .bind(start_from as i64)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to discard tail: {e}"))?;
+
+        sqlx::query("DELETE FROM evm_transaction_receipt WHERE block_number >= $1")
+            .bind(start_from as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to discard tail: {e}"))?;
+
+        sqlx::query("DELETE FROM evm_log WHERE block_number >= $1")
+            .bind(start_from as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to discard tail: {e}"))?;
+
+        let tx_by_hash: HashMap<_, _> = tx_rows
+            .into_iter()
+            .filter_map(|r| {
+                let tx = from_row_value::<did::Transaction, _>(&r, 0)
+                    .inspect_err(|e| {
+                        log::warn!("failed to decode tx data while discarding: {e}");
+                    })
+                    .ok()?;
+                Some((tx.hash.clone(), tx))
+            })
+            .collect();
+
+        let full_blocks = blocks_with_hashes.into_iter().filter_map(|b| {
+            let txs = b
+                .transactions
+                .iter()
+                .filter_map(|h| tx_by_hash.get(h).cloned())
+                .collect();
+            b.into_full_block(txs)
+                .inspect_err(|e| {
+                    log::warn!("failed to build full block from txs while discarding: {e}")
+                })
+                .ok()
+        });
+
+        let discarded_at = Utc::now();
+        for block in full_blocks {
+            let block_hash_str = block.hash.to_hex_str();
+            let reorg_depth = reorg.map(|reorg| reorg.depth as i64);
+            let new_hash = reorg
+                .and_then(|reorg| reorg.new_hashes.get(&block.number.as_u64()))
+                .map(|hash| hash.to_hex_str());
+
+            sqlx::query(
+                "INSERT INTO DISCARDED_EVM_BLOCK (id, data, reason, discarded_at, reorg_depth, new_hash)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(&block_hash_str)
+            .bind(serde_json::to_value(block)?)
+            .bind(reason)
+            .bind(discarded_at)
+            .bind(reorg_depth)
+            .bind(new_hash)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Error inserting discarded block {}: {:?}",
+                    block_hash_str,
+                    e
+                )
+            })
+            .map(|_| ())?;
+        }
+
+        sqlx::query("DELETE FROM certified_evm_block WHERE id >= $1")
+            .bind(start_from as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to discard tail: {e}"))?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+impl<DB> DatabaseClient for SqlDbClient<DB>
+where
+    DB: SqlDialect,
+    DB::Connection: Migrate,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    usize: ColumnIndex<DB::Row>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q, 'r> i64: Encode<'q, DB> + Decode<'r, DB> + Type<DB>,
+    for<'q, 'r> String: Encode<'q, DB> + Decode<'r, DB> + Type<DB>,
+    for<'q, 'r> serde_json::Value: Encode<'q, DB> + Decode<'r, DB> + Type<DB>,
+    for<'q, 'r> DateTime<Utc>: Encode<'q, DB> + Decode<'r, DB> + Type<DB>,
+{
+    async fn init(&self, block: Option<Block<H256>>, reset_database: bool) -> anyhow::Result<()> {
+        DB::migrator().run(&self.pool).await?;
+
+        if let Some(_latest_block_number) = self.get_latest_block_number().await? {
+            if let Some(block) = &block {
+                if !self.check_if_same_genesis_block_hash(block).await? {
+                    if reset_database {
+                        self.clear().await?;
+                    } else {
+                        return Err(anyhow::anyhow!(
+                            "The block hash in the database is different from the one in the block"
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Some(block) = block {
+            self.set_genesis_block_hash(block.hash).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn clear(&self) -> anyhow::Result<()> {
+        log::warn!("{} tables are being cleared", DB::NAME);
+
+        let mut tx = self.pool.begin().await?;
+        for statement in DB::CLEAR {
+            sqlx::query(statement).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_block_by_number(&self, block: u64) -> anyhow::Result<Block<H256>> {
+        sqlx::query("SELECT data FROM EVM_BLOCK WHERE EVM_BLOCK.id = $1")
+            .bind(block as i64)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting block {}: {:?}", block, e))
+            .and_then(|row| from_row_value(&row, 0))
+    }
+
+    async fn find_block_by_hash(&self, block_hash: H256) -> anyhow::Result<Option<Block<H256>>> {
+        let hex_block_hash = block_hash.to_hex_str();
+        let row = sqlx::query("SELECT data FROM EVM_BLOCK WHERE EVM_BLOCK.hash = $1")
+            .bind(&hex_block_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting block {}: {:?}", hex_block_hash, e))?;
+
+        row.map(|row| from_row_value(&row, 0)).transpose()
+    }
+
+    async fn find_first_block_from(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Option<Block<H256>>> {
+        let row = sqlx::query("SELECT data FROM EVM_BLOCK WHERE id >= $1 ORDER BY id LIMIT 1")
+            .bind(block_number as i64)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting block from {}: {:?}", block_number, e))?;
+
+        row.map(|row| from_row_value(&row, 0)).transpose()
+    }
+
+    async fn get_full_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Block<Transaction>> {
+        let block = self.get_block_by_number(block_number).await?;
+
+        let transactions: Vec<Transaction> =
+            sqlx::query("SELECT data FROM EVM_TRANSACTION WHERE EVM_TRANSACTION.block_number = $1")
+                .bind(block_number as i64)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| {
+                    anyhow::anyhow!("Error getting transactions for block {:?}: {:?}", block, e)
+                })
+                .and_then(|row| from_rows_value(&row, 0))?;
+
+        Ok(block.into_full_block(transactions)?)
+    }
+
+    async fn get_blocks_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<Block<H256>>> {
+        sqlx::query("SELECT data FROM EVM_BLOCK WHERE id >= $1 AND id <= $2 ORDER BY id")
+            .bind(from_block as i64)
+            .bind(to_block as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Error getting blocks from {} to {}: {:?}",
+                    from_block,
+                    to_block,
+                    e
+                )
+            })
+            .and_then(|rows| from_rows_value(&rows, 0))
+    }
+
+    async fn get_full_blocks_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<Block<Transaction>>> {
+        // One row for each transaction, or for each block without transactions
+        let rows = sqlx::query(
+            "SELECT EVM_BLOCK.id, EVM_BLOCK.data, EVM_TRANSACTION.data FROM EVM_BLOCK
+            LEFT JOIN EVM_TRANSACTION ON EVM_TRANSACTION.block_number = EVM_BLOCK.id
+            WHERE EVM_BLOCK.id >= $1 AND EVM_BLOCK.id <= $2
+            ORDER BY EVM_BLOCK.id",
+        )
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Error getting full blocks from {} to {}: {:?}",
+                from_block,
+                to_block,
+                e
+            )
+        })?;
+
+        let mut blocks = vec![];
+        let mut current: Option<(i64, Block<H256>, Vec<Transaction>)> = None;
+        for row in &rows {
+            let block_id: i64 = row.try_get(0)?;
+            if current.as_ref().is_none_or(|(id, _, _)| *id != block_id) {
+                if let Some((_, block, transactions)) = current.take() {
+                    blocks.push(block.into_full_block(transactions)?);
+                }
+                current = Some((block_id, from_row_value(row, 1)?, vec![]));
+            }
+
+            let transaction = row.try_get::<Option<serde_json::Value>, _>(2)?;
+            if let (Some((_, _, transactions)), Some(transaction)) = (current.as_mut(), transaction)
+            {
+                transactions.push(serde_json::from_value(transaction)?);
+            }
+        }
+        if let Some((_, block, transactions)) = current {
+            blocks.push(block.into_full_block(transactions)?);
+        }
+
+        Ok(blocks)
+    }
+
+    async fn insert_block_data(
+        &self,
+        blocks: &[Block<H256>],
+        transactions: &[Transaction],
+        receipts: &[TransactionReceipt],
+    ) -> anyhow::Result<()> {
+        if !blocks.is_empty() {
+            log::info!(
+                "Insert block data for blocks in range {} to {}",
+                blocks[0].number,
+                blocks[blocks.len() - 1].number
+            );
+        };
+
+        let mut tx = self.pool.begin().await?;
+
+        for block in blocks {
+            let block_id = block.number.0.to::<u64>();
+
+            sqlx::query("INSERT INTO EVM_BLOCK (id, hash, data) VALUES ($1, $2, $3)")
+                .bind(block_id as i64)
+                .bind(block.hash.to_hex_str())
+                .bind(serde_json::to_value(block)?)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| anyhow::anyhow!("Error inserting block {}: {:?}", block_id, e))
+                .map(|_| ())?;
+        }
+
+        for txn in transactions {
+            let hex_tx_hash = txn.hash.to_hex_str();
+            sqlx::query(
+                "INSERT INTO EVM_TRANSACTION (id, data, block_number, transaction_index, from_address, to_address, contract_address)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            )
+            .bind(&hex_tx_hash)
+            .bind(serde_json::to_value(txn)?)
+            .bind(
+                txn.block_number
+                    .expect("Block number not found")
+                    .0
+                    .to::<u64>() as i64,
+            )
+            .bind(txn.transaction_index.map(|index| index.as_u64() as i64))
+            .bind(txn.from.to_hex_str())
+            .bind(txn.to.as_ref().map(|to| to.to_hex_str()))
+            .bind(created_contract_address(txn).map(|address| address.to_hex_str()))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for receipt in receipts {
+            let hex_tx_hash = receipt.transaction_hash.to_hex_str();
+            sqlx::query(
+                "INSERT INTO EVM_TRANSACTION_RECEIPT (id, data, block_number) VALUES ($1, $2, $3)",
+            )
+            .bind(&hex_tx_hash)
+            .bind(serde_json::to_value(receipt)?)
+            .bind(receipt.block_number.0.to::<u64>() as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error inserting receipt {}: {:?}", hex_tx_hash, e))?;
+
+            for (log_index, log) in receipt.logs.iter().enumerate() {
+                let mut topics = log.topics.iter().map(|topic| topic.to_hex_str());
+
+                sqlx::query(
+                    "INSERT INTO EVM_LOG (transaction_hash, log_index, transaction_index, block_number, block_hash, address, topic0, topic1, topic2, topic3, data)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+                )
+                .bind(&hex_tx_hash)
+                .bind(log_index as i64)
+                .bind(receipt.transaction_index.as_u64() as i64)
+                .bind(receipt.block_number.as_u64() as i64)
+                .bind(receipt.block_hash.to_hex_str())
+                .bind(log.address.to_hex_str())
+                .bind(topics.next())
+                .bind(topics.next())
+                .bind(topics.next())
+                .bind(topics.next())
+                .bind(serde_json::to_value(log)?)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "Error inserting log {} of transaction {}: {:?}",
+                        log_index,
+                        hex_tx_hash,
+                        e
+                    )
+                })?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn insert_certified_block_data(&self, response: CertifiedBlock) -> anyhow::Result<()> {
+        let block_id = response.data.number.0.to::<u64>();
+
+        sqlx::query("INSERT INTO CERTIFIED_EVM_BLOCK (id, certified_response) VALUES ($1, $2) ON CONFLICT (id) DO UPDATE SET certified_response = $2")
+            .bind(block_id as i64)
+            .bind(serde_json::to_value(response)?)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error inserting certified block {}: {:?}", block_id, e))
+            .map(|_| ())
+    }
+
+    async fn get_last_certified_block_data(&self) -> anyhow::Result<CertifiedBlock> {
+        sqlx::query("SELECT certified_response FROM CERTIFIED_EVM_BLOCK ORDER BY id DESC LIMIT 1")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting last certified block: {:?}", e))
+            .and_then(|row| from_row_value(&row, 0))
+    }
+
+    /// Get the latest block number
+    async fn get_latest_block_number(&self) -> anyhow::Result<Option<u64>> {
+        sqlx::query("SELECT MAX(id) FROM EVM_BLOCK")
+            .fetch_one(&self.pool)
+            .await
+            .and_then(|row| row.try_get::<Option<i64>, _>(0))
+            .map(|n| n.map(|n| n as u64))
+            .map_err(|e| anyhow::anyhow!("Error getting latest block number: {:?}", e))
+    }
+
+    /// Get earliest block number
+    async fn get_earliest_block_number(&self) -> anyhow::Result<u64> {
+        sqlx::query("SELECT MIN(id) FROM EVM_BLOCK")
+            .fetch_one(&self.pool)
+            .await
+            .and_then(|row| row.try_get::<i64, _>(0).map(|n| n as u64))
+            .map_err(|e| anyhow::anyhow!("Error getting earliest block number: {:?}", e))
+    }
+
+    async fn get_missing_block_ranges(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<RangeInclusive<u64>>> {
+        // The blocks around the scanned range are added as sentinels,
+        // so that gaps at the edges of the range are found as well
+        let rows = sqlx::query(
+            "SELECT id + 1, next_id - 1 FROM (
+                SELECT id, LEAD(id) OVER (ORDER BY id) AS next_id FROM (
+                    SELECT id FROM EVM_BLOCK WHERE id BETWEEN $1 AND $2
+                    UNION ALL SELECT CAST($1 AS BIGINT) - 1
+                    UNION ALL SELECT CAST($2 AS BIGINT) + 1
+                ) AS ids
+            ) AS gaps WHERE next_id > id + 1 ORDER BY id",
+        )
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error getting missing blocks: {:?}", e))?;
+
+        rows.iter()
+            .map(|row| {
+                let start = row.try_get::<i64, _>(0)? as u64;
+                let end = row.try_get::<i64, _>(1)? as u64;
+                Ok(start..=end)
+            })
+            .collect()
+    }
+
+    async fn get_orphan_transaction_block_numbers(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<u64>> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT t.block_number FROM EVM_TRANSACTION t
+            LEFT JOIN EVM_BLOCK b ON b.id = t.block_number
+            WHERE b.id IS NULL AND t.block_number BETWEEN $1 AND $2
+            ORDER BY t.block_number",
+        )
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error getting orphan transactions: {:?}", e))?;
+
+        rows.iter()
+            .map(|row| Ok(row.try_get::<i64, _>(0)? as u64))
+            .collect()
+    }
+
+    async fn delete_block_transactions(&self, block_numbers: &[u64]) -> anyhow::Result<()> {
+        if block_numbers.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for table in ["EVM_TRANSACTION", "EVM_TRANSACTION_RECEIPT", "EVM_LOG"] {
+            let mut builder =
+                QueryBuilder::<DB>::new(format!("DELETE FROM {table} WHERE block_number IN ("));
+            let mut separated = builder.separated(", ");
+            for block_number in block_numbers {
+                separated.push_bind(*block_number as i64);
+            }
+            separated.push_unseparated(")");
+
+            builder
+                .build()
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| anyhow::anyhow!("Error deleting block transactions: {:?}", e))?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_genesis_balances(&self) -> anyhow::Result<Option<Vec<AccountBalance>>> {
+        self.fetch_key_value_data(GENESIS_BALANCES_KEY).await
+    }
+
+    async fn insert_genesis_balances(
+        &self,
+        genesis_balances: &[AccountBalance],
+    ) -> anyhow::Result<()> {
+        self.insert_key_value_data(GENESIS_BALANCES_KEY, genesis_balances)
+            .await
+    }
+
+    async fn get_chain_id(&self) -> anyhow::Result<Option<u64>> {
+        let data: Option<DataContainer<u64>> = self.fetch_key_value_data(CHAIN_ID_KEY).await?;
+        Ok(data.map(|d| d.data))
+    }
+
+    async fn insert_chain_id(&self, chain_id: u64) -> anyhow::Result<()> {
+        self.insert_key_value_data(CHAIN_ID_KEY, DataContainer::new(chain_id))
+            .await
+    }
+
+    async fn get_genesis_block_hash(&self) -> anyhow::Result<Option<H256>> {
+        let data: Option<DataContainer<H256>> =
+            self.fetch_key_value_data(GENESIS_BLOCK_HASH_KEY).await?;
+        Ok(data.map(|d| d.data))
+    }
+
+    async fn set_genesis_block_hash(&self, hash: H256) -> anyhow::Result<()> {
+        self.override_key_value_data(GENESIS_BLOCK_HASH_KEY, DataContainer::new(hash))
+            .await
+    }
+
+    async fn find_transaction(&self, tx_hash: H256) -> anyhow::Result<Option<Transaction>> {
+        let hex_tx_hash = tx_hash.to_hex_str();
+        let row = sqlx::query("SELECT data FROM EVM_TRANSACTION WHERE id = $1")
+            .bind(&hex_tx_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting transaction {}: {:?}", hex_tx_hash, e))?;
+
+        row.map(|row| from_row_value(&row, 0)).transpose()
+    }
+
+    async fn find_transaction_receipt(
+        &self,
+        tx_hash: H256,
+    ) -> anyhow::Result<Option<TransactionReceipt>> {
+        let hex_tx_hash = tx_hash.to_hex_str();
+        let row = sqlx::query("SELECT data FROM EVM_TRANSACTION_RECEIPT WHERE id = $1")
+            .bind(&hex_tx_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting receipt {}: {:?}", hex_tx_hash, e))?;
+
+        row.map(|row| from_row_value(&row, 0)).transpose()
+    }
+
+    async fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> anyhow::Result<Vec<TransactionReceipt>> {
+        let mut receipts: Vec<TransactionReceipt> = sqlx::query(
+            "SELECT data FROM EVM_TRANSACTION_RECEIPT WHERE EVM_TRANSACTION_RECEIPT.block_number = $1",
+        )
+        .bind(block_number as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Error getting receipts for block {}: {:?}",
+                block_number,
+                e
+            )
+        })
+        .and_then(|rows| from_rows_value(&rows, 0))?;
+
+        receipts.sort_by_key(|receipt| receipt.transaction_index.as_u64());
+
+        Ok(receipts)
+    }
+
+    async fn get_receipts_by_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<TransactionReceipt>> {
+        let mut receipts: Vec<TransactionReceipt> = sqlx::query(
+            "SELECT data FROM EVM_TRANSACTION_RECEIPT WHERE block_number >= $1 AND block_number <= $2",
+        )
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Error getting receipts from {} to {}: {:?}",
+                from_block,
+                to_block,
+                e
+            )
+        })
+        .and_then(|rows| from_rows_value(&rows, 0))?;
+
+        receipts.sort_by_key(|receipt| {
+            (
+                receipt.block_number.as_u64(),
+                receipt.transaction_index.as_u64(),
+            )
+        });
+
+        Ok(receipts)
+    }
+
+    async fn get_logs(&self, query: &LogsQuery) -> anyhow::Result<Vec<TransactionReceiptLog>> {
+        let mut builder = QueryBuilder::<DB>::new("SELECT data FROM EVM_LOG WHERE ");
+
+        match &query.block_filter {
+            LogsBlockFilter::Range { from, to } => {
+                builder
+                    .push("block_number >= ")
+                    .push_bind(*from as i64)
+                    .push(" AND block_number <= ")
+                    .push_bind(*to as i64);
+            }
+            LogsBlockFilter::Hash(hash) => {
+                builder.push("block_hash = ").push_bind(hash.to_hex_str());
+            }
+        }
+
+        if !query.addresses.is_empty() {
+            builder.push(" AND address IN (");
+            let mut separated = builder.separated(", ");
+            for address in &query.addresses {
+                separated.push_bind(address.to_hex_str());
+            }
+            separated.push_unseparated(")");
+        }
+
+        for (position, topics) in query.topics.iter().enumerate() {
+            if topics.is_empty() {
+                continue;
+            }
+
+            builder.push(format!(" AND topic{position} IN ("));
+            let mut separated = builder.separated(", ");
+            for topic in topics {
+                separated.push_bind(topic.to_hex_str());
+            }
+            separated.push_unseparated(")");
+        }
+
+        builder.push(" ORDER BY block_number, transaction_index, log_index");
+        if let Some(limit) = query.limit {
+            builder.push(" LIMIT ").push_bind(limit as i64);
+        }
+
+        builder
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting logs: {:?}", e))
+            .and_then(|rows| from_rows_value(&rows, 0))
+    }
+
+    async fn get_transactions_by_address(
+        &self,
+        query: &AddressTransactionsQuery,
+    ) -> anyhow::Result<Vec<Transaction>> {
+        let address = query.address.to_hex_str();
+        let mut builder = QueryBuilder::<DB>::new("SELECT data FROM EVM_TRANSACTION WHERE ");
+
+        match query.direction {
+            TransactionDirection::Any => {
+                builder
+                    .push("(from_address = ")
+                    .push_bind(address.clone())
+                    .push(" OR to_address = ")
+                    .push_bind(address.clone())
+                    .push(" OR contract_address = ")
+                    .push_bind(address.clone())
+                    .push(")");
+            }
+            TransactionDirection::From => {
+                builder.push("from_address = ").push_bind(address.clone());
+            }
+            TransactionDirection::To => {
+                builder
+                    .push("(to_address = ")
+                    .push_bind(address.clone())
+                    .push(" OR contract_address = ")
+                    .push_bind(address.clone())
+                    .push(")");
+            }
+        }
+
+        builder
+            .push(" AND block_number >= ")
+            .push_bind(query.from_block as i64)
+            .push(" AND block_number <= ")
+            .push_bind(query.to_block as i64);
+
+        if let Some(after) = query.after {
+            builder
+                .push(if query.descending {
+                    " AND (block_number, transaction_index) < ("
+                } else {
+                    " AND (block_number, transaction_index) > ("
+                })
+                .push_bind(after.block_number as i64)
+                .push(", ")
+                .push_bind(after.transaction_index as i64)
+                .push(")");
+        }
+
+        let order = if query.descending { "DESC" } else { "ASC" };
+        builder
+            .push(format!(
+                " ORDER BY block_number {order}, transaction_index {order} LIMIT "
+            ))
+            .push_bind(query.limit as i64);
+
+        builder
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error getting transactions of {}: {:?}", address, e))
+            .and_then(|rows| from_rows_value(&rows, 0))
+    }
+
+    async fn discard_blocks_from(&self, start_from: u64, reason: &str) -> anyhow::Result<()> {
+        self.discard_tail(start_from, reason, None).await
+    }
+
+    async fn discard_reorged_blocks(&self, reorg: &ChainReorg) -> anyhow::Result<()> {
+        self.discard_tail(
+            reorg.first_discarded_block,
+            REORG_DISCARD_REASON,
+            Some(reorg),
+        )
+        .await
+    }
+
+    async fn find_discarded_block_by_hash(
+        &self,
+        hash: H256,
+    ) -> anyhow::Result<Option<DiscardedBlock>> {
+        let hash_str = hash.to_hex_str();
+        let row = sqlx::query(
+            "SELECT data, reason, discarded_at, reorg_depth, new_hash FROM DISCARDED_EVM_BLOCK
+            WHERE DISCARDED_EVM_BLOCK.id = $1",
+        )
+        .bind(&hash_str)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error getting discarded block {}: {:?}", hash_str, e))?;
+
+        row.as_ref().map(discarded_block_from_row).transpose()
+    }
+
+    async fn get_discarded_blocks(
+        &self,
+        since: DateTime<Utc>,
+        after: Option<H256>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<DiscardedBlock>> {
+        let mut builder = QueryBuilder::<DB>::new(
+            "SELECT data, reason, discarded_at, reorg_depth, new_hash FROM DISCARDED_EVM_BLOCK WHERE ",
+        );
+
+        match after {
+            Some(after) => {
+                builder
+                    .push("(discarded_at, id) > (")
+                    .push_bind(since)
+                    .push(", ")
+                    .push_bind(after.to_hex_str())
+                    .push(")");
+            }
+            None => {
+                builder.push("discarded_at >= ").push_bind(since);
+            }
+        }
+
+        builder
+            .push(" ORDER BY discarded_at, id LIMIT ")
+            .push_bind(limit as i64);
+
+        let rows =
+            builder.build().fetch_all(&self.pool).await.map_err(|e| {
+                anyhow::anyhow!("Error getting discarded blocks since {since}: {e:?}")
+            })?;
+
+        rows.iter().map(discarded_block_from_row).collect()
+    }
+
+    async fn prune_blocks_before(
+        &self,
+        before_block: u64,
+        limit: u64,
+        headers_only: bool,
+    ) -> anyhow::Result<Option<u64>> {
+        let mut tx = self.pool.begin().await?;
+
+        // With headers only, the pruned blocks are the oldest ones
+        // still holding transactions
+        let last_block_query = if headers_only {
+            "SELECT MAX(block_number) FROM (
+                SELECT DISTINCT block_number FROM EVM_TRANSACTION WHERE block_number < $1
+                ORDER BY block_number LIMIT $2
+            ) AS pruned"
+        } else {
+            "SELECT MAX(id) FROM (
+                SELECT id FROM EVM_BLOCK WHERE id < $1 ORDER BY id LIMIT $2
+            ) AS pruned"
+        };
+        let last_block = sqlx::query(last_block_query)
+            .bind(before_block as i64)
+            .bind(limit as i64)
+            .fetch_one(&mut *tx)
+            .await
+            .and_then(|row| row.try_get::<Option<i64>, _>(0))
+            .map_err(|e| anyhow::anyhow!("Error getting blocks to prune: {:?}", e))?;
+
+        let Some(last_block) = last_block else {
+            return Ok(None);
+        };
+
+        log::info!("Pruning blocks up to {last_block}");
+
+        sqlx::query("DELETE FROM evm_transaction WHERE block_number <= $1")
+            .bind(last_block)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to prune transactions: {e}"))?;
+
+        sqlx::query("DELETE FROM evm_transaction_receipt WHERE block_number <= $1")
+            .bind(last_block)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to prune receipts: {e}"))?;
+
+        sqlx::query("DELETE FROM evm_log WHERE block_number <= $1")
+            .bind(last_block)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to prune logs: {e}"))?;
+
+        if headers_only {
+            sqlx::query(DB::CLEAR_BLOCK_TRANSACTIONS)
+                .bind(last_block)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to prune block transactions: {e}"))?;
+        } else {
+            sqlx::query("DELETE FROM evm_block WHERE id <= $1")
+                .bind(last_block)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to prune blocks: {e}"))?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(last_block as u64))
+    }
+
+    async fn prune_discarded_blocks(
+        &self,
+        discarded_before: DateTime<Utc>,
+        limit: u64,
+    ) -> anyhow::Result<u64> {
+        sqlx::query(
+            "DELETE FROM DISCARDED_EVM_BLOCK WHERE id IN (
+                SELECT id FROM DISCARDED_EVM_BLOCK WHERE discarded_at < $1 LIMIT $2
+            )",
+        )
+        .bind(discarded_before)
+        .bind(limit as i64)
+        .execute(&self.pool)
+        .await
+        .map(|result| DB::rows_affected(&result))
+        .map_err(|e| anyhow::anyhow!("Failed to prune discarded blocks: {e}"))
+    }
+
+    async fn get_block_info(&self) -> anyhow::Result<Option<BlockchainBlockInfo>> {
+        self.fetch_key_value_data(BLOCKCHAIN_BLOCK_INFO_KEY).await
+    }
+
+    async fn set_block_info(&self, info: BlockchainBlockInfo) -> anyhow::Result<()> {
+        self.override_key_value_data(BLOCKCHAIN_BLOCK_INFO_KEY, info)
+            .await
+    }
+}
+
+fn from_row_value<T: DeserializeOwned, R: Row>(row: &R, index: usize) -> anyhow::Result<T>
+where
+    usize: ColumnIndex<R>,
+    for<'r> serde_json::Value: Decode<'r, R::Database> + Type<R::Database>,
+{
+    let res = serde_json::from_value(row.try_get::<serde_json::Value, _>(index)?)?;
+    Ok(res)
+}
+
+fn discarded_block_from_row<R: Row>(row: &R) -> anyhow::Result<DiscardedBlock>
+where
+    usize: ColumnIndex<R>,
+    for<'r> i64: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> String: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> serde_json::Value: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> DateTime<Utc>: Decode<'r, R::Database> + Type<R::Database>,
+{
+    Ok(DiscardedBlock {
+        block: from_row_value(row, 0)?,
+        reason: row.try_get(1)?,
+        timestamp: row.try_get(2)?,
+        reorg_depth: row.try_get::<Option<i64>, _>(3)?.map(|depth| depth as u64),
+        new_hash: row
+            .try_get::<Option<String>, _>(4)?
+            .map(|hash| H256::from_hex_str(&hash))
+            .transpose()?,
+    })
+}
+
+fn from_rows_value<T: DeserializeOwned, R: Row>(rows: &[R], index: usize) -> anyhow::Result<Vec<T>>
+where
+    usize: ColumnIndex<R>,
+    for<'r> serde_json::Value: Decode<'r, R::Database> + Type<R::Database>,
+{
+    let mut res = Vec::with_capacity(rows.len());
+    for row in rows {
+        res.push(from_row_value(row, index)?);
+    }
+    Ok(res)
+}
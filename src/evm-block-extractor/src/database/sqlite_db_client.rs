@@ -0,0 +1,36 @@
+use ::sqlx::Sqlite;
+use ::sqlx::migrate::Migrator;
+use ::sqlx::sqlite::SqliteQueryResult;
+
+use super::sql_db_client::{SqlDbClient, SqlDialect};
+
+static MIGRATOR: Migrator = ::sqlx::migrate!("src_resources/db/sqlite/migrations");
+
+/// A blockchain client for SQLite
+pub type SqliteDbClient = SqlDbClient<Sqlite>;
+
+impl SqlDialect for Sqlite {
+    const NAME: &'static str = "SQLite";
+
+    // SQLite has no TRUNCATE
+    const CLEAR: &'static [&'static str] = &[
+        "DELETE FROM EVM_BLOCK",
+        "DELETE FROM EVM_TRANSACTION",
+        "DELETE FROM EVM_TRANSACTION_RECEIPT",
+        "DELETE FROM EVM_LOG",
+        "DELETE FROM EVM_KEY_VALUE_DATA",
+        "DELETE FROM CERTIFIED_EVM_BLOCK",
+    ];
+
+    const CLEAR_BLOCK_TRANSACTIONS: &'static str =
+        "UPDATE evm_block SET data = json_set(data, '$.transactions', json('[]'))
+        WHERE id <= $1 AND json_array_length(data, '$.transactions') > 0";
+
+    fn migrator() -> &'static Migrator {
+        &MIGRATOR
+    }
+
+    fn rows_affected(result: &SqliteQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
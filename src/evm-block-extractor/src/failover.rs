@@ -0,0 +1,209 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use did::BlockNumber;
+use did::rpc::request::RpcRequest;
+use did::rpc::response::RpcResponse;
+use ethereum_json_rpc_client::{Client, EthJsonRpcClient, JsonRpcError, JsonRpcResult};
+use log::*;
+
+use crate::database::DatabaseClient;
+use crate::metrics;
+
+/// An EVMC JSON-RPC endpoint
+#[derive(Clone)]
+pub struct Endpoint<C> {
+    /// URL of the endpoint, used to identify it in the logs
+    pub url: String,
+    pub client: C,
+}
+
+/// A client sending the requests to one of several EVMC endpoints.
+/// When the active endpoint fails, the requests are sent to the next healthy endpoint
+/// which serves the same blockchain stored in the database:
+/// it must report the same chain id, and the same block hash at the latest block both have.
+/// Failed endpoints are not selected again for the cooldown period.
+pub struct FailoverClient<C, DB> {
+    inner: Arc<FailoverInner<C, DB>>,
+}
+
+impl<C, DB> Clone for FailoverClient<C, DB> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+struct FailoverInner<C, DB> {
+    endpoints: Vec<Endpoint<C>>,
+    blockchain: Arc<DB>,
+    cooldown: Duration,
+    state: Mutex<FailoverState>,
+    /// Serializes the failovers, so that concurrent failed requests switch endpoint once
+    failover_lock: tokio::sync::Mutex<()>,
+}
+
+struct FailoverState {
+    /// Index of the endpoint receiving the requests
+    active: usize,
+    /// When each endpoint failed last, if it is in the cooldown period
+    failed_at: Vec<Option<Instant>>,
+}
+
+impl<C: Client + 'static, DB: DatabaseClient + 'static> FailoverClient<C, DB> {
+    /// Create a new client sending the requests to the first endpoint until it fails.
+    /// `blockchain` is the database the candidate endpoints are checked against.
+    pub fn new(
+        endpoints: Vec<Endpoint<C>>,
+        blockchain: Arc<DB>,
+        cooldown: Duration,
+    ) -> anyhow::Result<Self> {
+        if endpoints.is_empty() {
+            anyhow::bail!("At least one EVMC endpoint is required");
+        }
+
+        let failed_at = vec![None; endpoints.len()];
+        Ok(Self {
+            inner: Arc::new(FailoverInner {
+                endpoints,
+                blockchain,
+                cooldown,
+                state: Mutex::new(FailoverState {
+                    active: 0,
+                    failed_at,
+                }),
+                failover_lock: tokio::sync::Mutex::new(()),
+            }),
+        })
+    }
+}
+
+impl<C: Client + 'static, DB: DatabaseClient + 'static> Client for FailoverClient<C, DB> {
+    fn send_rpc_request(
+        &self,
+        request: RpcRequest,
+    ) -> Pin<Box<dyn Future<Output = JsonRpcResult<RpcResponse>> + Send>> {
+        let inner = self.inner.clone();
+
+        Box::pin(async move {
+            let active = inner.active();
+            let error = match inner.endpoints[active]
+                .client
+                .send_rpc_request(request.clone())
+                .await
+            {
+                // The EVMC answering with an error is healthy
+                Err(e) if !matches!(e, JsonRpcError::Evm(_)) => e,
+                response => return response,
+            };
+
+            warn!(
+                "EVMC endpoint {} failed: {error}",
+                inner.endpoints[active].url
+            );
+            match inner.failover(active).await {
+                Some(next) => inner.endpoints[next].client.send_rpc_request(request).await,
+                None => Err(error),
+            }
+        })
+    }
+}
+
+impl<C: Client, DB: DatabaseClient> FailoverInner<C, DB> {
+    fn state(&self) -> MutexGuard<'_, FailoverState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn active(&self) -> usize {
+        self.state().active
+    }
+
+    fn set_failed(&self, endpoint: usize) {
+        self.state().failed_at[endpoint] = Some(Instant::now());
+    }
+
+    /// Switches from the `failed` endpoint to the next healthy one serving the same blockchain.
+    /// Returns the new active endpoint, or `None` if no endpoint can replace the failed one.
+    async fn failover(&self, failed: usize) -> Option<usize> {
+        let _guard = self.failover_lock.lock().await;
+
+        // Another request has already switched endpoint
+        let active = self.active();
+        if active != failed {
+            return Some(active);
+        }
+        self.set_failed(failed);
+
+        let candidates = {
+            let state = self.state();
+            (1..self.endpoints.len())
+                .map(|offset| (failed + offset) % self.endpoints.len())
+                .filter(|&endpoint| {
+                    state.failed_at[endpoint]
+                        .is_none_or(|failed_at| failed_at.elapsed() >= self.cooldown)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for candidate in candidates {
+            let url = &self.endpoints[candidate].url;
+            match self.serves_same_blockchain(candidate).await {
+                Ok(true) => {
+                    let mut state = self.state();
+                    state.active = candidate;
+                    state.failed_at[candidate] = None;
+                    drop(state);
+
+                    warn!("Switched the EVMC endpoint to {url}");
+                    metrics::record_rpc_failover();
+                    return Some(candidate);
+                }
+                Ok(false) => {
+                    error!("EVMC endpoint {url} serves a different blockchain. Skipping it");
+                    self.set_failed(candidate);
+                }
+                Err(e) => {
+                    warn!("EVMC endpoint {url} is not available: {e:?}");
+                    self.set_failed(candidate);
+                }
+            }
+        }
+
+        error!("No EVMC endpoint available to replace the failed one");
+        None
+    }
+
+    /// Returns whether the endpoint reports the chain id stored in the database
+    /// and the same hash of the latest block both have
+    async fn serves_same_blockchain(&self, endpoint: usize) -> anyhow::Result<bool> {
+        let client = EthJsonRpcClient::new(self.endpoints[endpoint].client.clone());
+
+        let chain_id = client.get_chain_id().await?;
+        if let Some(stored_chain_id) = self.blockchain.get_chain_id().await? {
+            if chain_id != stored_chain_id {
+                warn!("Expected chain id {stored_chain_id}, found {chain_id}");
+                return Ok(false);
+            }
+        }
+
+        let Some(latest_block) = self.blockchain.get_latest_block_number().await? else {
+            return Ok(true);
+        };
+        let block_number = latest_block.min(client.get_block_number().await?);
+        let block = client
+            .get_block_by_number(BlockNumber::Number(block_number.into()))
+            .await?;
+
+        // A block which is not stored can't be compared, so the endpoint is not trusted
+        match self.blockchain.check_if_same_block_hash(&block).await {
+            Ok(same_hash) => Ok(same_hash),
+            Err(e) => {
+                warn!("Can't compare the hash of block {block_number}: {e:?}");
+                Ok(false)
+            }
+        }
+    }
+}
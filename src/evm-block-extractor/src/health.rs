@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use jsonrpsee::core::RpcResult;
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee::types::{ErrorCode, ErrorObject};
+use serde::{Deserialize, Serialize};
+
+use crate::database::DatabaseClient;
+
+/// HTTP path of the liveness probe, served by `health_live`
+pub const HEALTH_PATH: &str = "/health";
+/// HTTP path of the readiness probe, served by `health_ready`
+pub const READY_PATH: &str = "/ready";
+
+/// Sync status of the blocks stored in the database
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    /// Latest block stored in the database
+    pub latest_block_number: u64,
+    /// Latest block of the EVMC, as of the last extraction
+    pub remote_latest_block_number: u64,
+    /// Number of EVMC blocks not stored in the database yet
+    pub lag: u64,
+}
+
+/// health_* RPC methods, also served as plain HTTP GET routes
+#[rpc(server, namespace = "health")]
+pub trait Health {
+    #[method(name = "live")]
+    /// Returns `true` while the server is running
+    async fn live(&self) -> RpcResult<bool>;
+
+    #[method(name = "ready")]
+    /// Returns the sync status of the stored blocks.
+    /// Fails if the database is unreachable or empty, if the latest block of the EVMC
+    /// is unknown, or if the stored blocks lag behind the EVMC more than allowed.
+    async fn ready(&self) -> RpcResult<SyncStatus>;
+}
+
+pub struct HealthImpl<DB: DatabaseClient> {
+    pub blockchain: Arc<DB>,
+    /// The maximum number of EVMC blocks not stored yet for the server to be ready
+    pub max_sync_lag_blocks: u64,
+}
+
+impl<DB: DatabaseClient> HealthImpl<DB> {
+    pub fn new(db: Arc<DB>, max_sync_lag_blocks: u64) -> Self {
+        Self {
+            blockchain: db,
+            max_sync_lag_blocks,
+        }
+    }
+}
+
+/// Builds the error returned when the server is not ready
+fn not_ready(message: String) -> ErrorObject<'static> {
+    log::warn!("Server not ready: {message}");
+    ErrorObject::owned(ErrorCode::InternalError.code(), message, None::<()>)
+}
+
+impl<DB> HealthServer for HealthImpl<DB>
+where
+    DB: DatabaseClient + Send + Sync + 'static,
+{
+    async fn live(&self) -> RpcResult<bool> {
+        Ok(true)
+    }
+
+    async fn ready(&self) -> RpcResult<SyncStatus> {
+        let latest_block_number = self
+            .blockchain
+            .get_latest_block_number()
+            .await
+            .map_err(|e| not_ready(format!("database unreachable: {e}")))?
+            .ok_or_else(|| not_ready("no blocks stored".to_owned()))?;
+
+        let remote_latest_block_number = self
+            .blockchain
+            .get_block_info()
+            .await
+            .map_err(|e| not_ready(format!("database unreachable: {e}")))?
+            .ok_or_else(|| not_ready("remote head unknown".to_owned()))?
+            .latest_block_number;
+
+        let lag = remote_latest_block_number.saturating_sub(latest_block_number);
+
+        if lag > self.max_sync_lag_blocks {
+            return Err(not_ready(format!(
+                "stored blocks lag behind by {lag} blocks, more than {}",
+                self.max_sync_lag_blocks
+            )));
+        }
+
+        Ok(SyncStatus {
+            latest_block_number,
+            remote_latest_block_number,
+            lag,
+        })
+    }
+}
@@ -1,5 +1,11 @@
+pub mod certificate;
 pub mod config;
 pub mod database;
+pub mod failover;
+pub mod health;
+pub mod metrics;
+pub mod proxy;
 pub mod rpc;
 pub mod server;
+pub mod subscription;
 pub mod task;
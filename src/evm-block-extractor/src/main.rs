@@ -2,13 +2,14 @@ use std::error::Error;
 use std::sync::Arc;
 use std::time::Duration;
 
-use clap::Parser;
 use env_logger::Builder;
-use ethereum_json_rpc_client::EthJsonRpcClient;
-use ethereum_json_rpc_client::reqwest::ReqwestClient;
-use evm_block_extractor::config::ExtractorArgs;
+use evm_block_extractor::config::{Action, ExtractorArgs};
+use evm_block_extractor::metrics::start_metrics_listener;
 use evm_block_extractor::server::{server_start, server_stop};
-use evm_block_extractor::task::block_extractor::start_extractor;
+use evm_block_extractor::subscription::ChainEvents;
+use evm_block_extractor::task::archive::{start_export, start_import};
+use evm_block_extractor::task::block_extractor::{follow_chain, start_backfill, start_extractor};
+use evm_block_extractor::task::retention::start_pruning;
 use lightspeed_scheduler::JobExecutor;
 use lightspeed_scheduler::job::Job;
 use lightspeed_scheduler::scheduler::Scheduler;
@@ -16,7 +17,7 @@ use log::*;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let config = ExtractorArgs::parse();
+    let config = ExtractorArgs::load().unwrap_or_else(|e| e.exit());
 
     // Initialize logger
     init_logger(&config.log_filter)?;
@@ -24,27 +25,86 @@ async fn main() -> Result<(), Box<dyn Error>> {
     info!("Emvc Block Extractor");
     info!("----------------------");
     info!("- server_address: {}", config.server_address);
-    info!("- remote_rpc_url: {:?}", config.remote_rpc_url);
+    info!("- max_sync_lag_blocks: {}", config.max_sync_lag_blocks);
+    info!("- metrics_address: {:?}", config.metrics_address);
+    info!("- remote_rpc_urls: {:?}", config.remote_rpc_urls);
     info!("- rpc_batch_size: {}", config.rpc_batch_size);
+    info!("- max_parallel_batches: {}", config.max_parallel_batches);
     info!("- request_time_out_secs: {}", config.request_time_out_secs);
     info!(
         "- reset_db_on_state_change: {}",
         config.reset_db_on_state_change
     );
+    info!("- follow: {}", config.follow);
+    info!("- evm_canister_id: {:?}", config.evm_canister_id);
+    info!("- retention_policy: {:?}", config.retention_policy());
     info!("----------------------");
 
     let db_client = config.command.clone().build_client().await?;
 
+    if let Some(metrics_address) = config.metrics_address {
+        start_metrics_listener(metrics_address)?;
+    }
+
+    let evm_client = Arc::new(config.evm_client(db_client.clone())?);
+
+    // Run the maintenance task, if any, instead of the extractor and the server
+    if let Some(action) = config.command.action().cloned() {
+        match action {
+            Action::Backfill {
+                from_block,
+                to_block,
+            } => {
+                start_backfill(config, db_client, evm_client, from_block, to_block).await?;
+            }
+            Action::Export {
+                from_block,
+                to_block,
+                output_dir,
+                format,
+                blocks_per_file,
+            } => {
+                start_export(
+                    db_client,
+                    from_block,
+                    to_block,
+                    &output_dir,
+                    format,
+                    blocks_per_file,
+                )
+                .await?;
+            }
+            Action::Import { input_dir } => {
+                start_import(db_client, &input_dir).await?;
+            }
+        }
+
+        return Ok(());
+    }
+
     let job_executor = JobExecutor::new_with_local_tz();
-    let evm_client = Arc::new(EthJsonRpcClient::new(ReqwestClient::new(
-        config.remote_rpc_url.clone(),
-    )));
+
+    // Publishes the stored blocks to the WebSocket subscriptions
+    let chain_events = ChainEvents::default();
 
     // Configure and start the block extractor task
-    {
+    let mut follow_handle = if config.follow {
         let config = config.clone();
         let evm_client = evm_client.clone();
         let db_client = db_client.clone();
+        let chain_events = chain_events.clone();
+
+        Some(tokio::spawn(follow_chain(
+            config,
+            db_client,
+            evm_client,
+            chain_events,
+        )))
+    } else {
+        let config = config.clone();
+        let evm_client = evm_client.clone();
+        let db_client = db_client.clone();
+        let chain_events = chain_events.clone();
 
         job_executor
             .add_job_with_scheduler(
@@ -58,8 +118,33 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     let config = config.clone();
                     let evm_client = evm_client.clone();
                     let db_client = db_client.clone();
+                    let chain_events = chain_events.clone();
                     Box::pin(async move {
-                        start_extractor(config, db_client, evm_client).await?;
+                        start_extractor(config, db_client, evm_client, chain_events).await?;
+                        Ok(())
+                    })
+                }),
+            )
+            .await;
+
+        None
+    };
+
+    // Configure the retention job, if the blocks should be pruned
+    if let Some(policy) = config.retention_policy() {
+        let db_client = db_client.clone();
+
+        job_executor
+            .add_job_with_scheduler(
+                Scheduler::Interval {
+                    interval_duration: Duration::from_secs(config.retention_job_interval_seconds),
+                    execute_at_startup: true,
+                },
+                Job::new("evm_block_extractor", "prune_blocks", None, move || {
+                    let policy = policy.clone();
+                    let db_client = db_client.clone();
+                    Box::pin(async move {
+                        start_pruning(db_client, &policy).await?;
                         Ok(())
                     })
                 }),
@@ -71,18 +156,44 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let _job_executor_handle = job_executor.run().await?;
 
     // Start JSON RPC server
-    let server_handle = server_start(&config.server_address, db_client, evm_client).await?;
+    let server_handle = server_start(
+        &config.server_address,
+        db_client,
+        evm_client,
+        config.max_sync_lag_blocks,
+        config.certificate_verifier()?,
+        &config.proxy_methods,
+        chain_events,
+    )
+    .await?;
 
-    // Subscribe to the termination signals
-    match tokio::signal::ctrl_c().await {
-        Ok(_) => {
-            info!("Received shutdown signal");
+    // Subscribe to the termination signals.
+    // The extractor following the chain never stops on its own, so the process stops if it does,
+    // rather than serving stale blocks.
+    let mut follow_result = None;
+    tokio::select! {
+        signal = tokio::signal::ctrl_c() => match signal {
+            Ok(_) => {
+                info!("Received shutdown signal");
+            }
+            Err(err) => error!("Failed to listen for shutdown signal: {err}"),
+        },
+        result = async {
+            match follow_handle.as_mut() {
+                Some(handle) => handle.await,
+                None => std::future::pending().await,
+            }
+        } => {
+            follow_result = Some(result);
         }
-        Err(err) => error!("Failed to listen for shutdown signal: {err}"),
     }
 
     // Stop the world
     {
+        if let Some(follow_handle) = follow_handle {
+            follow_handle.abort();
+        }
+
         let stop_gracefully = true;
         job_executor
             .stop(stop_gracefully)
@@ -92,6 +203,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
         server_stop(server_handle).await?;
     }
 
+    if let Some(result) = follow_result {
+        let err = match result {
+            Ok(Ok(())) => anyhow::anyhow!("the task ended"),
+            Ok(Err(err)) => err,
+            Err(err) => err.into(),
+        };
+        error!("Block extractor stopped following the chain: {err:?}");
+        return Err(err.into());
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,249 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use did::rpc::request::RpcRequest;
+use did::rpc::response::{Response, RpcResponse};
+use ethereum_json_rpc_client::{Client, JsonRpcResult};
+use jsonrpsee::core::middleware::{Batch, BatchEntry, Notification, RpcServiceT};
+use jsonrpsee::types::Request;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics_exporter_prometheus::PrometheusBuilder;
+
+const LATEST_BLOCK: &str = "evm_block_extractor_latest_block";
+const REMOTE_HEAD: &str = "evm_block_extractor_remote_head";
+const LAG_BLOCKS: &str = "evm_block_extractor_lag_blocks";
+const BATCH_FETCH_SECONDS: &str = "evm_block_extractor_batch_fetch_seconds";
+const RPC_ERRORS: &str = "evm_block_extractor_rpc_errors_total";
+const RPC_FAILOVERS: &str = "evm_block_extractor_rpc_failovers_total";
+const DISCARDED_BLOCKS: &str = "evm_block_extractor_discarded_blocks_total";
+const REORG_DEPTH: &str = "evm_block_extractor_reorg_depth";
+const DB_WRITE_SECONDS: &str = "evm_block_extractor_db_write_seconds";
+const SERVER_REQUESTS: &str = "evm_block_extractor_server_requests_total";
+const INVALID_CERTIFICATES: &str = "evm_block_extractor_invalid_certificates_total";
+
+/// Method label of the requests for methods which are not served
+const UNKNOWN_METHOD: &str = "unknown";
+
+/// Latest values of the block numbers the lag is computed from
+static LATEST_BLOCK_NUMBER: AtomicU64 = AtomicU64::new(0);
+static REMOTE_HEAD_NUMBER: AtomicU64 = AtomicU64::new(0);
+
+/// Starts the HTTP listener serving the metrics in the Prometheus format.
+/// Until the listener is started, the metrics are not recorded.
+pub fn start_metrics_listener(address: SocketAddr) -> anyhow::Result<()> {
+    PrometheusBuilder::new()
+        .with_http_listener(address)
+        .install()?;
+
+    describe_gauge!(
+        LATEST_BLOCK,
+        "Number of the latest block stored in the database"
+    );
+    describe_gauge!(REMOTE_HEAD, "Number of the latest block of the EVMC");
+    describe_gauge!(
+        LAG_BLOCKS,
+        "Number of EVMC blocks not stored in the database yet"
+    );
+    describe_histogram!(
+        BATCH_FETCH_SECONDS,
+        "Time spent fetching a batch of blocks or receipts from the EVMC"
+    );
+    describe_counter!(RPC_ERRORS, "Failed requests to the EVMC by JSON-RPC method");
+    describe_counter!(
+        RPC_FAILOVERS,
+        "Switches of the EVMC endpoint after a failure"
+    );
+    describe_counter!(DISCARDED_BLOCKS, "Blocks discarded from the database");
+    describe_histogram!(REORG_DEPTH, "Depth of the chain reorganizations found");
+    describe_histogram!(
+        DB_WRITE_SECONDS,
+        "Time spent writing to the database by operation"
+    );
+    describe_counter!(
+        SERVER_REQUESTS,
+        "Requests served by the JSON-RPC server by method"
+    );
+    describe_counter!(
+        INVALID_CERTIFICATES,
+        "Certified blocks rejected because of an invalid certificate"
+    );
+
+    log::info!("Metrics served on {address}");
+
+    Ok(())
+}
+
+/// Records the number of the latest block stored in the database
+pub fn set_latest_block(block_number: u64) {
+    LATEST_BLOCK_NUMBER.store(block_number, Ordering::Relaxed);
+    gauge!(LATEST_BLOCK).set(block_number as f64);
+    update_lag();
+}
+
+/// Records the number of the latest block of the EVMC
+pub fn set_remote_head(block_number: u64) {
+    REMOTE_HEAD_NUMBER.store(block_number, Ordering::Relaxed);
+    gauge!(REMOTE_HEAD).set(block_number as f64);
+    update_lag();
+}
+
+fn update_lag() {
+    let lag = REMOTE_HEAD_NUMBER
+        .load(Ordering::Relaxed)
+        .saturating_sub(LATEST_BLOCK_NUMBER.load(Ordering::Relaxed));
+    gauge!(LAG_BLOCKS).set(lag as f64);
+}
+
+/// Records the time spent fetching a batch of the given kind, `blocks` or `receipts`
+pub fn record_batch_fetch(kind: &'static str, elapsed: Duration) {
+    histogram!(BATCH_FETCH_SECONDS, "kind" => kind).record(elapsed.as_secs_f64());
+}
+
+/// Records the time spent by a write operation on the database
+pub fn record_db_write(operation: &'static str, elapsed: Duration) {
+    histogram!(DB_WRITE_SECONDS, "operation" => operation).record(elapsed.as_secs_f64());
+}
+
+/// Records the blocks discarded by a chain reorganization
+pub fn record_reorg(depth: u64) {
+    counter!(DISCARDED_BLOCKS).increment(depth);
+    histogram!(REORG_DEPTH).record(depth as f64);
+}
+
+/// Records a failed request to the EVMC
+pub fn record_rpc_error(method: &str) {
+    counter!(RPC_ERRORS, "method" => method.to_owned()).increment(1);
+}
+
+/// Records a switch of the EVMC endpoint after a failure
+pub fn record_rpc_failover() {
+    counter!(RPC_FAILOVERS).increment(1);
+}
+
+/// Records a request served by the JSON-RPC server
+pub fn record_server_request(method: &str) {
+    counter!(SERVER_REQUESTS, "method" => method.to_owned()).increment(1);
+}
+
+/// Records a certified block rejected because of an invalid certificate
+pub fn record_invalid_certificate() {
+    counter!(INVALID_CERTIFICATES).increment(1);
+}
+
+/// A client which records the failed requests of the wrapped client by method
+#[derive(Clone)]
+pub struct MeteredClient<C> {
+    inner: C,
+}
+
+impl<C: Client> MeteredClient<C> {
+    /// Create a new client recording the failed requests of `inner`
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+impl<C: Client> Client for MeteredClient<C> {
+    fn send_rpc_request(
+        &self,
+        request: RpcRequest,
+    ) -> Pin<Box<dyn Future<Output = JsonRpcResult<RpcResponse>> + Send>> {
+        let calls = match &request {
+            RpcRequest::Single(call) => vec![(call.id.clone(), call.method.clone())],
+            RpcRequest::Batch(calls) => calls
+                .iter()
+                .map(|call| (call.id.clone(), call.method.clone()))
+                .collect(),
+        };
+        let response = self.inner.send_rpc_request(request);
+
+        Box::pin(async move {
+            let response = response.await;
+
+            let failures = match &response {
+                Err(_) => calls.iter().map(|(_, method)| method).collect(),
+                Ok(RpcResponse::Single(Response::Failure(_))) => {
+                    calls.iter().map(|(_, method)| method).collect()
+                }
+                Ok(RpcResponse::Single(Response::Success(_))) => vec![],
+                Ok(RpcResponse::Batch(responses)) => responses
+                    .iter()
+                    .filter_map(|response| match response {
+                        Response::Failure(failure) => calls
+                            .iter()
+                            .find(|(id, _)| *id == failure.id)
+                            .map(|(_, method)| method),
+                        Response::Success(_) => None,
+                    })
+                    .collect::<Vec<_>>(),
+            };
+            for method in failures {
+                record_rpc_error(method);
+            }
+
+            response
+        })
+    }
+}
+
+/// JSON-RPC server middleware which records the served requests by method.
+/// The requests for methods which are not served are recorded as `unknown`,
+/// so that clients can't create arbitrary metric labels.
+#[derive(Clone)]
+pub struct ServerRequestMetrics<S> {
+    service: S,
+    methods: Arc<HashSet<&'static str>>,
+}
+
+impl<S> ServerRequestMetrics<S> {
+    /// Create a new middleware wrapping `service`, which serves the given `methods`
+    pub fn new(service: S, methods: Arc<HashSet<&'static str>>) -> Self {
+        Self { service, methods }
+    }
+
+    fn record_request(&self, method: &str) {
+        if self.methods.contains(method) {
+            record_server_request(method);
+        } else {
+            record_server_request(UNKNOWN_METHOD);
+        }
+    }
+}
+
+impl<S> RpcServiceT for ServerRequestMetrics<S>
+where
+    S: RpcServiceT + Send + Sync + Clone + 'static,
+{
+    type MethodResponse = S::MethodResponse;
+    type NotificationResponse = S::NotificationResponse;
+    type BatchResponse = S::BatchResponse;
+
+    fn call<'a>(
+        &self,
+        request: Request<'a>,
+    ) -> impl Future<Output = Self::MethodResponse> + Send + 'a {
+        self.record_request(request.method_name());
+        self.service.call(request)
+    }
+
+    fn batch<'a>(&self, batch: Batch<'a>) -> impl Future<Output = Self::BatchResponse> + Send + 'a {
+        for entry in batch.iter() {
+            if let Ok(BatchEntry::Call(request)) = entry {
+                self.record_request(request.method_name());
+            }
+        }
+        self.service.batch(batch)
+    }
+
+    fn notification<'a>(
+        &self,
+        notification: Notification<'a>,
+    ) -> impl Future<Output = Self::NotificationResponse> + Send + 'a {
+        self.service.notification(notification)
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use did::rpc::id::Id;
+use did::rpc::params::Params;
+use ethereum_json_rpc_client::{Client, EthJsonRpcClient, JsonRpcError};
+use jsonrpsee::RpcModule;
+use jsonrpsee::core::RpcResult;
+use jsonrpsee::types::{ErrorCode, ErrorObject};
+use log::*;
+use serde_json::Value;
+
+/// Registers in `module` the `methods` forwarding their requests to the EVMC.
+/// The methods must not be served by the extractor itself.
+/// The errors returned by the EVMC are forwarded unchanged.
+pub fn register_proxy_methods<C>(
+    module: &mut RpcModule<()>,
+    evm_client: Arc<EthJsonRpcClient<C>>,
+    methods: &[String],
+) -> anyhow::Result<()>
+where
+    C: Client + 'static,
+{
+    for method in methods {
+        if module.method(method).is_some() {
+            anyhow::bail!("Method {method} is served by the extractor and can't be forwarded");
+        }
+
+        // The server is started once, so the leaked names are never reclaimed anyway
+        let method_name: &'static str = Box::leak(method.clone().into_boxed_str());
+        let evm_client = evm_client.clone();
+        module.register_async_method(method_name, move |params, _, _| {
+            let evm_client = evm_client.clone();
+            async move {
+                let params = match params.as_str() {
+                    Some(_) => params.parse::<Params>()?,
+                    None => Params::Array(vec![]),
+                };
+                forward(&evm_client, method_name, params).await
+            }
+        })?;
+
+        info!("Forwarding {method} to the EVMC");
+    }
+
+    Ok(())
+}
+
+/// Sends the request to the EVMC and returns its result
+async fn forward<C: Client>(
+    evm_client: &EthJsonRpcClient<C>,
+    method: &str,
+    params: Params,
+) -> RpcResult<Value> {
+    evm_client
+        .single_request(method.to_owned(), params, Id::String(method.to_owned()))
+        .await
+        .map_err(|e| match e {
+            JsonRpcError::Evm(failure) => ErrorObject::owned(
+                failure.error.code.code() as i32,
+                failure.error.message,
+                failure.error.data,
+            ),
+            e => {
+                warn!("Error forwarding {method} to the EVMC: {:?}", e);
+                ErrorCode::InternalError.into()
+            }
+        })
+}
@@ -1,15 +1,90 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use alloy::eips::BlockNumberOrTag;
 use alloy::primitives::{Address, U64, U256};
+use chrono::{DateTime, Utc};
+use did::certified::CertificateError;
 use did::evm_state::EvmGlobalState;
-use did::{BlockConfirmationData, BlockConfirmationResult, BlockchainBlockInfo};
+use did::logs::{BlockFilter, LogFilter};
+use did::transaction::TransactionReceiptLog;
+use did::{
+    Block, BlockConfirmationData, BlockConfirmationResult, BlockNumber, BlockchainBlockInfo, H160,
+    H256, Transaction, TransactionReceipt,
+};
 use ethereum_json_rpc_client::{Client, EthJsonRpcClient};
 use jsonrpsee::core::RpcResult;
 use jsonrpsee::proc_macros::rpc;
 use jsonrpsee::types::{ErrorCode, ErrorObject};
+use serde::{Deserialize, Serialize};
+
+use crate::certificate::CertificateVerifier;
+use crate::database::{
+    AddressTransactionsQuery, CertifiedBlock, DatabaseClient, DiscardedBlock, LogsBlockFilter,
+    LogsQuery, TransactionDirection, TransactionPosition,
+};
+
+/// Maximum number of logs returned by a single `eth_getLogs` request
+const MAX_LOGS_PER_QUERY: usize = 10_000;
+/// Maximum number of topics in a log
+pub(crate) const MAX_LOG_TOPICS: usize = 4;
+/// Maximum number of blocks returned by a single `ic_getBlocksByRange` request
+const MAX_BLOCKS_PER_RANGE: u64 = 1_000;
+/// Default number of transactions in a page of `ic_getTransactionsByAddress`
+const DEFAULT_TRANSACTIONS_PER_PAGE: usize = 100;
+/// Maximum number of transactions in a page of `ic_getTransactionsByAddress`
+const MAX_TRANSACTIONS_PER_PAGE: usize = 1_000;
+/// Default number of blocks in a page of `ic_getDiscardedBlocks`
+const DEFAULT_DISCARDED_BLOCKS_PER_PAGE: usize = 10;
+/// Maximum number of blocks in a page of `ic_getDiscardedBlocks`
+const MAX_DISCARDED_BLOCKS_PER_PAGE: usize = 100;
+
+/// Filter of the `ic_getTransactionsByAddress` request
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressTransactionsFilter {
+    /// First block of the range, `earliest` if not set
+    pub from_block: Option<BlockNumberOrTag>,
+    /// Last block of the range, `latest` if not set
+    pub to_block: Option<BlockNumberOrTag>,
+    #[serde(default)]
+    pub direction: TransactionDirection,
+    /// Whether the transactions are returned from the newest to the oldest
+    #[serde(default)]
+    pub descending: bool,
+    /// The `next` cursor of the previous page
+    pub after: Option<TransactionPosition>,
+    /// Maximum number of transactions in the page
+    pub limit: Option<usize>,
+}
+
+/// A page of the transactions returned by `ic_getTransactionsByAddress`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressTransactionsPage {
+    pub transactions: Vec<Transaction>,
+    /// Cursor of the next page, `None` if this is the last page
+    pub next: Option<TransactionPosition>,
+}
+
+/// A page of the blocks returned by `ic_getDiscardedBlocks`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscardedBlocksPage {
+    pub blocks: Vec<DiscardedBlock>,
+    /// Cursor of the next page, `None` if this is the last page
+    pub next: Option<DiscardedBlocksCursor>,
+}
 
-use crate::database::{CertifiedBlock, DatabaseClient};
+/// Position of the last block of a page of `ic_getDiscardedBlocks`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscardedBlocksCursor {
+    pub since: DateTime<Utc>,
+    pub after: H256,
+}
+
+/// A certified block with the result of the verification of its certificate
+type VerifiedCertificate = (CertifiedBlock, Result<(), CertificateError>);
 
 pub struct EthImpl<C, DB>
 where
@@ -18,6 +93,10 @@ where
 {
     pub blockchain: Arc<DB>,
     pub evm_client: Arc<EthJsonRpcClient<C>>,
+    /// Verifies the certified blocks before serving them
+    pub certificate_verifier: Option<CertificateVerifier>,
+    /// The last verified certified block, shared by the clones
+    last_verified_certificate: Arc<Mutex<Option<VerifiedCertificate>>>,
 }
 
 impl<C, DB> Clone for EthImpl<C, DB>
@@ -29,6 +108,8 @@ where
         Self {
             blockchain: self.blockchain.clone(),
             evm_client: self.evm_client.clone(),
+            certificate_verifier: self.certificate_verifier.clone(),
+            last_verified_certificate: self.last_verified_certificate.clone(),
         }
     }
 }
@@ -42,10 +123,158 @@ where
         Self {
             blockchain: db,
             evm_client,
+            certificate_verifier: None,
+            last_verified_certificate: Default::default(),
+        }
+    }
+
+    /// Sets the verifier of the certified blocks.
+    /// Certified blocks with an invalid certificate are not served.
+    /// The result of the verification of the last served certified block is kept,
+    /// so that serving it again does not verify its certificate again.
+    pub fn with_certificate_verifier(
+        mut self,
+        certificate_verifier: Option<CertificateVerifier>,
+    ) -> Self {
+        self.certificate_verifier = certificate_verifier;
+        self
+    }
+
+    /// Resolves the block number or tag to the number of a block in the database.
+    /// Tags are resolved from the stored `BlockchainBlockInfo` and capped at the latest block in the database.
+    /// Returns `None` if the database is empty.
+    async fn resolve_block_number(&self, block: BlockNumberOrTag) -> RpcResult<Option<u64>> {
+        let db = &self.blockchain;
+
+        let Some(latest_block_in_db) = db.get_latest_block_number().await.map_err(|e| {
+            log::warn!("Error getting latest block number: {:?}", e);
+            ErrorCode::InternalError
+        })?
+        else {
+            return Ok(None);
+        };
+
+        let block_info_future = async {
+            match db.get_block_info().await {
+                Ok(Some(info)) => info,
+                Ok(None) => {
+                    log::warn!("No block info set, can't select {block} block.");
+                    // We can't get the block info if the evm-canister version is too old.
+                    // Once all the canisters are updated, we can remove this logic and return instead of proceed.
+                    // TODO: Remove this logic in EPROD-1123
+                    // Err(ErrorCode::InternalError)
+                    BlockchainBlockInfo {
+                        earliest_block_number: 0,
+                        latest_block_number: latest_block_in_db,
+                        safe_block_number: latest_block_in_db,
+                        finalized_block_number: latest_block_in_db,
+                        pending_block_number: latest_block_in_db + 1,
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Error getting blockchain block info: {:?}", e);
+                    // We can't get the block info if the evm-canister version is too old.
+                    // Once all the canisters are updated, we can remove this logic and return instead of proceed.
+                    // TODO: Remove this logic in EPROD-1123
+                    // Err(ErrorCode::InternalError)
+                    BlockchainBlockInfo {
+                        earliest_block_number: 0,
+                        latest_block_number: latest_block_in_db,
+                        safe_block_number: latest_block_in_db,
+                        finalized_block_number: latest_block_in_db,
+                        pending_block_number: latest_block_in_db + 1,
+                    }
+                }
+            }
+        };
+
+        let block_number = match block {
+            BlockNumberOrTag::Finalized => {
+                let block_info = block_info_future.await;
+                block_info.finalized_block_number.min(latest_block_in_db)
+            }
+            BlockNumberOrTag::Safe => {
+                let block_info = block_info_future.await;
+                block_info.safe_block_number.min(latest_block_in_db)
+            }
+            BlockNumberOrTag::Pending => {
+                // The pending block is never stored, so the latest block in DB is returned instead
+                let block_info = block_info_future.await;
+                block_info.pending_block_number.min(latest_block_in_db)
+            }
+            BlockNumberOrTag::Latest => latest_block_in_db,
+            BlockNumberOrTag::Earliest => {
+                let earliest_block_in_db = db.get_earliest_block_number().await.map_err(|e| {
+                    log::error!("Error getting earliest block number: {:?}", e);
+                    ErrorCode::InternalError
+                })?;
+                let block_info = block_info_future.await;
+                // Blocks before the earliest block in DB were never extracted
+                block_info
+                    .earliest_block_number
+                    .max(earliest_block_in_db)
+                    .min(latest_block_in_db)
+            }
+            BlockNumberOrTag::Number(num) => num,
+        };
+
+        Ok(Some(block_number))
+    }
+
+    /// Verifies the certificate of the certified block, reusing the result of the previous
+    /// verification if the same certified block is served again
+    fn verify_certificate(
+        &self,
+        verifier: &CertificateVerifier,
+        certified_block: &CertifiedBlock,
+    ) -> Result<(), CertificateError> {
+        let mut last_verified = self
+            .last_verified_certificate
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some((block, result)) = last_verified.as_ref() {
+            if block == certified_block {
+                return result.clone();
+            }
         }
+
+        let result = verifier.verify(certified_block);
+        *last_verified = Some((certified_block.clone(), result.clone()));
+        result
+    }
+
+    /// Returns the stored block with the given number, if any
+    async fn find_block_by_number(&self, block_number: u64) -> RpcResult<Option<Block<H256>>> {
+        let block = self
+            .blockchain
+            .find_block_by_number(block_number)
+            .await
+            .map_err(|e| {
+                log::error!("Error getting block: {:?}", e);
+                ErrorCode::InternalError
+            })?;
+
+        Ok(block)
     }
 }
 
+/// Converts the `did` block number into the `alloy` block number or tag
+fn to_block_number_or_tag(block: BlockNumber) -> BlockNumberOrTag {
+    match block {
+        BlockNumber::Latest => BlockNumberOrTag::Latest,
+        BlockNumber::Earliest => BlockNumberOrTag::Earliest,
+        BlockNumber::Pending => BlockNumberOrTag::Pending,
+        BlockNumber::Safe => BlockNumberOrTag::Safe,
+        BlockNumber::Finalized => BlockNumberOrTag::Finalized,
+        BlockNumber::Number(num) => BlockNumberOrTag::Number(num.as_u64()),
+    }
+}
+
+/// Builds an invalid params error with the given message
+pub(crate) fn invalid_params(message: impl Into<String>) -> ErrorObject<'static> {
+    ErrorObject::owned(ErrorCode::InvalidParams.code(), message, None::<()>)
+}
+
 /// eth_* RPC methods
 #[rpc(server, namespace = "eth")]
 pub trait Eth {
@@ -57,6 +286,16 @@ pub trait Eth {
         full_transactions: bool,
     ) -> RpcResult<serde_json::Value>;
 
+    #[method(name = "getBlockByHash")]
+    /// Get a block by hash.
+    /// Blocks discarded by the extractor are returned together with the discard reason;
+    /// blocks discarded by a chain reorganization also report its depth and the replacing block hash.
+    async fn get_block_by_hash(
+        &self,
+        hash: H256,
+        full_transactions: bool,
+    ) -> RpcResult<serde_json::Value>;
+
     #[method(name = "blockNumber")]
     /// Get the latest block number
     async fn block_number(&self) -> RpcResult<U256>;
@@ -64,6 +303,33 @@ pub trait Eth {
     #[method(name = "chainId")]
     /// Get the chain id
     async fn get_chain_id(&self) -> RpcResult<U64>;
+
+    #[method(name = "getLogs")]
+    /// Get the logs matching the given filter
+    async fn get_logs(&self, filter: serde_json::Value) -> RpcResult<Vec<TransactionReceiptLog>>;
+
+    #[method(name = "getTransactionByHash")]
+    /// Get a transaction by hash
+    async fn get_transaction_by_hash(&self, hash: H256) -> RpcResult<Option<Transaction>>;
+
+    #[method(name = "getTransactionReceipt")]
+    /// Get the receipt of a transaction by transaction hash
+    async fn get_transaction_receipt(&self, hash: H256) -> RpcResult<Option<TransactionReceipt>>;
+
+    #[method(name = "getTransactionByBlockNumberAndIndex")]
+    /// Get a transaction by block number and index of the transaction in the block
+    async fn get_transaction_by_block_number_and_index(
+        &self,
+        block: BlockNumberOrTag,
+        index: U64,
+    ) -> RpcResult<Option<Transaction>>;
+
+    #[method(name = "getBlockTransactionCountByNumber")]
+    /// Get the number of transactions in a block
+    async fn get_block_transaction_count_by_number(
+        &self,
+        block: BlockNumberOrTag,
+    ) -> RpcResult<Option<U64>>;
 }
 
 /// ic_* RPC methods
@@ -83,6 +349,41 @@ pub trait IC {
         &self,
         data: BlockConfirmationData,
     ) -> RpcResult<BlockConfirmationResult>;
+
+    #[method(name = "getBlocksByRange")]
+    /// Returns the blocks from `from` to `to` (inclusive), ordered by number.
+    /// Blocks not stored yet are not returned.
+    async fn get_blocks_by_range(
+        &self,
+        from: BlockNumberOrTag,
+        to: BlockNumberOrTag,
+        full_transactions: bool,
+    ) -> RpcResult<serde_json::Value>;
+
+    #[method(name = "getTransactionsByAddress")]
+    /// Returns a page of the transactions sent from or to the address,
+    /// including the transaction creating a contract at it.
+    /// Pass the `next` cursor of a page as `after` to get the following page.
+    async fn get_transactions_by_address(
+        &self,
+        address: H160,
+        filter: Option<AddressTransactionsFilter>,
+    ) -> RpcResult<AddressTransactionsPage>;
+
+    #[method(name = "getDiscardedBlockByHash")]
+    /// Returns the block discarded by a reorganization or by a confirmation strategy,
+    /// with the reason and the time it was discarded.
+    async fn get_discarded_block_by_hash(&self, hash: H256) -> RpcResult<Option<DiscardedBlock>>;
+
+    #[method(name = "getDiscardedBlocks")]
+    /// Returns a page of the blocks discarded since the given time, ordered by discard time.
+    /// Pass the `since` and `after` of the `next` cursor of a page to get the following page.
+    async fn get_discarded_blocks(
+        &self,
+        since: DateTime<Utc>,
+        limit: Option<usize>,
+        after: Option<H256>,
+    ) -> RpcResult<DiscardedBlocksPage>;
 }
 
 #[jsonrpsee::core::async_trait]
@@ -114,6 +415,21 @@ where
                 ErrorCode::InternalError
             })?;
 
+        if let Some(verifier) = &self.certificate_verifier {
+            self.verify_certificate(verifier, &certified_data)
+                .map_err(|e| {
+                    log::error!(
+                        "Invalid certificate for block {}: {e}",
+                        certified_data.data.number.as_u64()
+                    );
+                    ErrorObject::owned(
+                        ErrorCode::InternalError.code(),
+                        format!("invalid certificate: {e}"),
+                        None::<()>,
+                    )
+                })?;
+        }
+
         Ok(certified_data)
     }
 
@@ -153,6 +469,156 @@ where
 
         Ok(confirmation_result)
     }
+
+    async fn get_blocks_by_range(
+        &self,
+        from: BlockNumberOrTag,
+        to: BlockNumberOrTag,
+        full_transactions: bool,
+    ) -> RpcResult<serde_json::Value> {
+        let (Some(from_block), Some(to_block)) = (
+            self.resolve_block_number(from).await?,
+            self.resolve_block_number(to).await?,
+        ) else {
+            return Ok(serde_json::Value::Array(vec![]));
+        };
+
+        if from_block > to_block {
+            return Err(invalid_params("from is greater than to"));
+        }
+        if to_block - from_block >= MAX_BLOCKS_PER_RANGE {
+            return Err(invalid_params(format!(
+                "range exceeds the maximum of {MAX_BLOCKS_PER_RANGE} blocks"
+            )));
+        }
+
+        let blocks = if full_transactions {
+            self.blockchain
+                .get_full_blocks_by_range(from_block, to_block)
+                .await
+                .and_then(|blocks| Ok(serde_json::to_value(blocks)?))
+        } else {
+            self.blockchain
+                .get_blocks_by_range(from_block, to_block)
+                .await
+                .and_then(|blocks| Ok(serde_json::to_value(blocks)?))
+        };
+
+        blocks.map_err(|e| {
+            log::error!(
+                "Error getting blocks from {from_block} to {to_block}: {:?}",
+                e
+            );
+            ErrorObject::from(ErrorCode::InternalError)
+        })
+    }
+
+    async fn get_transactions_by_address(
+        &self,
+        address: H160,
+        filter: Option<AddressTransactionsFilter>,
+    ) -> RpcResult<AddressTransactionsPage> {
+        let filter = filter.unwrap_or_default();
+
+        let limit = filter.limit.unwrap_or(DEFAULT_TRANSACTIONS_PER_PAGE);
+        if limit == 0 || limit > MAX_TRANSACTIONS_PER_PAGE {
+            return Err(invalid_params(format!(
+                "limit must be between 1 and {MAX_TRANSACTIONS_PER_PAGE}"
+            )));
+        }
+
+        let from_block = self
+            .resolve_block_number(filter.from_block.unwrap_or(BlockNumberOrTag::Earliest))
+            .await?;
+        let to_block = self
+            .resolve_block_number(filter.to_block.unwrap_or(BlockNumberOrTag::Latest))
+            .await?;
+        let (Some(from_block), Some(to_block)) = (from_block, to_block) else {
+            return Ok(AddressTransactionsPage {
+                transactions: vec![],
+                next: None,
+            });
+        };
+
+        if from_block > to_block {
+            return Err(invalid_params("fromBlock is greater than toBlock"));
+        }
+
+        let query = AddressTransactionsQuery {
+            address,
+            direction: filter.direction,
+            from_block,
+            to_block,
+            descending: filter.descending,
+            after: filter.after,
+            // One more transaction tells if there is a next page
+            limit: limit + 1,
+        };
+
+        let mut transactions = self
+            .blockchain
+            .get_transactions_by_address(&query)
+            .await
+            .map_err(|e| {
+                log::error!("Error getting transactions by address: {:?}", e);
+                ErrorCode::InternalError
+            })?;
+
+        let next = if transactions.len() > limit {
+            transactions.truncate(limit);
+            transactions.last().map(TransactionPosition::of)
+        } else {
+            None
+        };
+
+        Ok(AddressTransactionsPage { transactions, next })
+    }
+
+    async fn get_discarded_block_by_hash(&self, hash: H256) -> RpcResult<Option<DiscardedBlock>> {
+        self.blockchain
+            .find_discarded_block_by_hash(hash)
+            .await
+            .map_err(|e| {
+                log::error!("Error getting discarded block: {:?}", e);
+                ErrorObject::from(ErrorCode::InternalError)
+            })
+    }
+
+    async fn get_discarded_blocks(
+        &self,
+        since: DateTime<Utc>,
+        limit: Option<usize>,
+        after: Option<H256>,
+    ) -> RpcResult<DiscardedBlocksPage> {
+        let limit = limit.unwrap_or(DEFAULT_DISCARDED_BLOCKS_PER_PAGE);
+        if limit == 0 || limit > MAX_DISCARDED_BLOCKS_PER_PAGE {
+            return Err(invalid_params(format!(
+                "limit must be between 1 and {MAX_DISCARDED_BLOCKS_PER_PAGE}"
+            )));
+        }
+
+        // One more block tells if there is a next page
+        let mut blocks = self
+            .blockchain
+            .get_discarded_blocks(since, after, limit + 1)
+            .await
+            .map_err(|e| {
+                log::error!("Error getting discarded blocks: {:?}", e);
+                ErrorCode::InternalError
+            })?;
+
+        let next = if blocks.len() > limit {
+            blocks.truncate(limit);
+            blocks.last().map(|discarded| DiscardedBlocksCursor {
+                since: discarded.timestamp,
+                after: discarded.block.hash.clone(),
+            })
+        } else {
+            None
+        };
+
+        Ok(DiscardedBlocksPage { blocks, next })
+    }
 }
 
 #[jsonrpsee::core::async_trait]
@@ -166,72 +632,10 @@ where
         block: BlockNumberOrTag,
         include_transactions: bool,
     ) -> RpcResult<serde_json::Value> {
-        let db = &self.blockchain;
-
-        let Some(latest_block_in_db) =
-            self.blockchain
-                .get_latest_block_number()
-                .await
-                .map_err(|e| {
-                    log::warn!("Error getting earliest block number: {:?}", e);
-                    ErrorCode::InternalError
-                })?
-        else {
+        let Some(block_number) = self.resolve_block_number(block).await? else {
             return Ok(serde_json::Value::Null);
         };
 
-        let block_info_future = async {
-            match db.get_block_info().await {
-                Ok(Some(info)) => info,
-                Ok(None) => {
-                    log::warn!("No block info set, can't select {block} block.");
-                    // We can't get the block info if the evm-canister version is too old.
-                    // Once all the canisters are updated, we can remove this logic and return instead of proceed.
-                    // TODO: Remove this logic in EPROD-1123
-                    // Err(ErrorCode::InternalError)
-                    BlockchainBlockInfo {
-                        earliest_block_number: 0,
-                        latest_block_number: latest_block_in_db,
-                        safe_block_number: latest_block_in_db,
-                        finalized_block_number: latest_block_in_db,
-                        pending_block_number: latest_block_in_db + 1,
-                    }
-                }
-                Err(e) => {
-                    log::warn!("Error getting blockchain block info: {:?}", e);
-                    // We can't get the block info if the evm-canister version is too old.
-                    // Once all the canisters are updated, we can remove this logic and return instead of proceed.
-                    // TODO: Remove this logic in EPROD-1123
-                    // Err(ErrorCode::InternalError)
-                    BlockchainBlockInfo {
-                        earliest_block_number: 0,
-                        latest_block_number: latest_block_in_db,
-                        safe_block_number: latest_block_in_db,
-                        finalized_block_number: latest_block_in_db,
-                        pending_block_number: latest_block_in_db + 1,
-                    }
-                }
-            }
-        };
-
-        let block_number = match block {
-            BlockNumberOrTag::Finalized => {
-                let block_info = block_info_future.await;
-                block_info.finalized_block_number.min(latest_block_in_db)
-            }
-            BlockNumberOrTag::Safe => {
-                let block_info = block_info_future.await;
-                block_info.safe_block_number.min(latest_block_in_db)
-            }
-            BlockNumberOrTag::Latest => latest_block_in_db,
-            BlockNumberOrTag::Earliest => db.get_earliest_block_number().await.map_err(|e| {
-                log::error!("Error getting earliest block number: {:?}", e);
-                ErrorCode::InternalError
-            })?,
-            BlockNumberOrTag::Number(num) => num,
-            BlockNumberOrTag::Pending => return Ok(serde_json::Value::Null),
-        };
-
         if include_transactions {
             let block = self
                 .blockchain
@@ -267,6 +671,82 @@ where
         }
     }
 
+    async fn get_block_by_hash(
+        &self,
+        hash: H256,
+        include_transactions: bool,
+    ) -> RpcResult<serde_json::Value> {
+        let block = self
+            .blockchain
+            .find_block_by_hash(hash.clone())
+            .await
+            .map_err(|e| {
+                log::error!("Error getting block: {:?}", e);
+                ErrorCode::InternalError
+            })?;
+
+        if let Some(block) = block {
+            let block = if include_transactions {
+                let full_block = self
+                    .blockchain
+                    .get_full_block_by_number(block.number.as_u64())
+                    .await
+                    .map_err(|e| {
+                        log::error!("Error getting block: {:?}", e);
+                        ErrorCode::InternalError
+                    })?;
+                serde_json::to_value(&full_block)
+            } else {
+                serde_json::to_value(&block)
+            };
+
+            return block.map_err(|e| {
+                log::error!("Error serializing block: {:?}", e);
+                ErrorCode::InternalError.into()
+            });
+        }
+
+        let discarded = self
+            .blockchain
+            .find_discarded_block_by_hash(hash)
+            .await
+            .map_err(|e| {
+                log::error!("Error getting discarded block: {:?}", e);
+                ErrorCode::InternalError
+            })?;
+
+        let Some(discarded) = discarded else {
+            return Ok(serde_json::Value::Null);
+        };
+
+        let block = if include_transactions {
+            serde_json::to_value(&discarded.block)
+        } else {
+            serde_json::to_value(Block::<H256>::from(discarded.block))
+        };
+
+        let mut block = block.map_err(|e| {
+            log::error!("Error serializing block: {:?}", e);
+            ErrorCode::InternalError
+        })?;
+
+        if let Some(fields) = block.as_object_mut() {
+            fields.insert("discardReason".into(), discarded.reason.into());
+            fields.insert(
+                "discardedAt".into(),
+                discarded.timestamp.to_rfc3339().into(),
+            );
+            if let Some(reorg_depth) = discarded.reorg_depth {
+                fields.insert("reorgDepth".into(), reorg_depth.into());
+            }
+            if let Some(new_hash) = discarded.new_hash {
+                fields.insert("replacedBy".into(), new_hash.to_hex_str().into());
+            }
+        }
+
+        Ok(block)
+    }
+
     async fn block_number(&self) -> RpcResult<U256> {
         let block_number = self
             .blockchain
@@ -294,4 +774,128 @@ where
 
         Ok(U64::from(chain_id))
     }
+
+    async fn get_logs(&self, filter: serde_json::Value) -> RpcResult<Vec<TransactionReceiptLog>> {
+        let filter = LogFilter::try_from(filter).map_err(|e| invalid_params(e.message))?;
+
+        let block_filter = match filter.block_filter {
+            Some(BlockFilter::Exact { block_hash }) => LogsBlockFilter::Hash(block_hash),
+            Some(BlockFilter::Bounded {
+                from_block,
+                to_block,
+            }) => {
+                let from = self
+                    .resolve_block_number(
+                        from_block.map_or(BlockNumberOrTag::Latest, to_block_number_or_tag),
+                    )
+                    .await?;
+                let to = self
+                    .resolve_block_number(
+                        to_block.map_or(BlockNumberOrTag::Latest, to_block_number_or_tag),
+                    )
+                    .await?;
+
+                match (from, to) {
+                    (Some(from), Some(to)) if from <= to => LogsBlockFilter::Range { from, to },
+                    (Some(_), Some(_)) => {
+                        return Err(invalid_params("fromBlock is greater than toBlock"));
+                    }
+                    _ => return Ok(vec![]),
+                }
+            }
+            None => match self.resolve_block_number(BlockNumberOrTag::Latest).await? {
+                Some(latest) => LogsBlockFilter::Range {
+                    from: latest,
+                    to: latest,
+                },
+                None => return Ok(vec![]),
+            },
+        };
+
+        let topics = filter.topics.unwrap_or_default();
+        if topics.len() > MAX_LOG_TOPICS {
+            return Err(invalid_params(format!(
+                "too many topics: expected at most {MAX_LOG_TOPICS}"
+            )));
+        }
+
+        let query = LogsQuery {
+            block_filter,
+            addresses: filter.address.map(|a| a.0).unwrap_or_default(),
+            topics: topics
+                .into_iter()
+                .map(|topic| topic.map(|t| t.0).unwrap_or_default())
+                .collect(),
+            limit: Some(MAX_LOGS_PER_QUERY + 1),
+        };
+
+        let logs = self.blockchain.get_logs(&query).await.map_err(|e| {
+            log::error!("Error getting logs: {:?}", e);
+            ErrorCode::InternalError
+        })?;
+
+        if logs.len() > MAX_LOGS_PER_QUERY {
+            return Err(invalid_params(format!(
+                "query returned more than {MAX_LOGS_PER_QUERY} results"
+            )));
+        }
+
+        Ok(logs)
+    }
+
+    async fn get_transaction_by_hash(&self, hash: H256) -> RpcResult<Option<Transaction>> {
+        self.blockchain.find_transaction(hash).await.map_err(|e| {
+            log::error!("Error getting transaction: {:?}", e);
+            ErrorObject::from(ErrorCode::InternalError)
+        })
+    }
+
+    async fn get_transaction_receipt(&self, hash: H256) -> RpcResult<Option<TransactionReceipt>> {
+        self.blockchain
+            .find_transaction_receipt(hash)
+            .await
+            .map_err(|e| {
+                log::error!("Error getting transaction receipt: {:?}", e);
+                ErrorObject::from(ErrorCode::InternalError)
+            })
+    }
+
+    async fn get_transaction_by_block_number_and_index(
+        &self,
+        block: BlockNumberOrTag,
+        index: U64,
+    ) -> RpcResult<Option<Transaction>> {
+        let Some(block_number) = self.resolve_block_number(block).await? else {
+            return Ok(None);
+        };
+
+        let Some(block) = self.find_block_by_number(block_number).await? else {
+            return Ok(None);
+        };
+
+        let Some(tx_hash) = block.transactions.into_iter().nth(index.to::<usize>()) else {
+            return Ok(None);
+        };
+
+        self.blockchain
+            .find_transaction(tx_hash)
+            .await
+            .map_err(|e| {
+                log::error!("Error getting transaction: {:?}", e);
+                ErrorObject::from(ErrorCode::InternalError)
+            })
+    }
+
+    async fn get_block_transaction_count_by_number(
+        &self,
+        block: BlockNumberOrTag,
+    ) -> RpcResult<Option<U64>> {
+        let Some(block_number) = self.resolve_block_number(block).await? else {
+            return Ok(None);
+        };
+
+        let block = self.find_block_by_number(block_number).await?;
+
+        Ok(block.map(|block| U64::from(block.transactions.len())))
+    }
 }
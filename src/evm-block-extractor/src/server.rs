@@ -1,29 +1,66 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use ethereum_json_rpc_client::{Client, EthJsonRpcClient};
 use jsonrpsee::RpcModule;
+use jsonrpsee::core::middleware::RpcServiceBuilder;
+use jsonrpsee::server::middleware::http::ProxyGetRequestLayer;
 use jsonrpsee::server::{Server, ServerHandle};
 use log::*;
+use tower::ServiceBuilder;
 
+use crate::certificate::CertificateVerifier;
 use crate::database::DatabaseClient;
+use crate::health::{HEALTH_PATH, HealthImpl, HealthServer, READY_PATH};
+use crate::metrics::ServerRequestMetrics;
+use crate::proxy::register_proxy_methods;
 use crate::rpc::{EthImpl, EthServer, ICServer};
+use crate::subscription::{ChainEvents, EthPubSubImpl, EthPubSubServer};
 
-/// Start the RPC server
+/// Start the RPC server.
+/// The server is ready while the stored blocks lag behind the EVMC
+/// by at most `max_sync_lag_blocks` blocks.
+/// If a certificate verifier is given, certified blocks with an invalid certificate are not served.
+/// The `proxy_methods`, not served by the extractor, are forwarded to the EVMC.
+/// The WebSocket subscriptions are notified of the `chain_events`.
 pub async fn server_start<DB: DatabaseClient + Send + Sync + 'static>(
     server_address: &str,
     db_client: Arc<DB>,
     evm_client: Arc<EthJsonRpcClient<impl Client + 'static>>,
+    max_sync_lag_blocks: u64,
+    certificate_verifier: Option<CertificateVerifier>,
+    proxy_methods: &[String],
+    chain_events: ChainEvents,
 ) -> anyhow::Result<ServerHandle> {
     info!("Start server");
 
-    let server = Server::builder().build(server_address).await?;
+    // Serve the health probes as plain HTTP GET routes
+    let http_middleware = ServiceBuilder::new().layer(ProxyGetRequestLayer::new([
+        (HEALTH_PATH, "health_live"),
+        (READY_PATH, "health_ready"),
+    ])?);
 
-    let eth = EthImpl::new(db_client, evm_client);
+    let health = HealthImpl::new(db_client.clone(), max_sync_lag_blocks);
+    let eth =
+        EthImpl::new(db_client, evm_client.clone()).with_certificate_verifier(certificate_verifier);
 
     let mut module = RpcModule::new(());
 
     module.merge(EthServer::into_rpc(eth.clone()))?;
     module.merge(ICServer::into_rpc(eth))?;
+    module.merge(HealthServer::into_rpc(health))?;
+    module.merge(EthPubSubServer::into_rpc(EthPubSubImpl::new(chain_events)))?;
+    register_proxy_methods(&mut module, evm_client, proxy_methods)?;
+
+    let methods: Arc<HashSet<_>> = Arc::new(module.method_names().collect());
+    let server = Server::builder()
+        .set_http_middleware(http_middleware)
+        .set_rpc_middleware(
+            RpcServiceBuilder::new()
+                .layer_fn(move |service| ServerRequestMetrics::new(service, methods.clone())),
+        )
+        .build(server_address)
+        .await?;
 
     info!("Server started on {}", server.local_addr()?);
 
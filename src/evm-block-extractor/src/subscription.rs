@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use did::logs::LogFilter;
+use did::transaction::TransactionReceiptLog;
+use did::{Block, H160, H256};
+use jsonrpsee::core::SubscriptionResult;
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee::types::ErrorObject;
+use jsonrpsee::{PendingSubscriptionSink, SubscriptionSink};
+use log::*;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::rpc::{MAX_LOG_TOPICS, invalid_params};
+
+/// Number of chain events buffered for each subscriber.
+/// Subscriptions falling further behind are closed.
+const CHAIN_EVENTS_CAPACITY: usize = 1024;
+
+/// Change of the blocks stored in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// New blocks were stored, together with the logs of their transactions
+    BlocksStored {
+        blocks: Vec<Block<H256>>,
+        logs: Vec<TransactionReceiptLog>,
+    },
+    /// Blocks were discarded by a chain reorganization;
+    /// contains the logs of their transactions, marked as removed
+    LogsRemoved(Vec<TransactionReceiptLog>),
+}
+
+/// Broadcasts the chain events to the subscribers
+#[derive(Debug, Clone)]
+pub struct ChainEvents {
+    sender: broadcast::Sender<Arc<ChainEvent>>,
+}
+
+impl Default for ChainEvents {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CHAIN_EVENTS_CAPACITY).0,
+        }
+    }
+}
+
+impl ChainEvents {
+    /// Sends the event to the current subscribers
+    pub fn publish(&self, event: ChainEvent) {
+        // Sending fails only if there are no subscribers
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    /// Returns a receiver of the events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ChainEvent>> {
+        self.sender.subscribe()
+    }
+}
+
+/// Kind of the `eth_subscribe` subscription
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubscriptionKind {
+    /// The headers of the new blocks
+    NewHeads,
+    /// The logs of the new blocks matching a filter,
+    /// and the ones of the discarded blocks marked as removed
+    Logs,
+}
+
+/// eth_subscribe and eth_unsubscribe, served over WebSocket
+#[rpc(server, namespace = "eth")]
+pub trait EthPubSub {
+    #[subscription(name = "subscribe" => "subscription", unsubscribe = "unsubscribe", item = serde_json::Value)]
+    /// Subscribes to the new blocks or to the logs matching the filter.
+    /// The block range of the filter is ignored.
+    async fn subscribe(
+        &self,
+        kind: SubscriptionKind,
+        filter: Option<serde_json::Value>,
+    ) -> SubscriptionResult;
+}
+
+pub struct EthPubSubImpl {
+    pub chain_events: ChainEvents,
+}
+
+impl EthPubSubImpl {
+    pub fn new(chain_events: ChainEvents) -> Self {
+        Self { chain_events }
+    }
+}
+
+#[jsonrpsee::core::async_trait]
+impl EthPubSubServer for EthPubSubImpl {
+    async fn subscribe(
+        &self,
+        pending: PendingSubscriptionSink,
+        kind: SubscriptionKind,
+        filter: Option<serde_json::Value>,
+    ) -> SubscriptionResult {
+        let logs_filter = match kind {
+            SubscriptionKind::NewHeads => None,
+            SubscriptionKind::Logs => match LogsFilter::new(filter) {
+                Ok(filter) => Some(filter),
+                Err(e) => {
+                    pending.reject(e).await;
+                    return Ok(());
+                }
+            },
+        };
+
+        // Subscribe before accepting, so that no event following the subscription is missed
+        let events = self.chain_events.subscribe();
+        let sink = pending.accept().await?;
+
+        notify(sink, events, logs_filter).await
+    }
+}
+
+/// Sends the notifications of the chain events to the subscriber until it unsubscribes.
+/// Only the headers of the new blocks are sent if `logs_filter` is `None`.
+async fn notify(
+    sink: SubscriptionSink,
+    mut events: broadcast::Receiver<Arc<ChainEvent>>,
+    logs_filter: Option<LogsFilter>,
+) -> SubscriptionResult {
+    loop {
+        let event = tokio::select! {
+            _ = sink.closed() => return Ok(()),
+            event = events.recv() => event,
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                warn!(
+                    "Subscription {:?} missed {missed} chain events",
+                    sink.subscription_id()
+                );
+                return Err(
+                    format!("Subscription closed after missing {missed} chain events").into(),
+                );
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+
+        let notifications = match (event.as_ref(), &logs_filter) {
+            (ChainEvent::BlocksStored { blocks, .. }, None) => blocks
+                .iter()
+                .map(header_notification)
+                .collect::<Result<Vec<_>, _>>()?,
+            (ChainEvent::LogsRemoved(_), None) => vec![],
+            (
+                ChainEvent::BlocksStored { logs, .. } | ChainEvent::LogsRemoved(logs),
+                Some(filter),
+            ) => logs
+                .iter()
+                .filter(|log| filter.matches(log))
+                .map(serde_json::value::to_raw_value)
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        for notification in notifications {
+            if sink.send(notification).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Serializes the header of the block, that is the block without its `transactions`
+fn header_notification(block: &Block<H256>) -> serde_json::Result<Box<RawValue>> {
+    let mut header = serde_json::to_value(block)?;
+    if let Some(fields) = header.as_object_mut() {
+        fields.remove("transactions");
+    }
+
+    serde_json::value::to_raw_value(&header)
+}
+
+/// Addresses and topics of the logs of a `logs` subscription
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct LogsFilter {
+    /// Addresses of the contracts that emitted the logs; empty matches any address
+    addresses: Vec<H160>,
+    /// Accepted topics by position; an empty position matches any topic
+    topics: Vec<Vec<H256>>,
+}
+
+impl LogsFilter {
+    /// Parses the filter of the subscription; a missing filter matches every log
+    fn new(filter: Option<serde_json::Value>) -> Result<Self, ErrorObject<'static>> {
+        let Some(filter) = filter else {
+            return Ok(Self::default());
+        };
+        let filter = LogFilter::try_from(filter).map_err(|e| invalid_params(e.message))?;
+
+        let topics = filter.topics.unwrap_or_default();
+        if topics.len() > MAX_LOG_TOPICS {
+            return Err(invalid_params(format!(
+                "too many topics: expected at most {MAX_LOG_TOPICS}"
+            )));
+        }
+
+        Ok(Self {
+            addresses: filter.address.map(|a| a.0).unwrap_or_default(),
+            topics: topics
+                .into_iter()
+                .map(|topic| topic.map(|t| t.0).unwrap_or_default())
+                .collect(),
+        })
+    }
+
+    fn matches(&self, log: &TransactionReceiptLog) -> bool {
+        (self.addresses.is_empty() || self.addresses.contains(&log.address))
+            && self.topics.iter().enumerate().all(|(position, topics)| {
+                topics.is_empty()
+                    || log
+                        .topics
+                        .get(position)
+                        .is_some_and(|topic| topics.contains(topic))
+            })
+    }
+}
@@ -0,0 +1,513 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use alloy::consensus::TxEnvelope;
+use alloy::consensus::transaction::SignerRecoverable;
+use alloy::rlp::{Decodable, EMPTY_LIST_CODE, Encodable, Header};
+use clap::ValueEnum;
+use did::block::calculate_block_hash;
+use did::{Block, H256, Transaction, TransactionReceipt};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use log::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::database::{AccountBalance, DatabaseClient};
+use crate::task::block_extractor::validate_chain;
+
+/// Name of the manifest file in an archive directory
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Number of blocks inserted in the database at once by the import
+const IMPORT_BATCH_SIZE: usize = 100;
+
+/// Format of the blocks in the archive files
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// One JSON object per line, with a block, its transactions and their receipts
+    Jsonl,
+    /// Concatenated RLP encoded blocks with their transactions, as exported by geth.
+    /// Receipts are not included.
+    Rlp,
+}
+
+impl ArchiveFormat {
+    /// Extension of the compressed archive files
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Jsonl => "jsonl.gz",
+            ArchiveFormat::Rlp => "rlp.gz",
+        }
+    }
+}
+
+/// A block with its transactions and their receipts, as written in the JSONL archive files
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedBlock {
+    pub block: Block<Transaction>,
+    pub receipts: Vec<TransactionReceipt>,
+}
+
+/// An archive file holding a chunk of the exported blocks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveChunk {
+    /// Name of the file in the archive directory
+    pub file_name: String,
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Hex encoded SHA-256 digest of the compressed file
+    pub sha256: String,
+}
+
+/// Description of an archive, written in its manifest file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifest {
+    pub format: ArchiveFormat,
+    pub chain_id: Option<u64>,
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Hash of the block `to_block`
+    pub last_block_hash: H256,
+    pub genesis_balances: Option<Vec<AccountBalance>>,
+    /// The archive files, in ascending block order
+    pub chunks: Vec<ArchiveChunk>,
+}
+
+/// Exports the blocks from `from_block` to `to_block` to gzip compressed files in `output_dir`,
+/// `blocks_per_file` blocks per file, along with a manifest describing the archive.
+/// `from_block` and `to_block` default to the earliest and latest blocks in the database.
+/// The exported range must have no missing blocks.
+pub async fn start_export<DB: DatabaseClient>(
+    db_client: Arc<DB>,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    output_dir: &Path,
+    format: ArchiveFormat,
+    blocks_per_file: u64,
+) -> anyhow::Result<ArchiveManifest> {
+    anyhow::ensure!(blocks_per_file > 0, "blocks per file must be positive");
+
+    db_client.init(None, false).await?;
+
+    let Some(latest_block) = db_client.get_latest_block_number().await? else {
+        anyhow::bail!("No blocks in the database. Nothing to export");
+    };
+    let from_block = match from_block {
+        Some(from_block) => from_block,
+        None => db_client.get_earliest_block_number().await?,
+    };
+    let to_block = to_block.unwrap_or(latest_block);
+    anyhow::ensure!(
+        from_block <= to_block,
+        "Invalid block range: {from_block} > {to_block}"
+    );
+
+    let missing_ranges = db_client
+        .get_missing_block_ranges(from_block, to_block)
+        .await?;
+    anyhow::ensure!(
+        missing_ranges.is_empty(),
+        "Blocks {:?} are missing from the database. Run the backfill before exporting",
+        missing_ranges
+    );
+
+    info!(
+        "Exporting blocks from {from_block} to {to_block} to {}",
+        output_dir.display()
+    );
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut chunks = vec![];
+    for chunk_from in (from_block..=to_block).step_by(blocks_per_file as usize) {
+        let chunk_to = (chunk_from + blocks_per_file - 1).min(to_block);
+        let file_name = format!(
+            "blocks_{chunk_from:012}_{chunk_to:012}.{}",
+            format.file_extension()
+        );
+
+        // Each chunk is read with a query for its blocks and one for their receipts
+        let blocks = db_client
+            .get_full_blocks_by_range(chunk_from, chunk_to)
+            .await?;
+        anyhow::ensure!(
+            blocks.len() as u64 == chunk_to - chunk_from + 1,
+            "Blocks from {chunk_from} to {chunk_to} are missing from the database"
+        );
+        let mut receipts = match format {
+            ArchiveFormat::Jsonl => {
+                db_client
+                    .get_receipts_by_range(chunk_from, chunk_to)
+                    .await?
+            }
+            ArchiveFormat::Rlp => vec![],
+        }
+        .into_iter()
+        .peekable();
+
+        let mut writer = ChunkWriter::create(&output_dir.join(&file_name))?;
+        for block in blocks {
+            match format {
+                ArchiveFormat::Jsonl => {
+                    let block_number = block.number.as_u64();
+                    let mut block_receipts = vec![];
+                    while let Some(receipt) =
+                        receipts.next_if(|receipt| receipt.block_number.as_u64() == block_number)
+                    {
+                        block_receipts.push(receipt);
+                    }
+                    let archived_block = ArchivedBlock {
+                        block,
+                        receipts: block_receipts,
+                    };
+                    serde_json::to_writer(&mut writer, &archived_block)?;
+                    writer.write_all(b"\n")?;
+                }
+                ArchiveFormat::Rlp => {
+                    writer.write_all(&encode_block_rlp(&block)?)?;
+                }
+            }
+        }
+        let sha256 = writer.finish()?;
+
+        debug!("Exported blocks from {chunk_from} to {chunk_to} to {file_name}");
+        chunks.push(ArchiveChunk {
+            file_name,
+            from_block: chunk_from,
+            to_block: chunk_to,
+            sha256,
+        });
+    }
+
+    let manifest = ArchiveManifest {
+        format,
+        chain_id: db_client.get_chain_id().await?,
+        from_block,
+        to_block,
+        last_block_hash: db_client.get_block_by_number(to_block).await?.hash,
+        genesis_balances: db_client.get_genesis_balances().await?,
+        chunks,
+    };
+
+    let manifest_file = File::create(output_dir.join(MANIFEST_FILE_NAME))?;
+    serde_json::to_writer_pretty(BufWriter::new(manifest_file), &manifest)?;
+
+    info!("Export completed: {} files written", manifest.chunks.len());
+
+    Ok(manifest)
+}
+
+/// Imports the blocks of the archive in `input_dir` into the database.
+///
+/// The import resumes from the block following the latest one in the database, so an interrupted
+/// import can be restarted, and the archived blocks must extend the stored chain.
+/// Returns the number of imported blocks.
+pub async fn start_import<DB: DatabaseClient>(
+    db_client: Arc<DB>,
+    input_dir: &Path,
+) -> anyhow::Result<u64> {
+    let manifest_file = File::open(input_dir.join(MANIFEST_FILE_NAME))?;
+    let manifest: ArchiveManifest = serde_json::from_reader(BufReader::new(manifest_file))?;
+
+    db_client.init(None, false).await?;
+
+    if let Some(chain_id) = manifest.chain_id {
+        match db_client.get_chain_id().await? {
+            Some(stored_chain_id) => anyhow::ensure!(
+                stored_chain_id == chain_id,
+                "The archive chain id {chain_id} differs from the database chain id {stored_chain_id}"
+            ),
+            None => db_client.insert_chain_id(chain_id).await?,
+        }
+    }
+
+    if let Some(genesis_balances) = &manifest.genesis_balances {
+        if db_client.get_genesis_balances().await?.is_none() {
+            db_client.insert_genesis_balances(genesis_balances).await?;
+        }
+    }
+
+    let mut previous_block = match db_client.get_latest_block_number().await? {
+        Some(latest_block) => Some(db_client.get_block_by_number(latest_block).await?),
+        None => None,
+    };
+    let next_block = match &previous_block {
+        Some(block) => block.number.as_u64() + 1,
+        None => manifest.from_block,
+    };
+
+    if next_block > manifest.to_block {
+        let stored_hash = db_client.get_block_by_number(manifest.to_block).await?.hash;
+        anyhow::ensure!(
+            stored_hash == manifest.last_block_hash,
+            "The stored block {} has hash {stored_hash}, but the archive has {}",
+            manifest.to_block,
+            manifest.last_block_hash
+        );
+        info!("All the archived blocks are already in the database. Nothing to import");
+        return Ok(0);
+    }
+    anyhow::ensure!(
+        next_block >= manifest.from_block,
+        "The archive starts at block {}, but the database ends at block {}",
+        manifest.from_block,
+        next_block - 1
+    );
+
+    // The last archived block is checked before importing anything
+    let Some(last_chunk) = manifest.chunks.last() else {
+        anyhow::bail!("The archive has no files");
+    };
+    let last_block_hash = read_chunk(input_dir, last_chunk, manifest.format)?
+        .pop()
+        .map(|archived| archived.block.hash);
+    anyhow::ensure!(
+        last_chunk.to_block == manifest.to_block
+            && last_block_hash.as_ref() == Some(&manifest.last_block_hash),
+        "The last archived block hash {last_block_hash:?} differs from the manifest hash {}",
+        manifest.last_block_hash
+    );
+
+    info!(
+        "Importing blocks from {next_block} to {} from {}",
+        manifest.to_block,
+        input_dir.display()
+    );
+
+    let mut imported_blocks = 0;
+    for chunk in manifest
+        .chunks
+        .iter()
+        .filter(|chunk| chunk.to_block >= next_block)
+    {
+        let archived_blocks = read_chunk(input_dir, chunk, manifest.format)?
+            .into_iter()
+            .filter(|archived| archived.block.number.as_u64() >= next_block)
+            .collect::<Vec<_>>();
+
+        let blocks = archived_blocks
+            .iter()
+            .map(|archived| archived.block.clone())
+            .collect::<Vec<_>>();
+        validate_chain(previous_block.clone(), &blocks).map_err(|e| {
+            anyhow::anyhow!(
+                "Error importing {}: the blocks do not extend the stored chain: {e}",
+                chunk.file_name
+            )
+        })?;
+
+        for batch in archived_blocks.chunks(IMPORT_BATCH_SIZE) {
+            let transactions = batch
+                .iter()
+                .flat_map(|archived| &archived.block.transactions)
+                .cloned()
+                .collect::<Vec<_>>();
+            let receipts = batch
+                .iter()
+                .flat_map(|archived| &archived.receipts)
+                .cloned()
+                .collect::<Vec<_>>();
+            let blocks = batch
+                .iter()
+                .map(|archived| archived.block.clone().into())
+                .collect::<Vec<Block<H256>>>();
+
+            db_client
+                .insert_block_data(&blocks, &transactions, &receipts)
+                .await?;
+            imported_blocks += batch.len() as u64;
+        }
+
+        if let Some(last_block) = blocks.last() {
+            previous_block = Some(last_block.clone().into());
+        }
+        debug!(
+            "Imported blocks from {} to {}",
+            chunk.from_block, chunk.to_block
+        );
+    }
+
+    info!("Import completed: {imported_blocks} blocks imported");
+
+    Ok(imported_blocks)
+}
+
+/// Reads the blocks of an archive file, checking its digest
+fn read_chunk(
+    input_dir: &Path,
+    chunk: &ArchiveChunk,
+    format: ArchiveFormat,
+) -> anyhow::Result<Vec<ArchivedBlock>> {
+    let compressed = std::fs::read(input_dir.join(&chunk.file_name))?;
+    let sha256 = alloy::hex::encode(Sha256::digest(&compressed));
+    anyhow::ensure!(
+        sha256 == chunk.sha256,
+        "The digest of {} is {sha256}, but the manifest has {}",
+        chunk.file_name,
+        chunk.sha256
+    );
+
+    let mut decoder = GzDecoder::new(compressed.as_slice());
+    let archived_blocks = match format {
+        ArchiveFormat::Jsonl => BufReader::new(decoder)
+            .lines()
+            .map(|line| Ok(serde_json::from_str::<ArchivedBlock>(&line?)?))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        ArchiveFormat::Rlp => {
+            let mut data = Vec::new();
+            decoder.read_to_end(&mut data)?;
+
+            let mut buf = data.as_slice();
+            let mut archived_blocks = Vec::new();
+            while !buf.is_empty() {
+                let block = decode_block_rlp(&mut buf).map_err(|e| {
+                    anyhow::anyhow!("Error decoding block in {}: {:?}", chunk.file_name, e)
+                })?;
+                archived_blocks.push(ArchivedBlock {
+                    block,
+                    receipts: vec![],
+                });
+            }
+            archived_blocks
+        }
+    };
+
+    let block_numbers = archived_blocks
+        .iter()
+        .map(|archived| archived.block.number.as_u64());
+    anyhow::ensure!(
+        block_numbers.eq(chunk.from_block..=chunk.to_block),
+        "{} does not hold the blocks from {} to {}",
+        chunk.file_name,
+        chunk.from_block,
+        chunk.to_block
+    );
+
+    Ok(archived_blocks)
+}
+
+/// Decodes a block encoded by [`encode_block_rlp`], restoring the fields
+/// which are not part of the encoding: the block hash, the transactions
+/// location in the block and their senders.
+pub fn decode_block_rlp(buf: &mut &[u8]) -> anyhow::Result<Block<Transaction>> {
+    let mut block = Block::<Transaction>::decode(buf)?;
+    block.hash = calculate_block_hash(&block);
+
+    for (index, tx) in block.transactions.iter_mut().enumerate() {
+        let signer = TxEnvelope::try_from(tx.clone())
+            .map_err(|e| anyhow::anyhow!("Error decoding transaction {}: {:?}", tx.hash, e))?
+            .recover_signer()
+            .map_err(|e| anyhow::anyhow!("Error recovering signer of {}: {:?}", tx.hash, e))?;
+
+        tx.from = signer.into();
+        tx.block_hash = Some(block.hash.clone());
+        tx.block_number = Some(block.number);
+        tx.transaction_index = Some((index as u64).into());
+    }
+
+    Ok(block)
+}
+
+/// Encodes a block with its transactions as an Ethereum block: the list of
+/// the header, the transactions and the (empty) uncles.
+/// This is the encoding decoded by `Block<Transaction>`.
+pub fn encode_block_rlp(block: &Block<Transaction>) -> anyhow::Result<Vec<u8>> {
+    let header = block.header_rlp_encoded();
+
+    let mut transactions = Vec::new();
+    for tx in &block.transactions {
+        let encoded_tx = tx
+            .rlp_encoded_2718()
+            .map_err(|e| anyhow::anyhow!("Error encoding transaction {}: {:?}", tx.hash, e))?;
+
+        // Legacy transactions are RLP lists, typed transactions are wrapped in RLP strings
+        if encoded_tx
+            .first()
+            .is_some_and(|first_byte| *first_byte >= EMPTY_LIST_CODE)
+        {
+            transactions.extend_from_slice(&encoded_tx);
+        } else {
+            encoded_tx.encode(&mut transactions);
+        }
+    }
+
+    let transactions_header = Header {
+        list: true,
+        payload_length: transactions.len(),
+    };
+    let block_header = Header {
+        list: true,
+        payload_length: header.len() + transactions_header.length() + transactions.len() + 1,
+    };
+
+    let mut out = Vec::with_capacity(block_header.length() + block_header.payload_length);
+    block_header.encode(&mut out);
+    out.extend_from_slice(&header);
+    transactions_header.encode(&mut out);
+    out.extend_from_slice(&transactions);
+    out.push(EMPTY_LIST_CODE);
+
+    Ok(out)
+}
+
+/// Writes a gzip compressed archive file, computing the digest of the compressed data
+struct ChunkWriter {
+    encoder: GzEncoder<DigestWriter<BufWriter<File>>>,
+}
+
+impl ChunkWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let encoder = GzEncoder::new(
+            DigestWriter {
+                inner: file,
+                hasher: Sha256::new(),
+            },
+            Compression::default(),
+        );
+
+        Ok(Self { encoder })
+    }
+
+    /// Completes the file and returns the hex encoded SHA-256 digest of its content
+    fn finish(self) -> io::Result<String> {
+        let mut digest_writer = self.encoder.finish()?;
+        digest_writer.inner.flush()?;
+
+        Ok(alloy::hex::encode(digest_writer.hasher.finalize()))
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// Computes the SHA-256 digest of the data written to the inner writer
+struct DigestWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
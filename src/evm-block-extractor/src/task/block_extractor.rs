@@ -1,40 +1,59 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 
 use did::BlockNumber;
+use did::block::{calculate_block_hash, calculate_transactions_root};
 use did::evm_state::EvmGlobalState;
 use ethereum_json_rpc_client::{Client, EthJsonRpcClient};
+use futures::StreamExt;
 use log::*;
-use tokio::time::Duration;
+use tokio::time::{Duration, Instant};
 
+use crate::certificate::CertificateVerifier;
 use crate::config::ExtractorArgs;
-use crate::database::{AccountBalance, CertifiedBlock, DatabaseClient};
-
-/// Starts the block extractor process
+use crate::database::{
+    AccountBalance, CertifiedBlock, ChainReorg, DatabaseClient, LogsBlockFilter, LogsQuery,
+};
+use crate::metrics;
+use crate::subscription::{ChainEvent, ChainEvents};
+
+/// Starts the block extractor process.
+/// The changes of the stored blocks are published to `chain_events`.
 pub async fn start_extractor<C: Client, DB: DatabaseClient>(
     config: ExtractorArgs,
     db_client: Arc<DB>,
     evm_client: Arc<EthJsonRpcClient<C>>,
+    chain_events: ChainEvents,
 ) -> anyhow::Result<()> {
-    let earliest_block = evm_client
-        .get_block_by_number(BlockNumber::Earliest)
-        .await?;
-
-    db_client
-        .init(Some(earliest_block), config.reset_db_on_state_change)
-        .await?;
+    init_database(
+        db_client.as_ref(),
+        &evm_client,
+        config.reset_db_on_state_change,
+    )
+    .await?;
 
     let mut extractor = BlockExtractor::new(
         evm_client.clone(),
         config.request_time_out_secs,
         config.rpc_batch_size,
+        config.max_parallel_batches,
         db_client.clone(),
-    );
+    )
+    .with_certificate_verifier(config.certificate_verifier()?)
+    .with_integrity_verification(config.verify_block_integrity)
+    .with_max_reorg_depth(config.max_reorg_depth)
+    .with_chain_events(chain_events);
 
     let end_block = evm_client.get_block_number().await?;
     debug!("latest block number in evm: {}", end_block);
+    metrics::set_remote_head(end_block);
 
     let start_block = db_client.get_latest_block_number().await?;
     debug!("latest block number stored: {:?}", start_block);
+    if let Some(start_block) = start_block {
+        metrics::set_latest_block(start_block);
+    }
 
     extractor
         .collect_all(start_block.map(|b| b + 1).unwrap_or_default(), end_block)
@@ -43,12 +62,136 @@ pub async fn start_extractor<C: Client, DB: DatabaseClient>(
     Ok(())
 }
 
+/// Starts the block extractor in head-following mode.
+/// A single extractor is kept alive and polls the EVMC for new blocks;
+/// the polling interval grows while no new blocks are found and backs off on errors.
+/// The database is initialized in the same loop, so that an unreachable EVMC at startup
+/// is retried as well. The changes of the stored blocks are published to `chain_events`.
+/// This function only returns if the configuration is invalid.
+pub async fn follow_chain<C: Client, DB: DatabaseClient>(
+    config: ExtractorArgs,
+    db_client: Arc<DB>,
+    evm_client: Arc<EthJsonRpcClient<C>>,
+    chain_events: ChainEvents,
+) -> anyhow::Result<()> {
+    let mut extractor = BlockExtractor::new(
+        evm_client.clone(),
+        config.request_time_out_secs,
+        config.rpc_batch_size,
+        config.max_parallel_batches,
+        db_client.clone(),
+    )
+    .with_certificate_verifier(config.certificate_verifier()?)
+    .with_integrity_verification(config.verify_block_integrity)
+    .with_max_reorg_depth(config.max_reorg_depth)
+    .with_chain_events(chain_events);
+
+    let min_poll_interval = Duration::from_millis(config.follow_min_poll_interval_millis);
+    let max_poll_interval =
+        Duration::from_millis(config.follow_max_poll_interval_millis).max(min_poll_interval);
+    let max_error_backoff =
+        Duration::from_secs(config.follow_max_error_backoff_secs).max(min_poll_interval);
+
+    let mut initialized = false;
+    let mut poll_interval = min_poll_interval;
+    loop {
+        let result = if initialized {
+            extractor.collect_new_blocks().await
+        } else {
+            init_database(
+                db_client.as_ref(),
+                &evm_client,
+                config.reset_db_on_state_change,
+            )
+            .await
+            .map(|()| {
+                initialized = true;
+                true
+            })
+        };
+
+        poll_interval = match result {
+            Ok(true) => min_poll_interval,
+            Ok(false) => (poll_interval * 2).min(max_poll_interval),
+            Err(e) => {
+                let backoff = (poll_interval * 2).min(max_error_backoff);
+                warn!("Error following the chain: {e:?}. Retrying in {backoff:?}");
+                backoff
+            }
+        };
+
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Initializes the database with the earliest block of the EVMC
+async fn init_database<C: Client, DB: DatabaseClient>(
+    db_client: &DB,
+    evm_client: &EthJsonRpcClient<C>,
+    reset_database: bool,
+) -> anyhow::Result<()> {
+    let earliest_block = evm_client
+        .get_block_by_number(BlockNumber::Earliest)
+        .await?;
+
+    db_client.init(Some(earliest_block), reset_database).await
+}
+
+/// Scans the database for missing blocks and orphan transactions,
+/// and refetches the affected blocks from the EVMC.
+/// The scan covers the blocks from `from_block` to `to_block`, which default
+/// to the earliest and latest blocks in the database.
+pub async fn start_backfill<C: Client, DB: DatabaseClient>(
+    config: ExtractorArgs,
+    db_client: Arc<DB>,
+    evm_client: Arc<EthJsonRpcClient<C>>,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+) -> anyhow::Result<()> {
+    db_client.init(None, false).await?;
+
+    let Some(latest_block) = db_client.get_latest_block_number().await? else {
+        info!("No blocks in the database. Nothing to backfill");
+        return Ok(());
+    };
+    let from_block = match from_block {
+        Some(from_block) => from_block,
+        None => db_client.get_earliest_block_number().await?,
+    };
+    let to_block = to_block.unwrap_or(latest_block);
+
+    let extractor = BlockExtractor::new(
+        evm_client,
+        config.request_time_out_secs,
+        config.rpc_batch_size,
+        config.max_parallel_batches,
+        db_client,
+    )
+    .with_integrity_verification(config.verify_block_integrity);
+
+    let repaired_ranges = extractor.backfill(from_block, to_block).await?;
+    info!(
+        "Backfill from {from_block} to {to_block} completed. Repaired ranges: {:?}",
+        repaired_ranges
+    );
+
+    Ok(())
+}
+
+/// Default maximum number of stored blocks discarded by a chain reorganization
+pub const DEFAULT_MAX_REORG_DEPTH: u64 = 1000;
+
 /// Extracts blocks from an EVMC and stores them in a database
 pub struct BlockExtractor<C: Client, DB: DatabaseClient> {
     client: Arc<EthJsonRpcClient<C>>,
     request_time_out_secs: u64,
     rpc_batch_size: usize,
+    max_parallel_batches: usize,
     blockchain: Arc<DB>,
+    certificate_verifier: Option<CertificateVerifier>,
+    verify_integrity: bool,
+    max_reorg_depth: u64,
+    chain_events: Option<ChainEvents>,
 }
 
 /// Outcome of the block extraction process
@@ -65,16 +208,53 @@ impl<C: Client, DB: DatabaseClient> BlockExtractor<C, DB> {
         client: Arc<EthJsonRpcClient<C>>,
         request_time_out_secs: u64,
         rpc_batch_size: usize,
+        max_parallel_batches: usize,
         blockchain: Arc<DB>,
     ) -> Self {
         Self {
             client,
             blockchain,
             rpc_batch_size,
+            max_parallel_batches,
             request_time_out_secs,
+            certificate_verifier: None,
+            verify_integrity: false,
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+            chain_events: None,
         }
     }
 
+    /// Sets the verifier of the certified blocks.
+    /// Certified blocks with an invalid certificate are not stored.
+    pub fn with_certificate_verifier(
+        mut self,
+        certificate_verifier: Option<CertificateVerifier>,
+    ) -> Self {
+        self.certificate_verifier = certificate_verifier;
+        self
+    }
+
+    /// Sets whether the hash and the transactions root of the fetched blocks
+    /// are recomputed from their content. Blocks not matching their content are not stored.
+    pub fn with_integrity_verification(mut self, verify_integrity: bool) -> Self {
+        self.verify_integrity = verify_integrity;
+        self
+    }
+
+    /// Sets the maximum number of stored blocks discarded by a chain reorganization.
+    /// Deeper reorganizations are not recovered and make the extraction fail.
+    pub fn with_max_reorg_depth(mut self, max_reorg_depth: u64) -> Self {
+        self.max_reorg_depth = max_reorg_depth;
+        self
+    }
+
+    /// Sets the channel publishing the blocks stored by the extractor
+    /// and the logs removed by the chain reorganizations
+    pub fn with_chain_events(mut self, chain_events: ChainEvents) -> Self {
+        self.chain_events = Some(chain_events);
+        self
+    }
+
     /// Collects blocks from the EVMC and stores them in the database.
     /// Returns the inclusive range of blocks that were collected.
     /// This collects also the genesis accounts if needed.
@@ -131,18 +311,45 @@ impl<C: Client, DB: DatabaseClient> BlockExtractor<C, DB> {
             from_block_inclusive, to_block_inclusive
         );
 
+        let extractor: &Self = self;
         let mut next_from = from_block_inclusive;
 
         while next_from <= to_block_inclusive {
-            let evm_blocks = self.fetch_new_blocks(next_from, to_block_inclusive).await?;
+            // Up to `max_parallel_batches` batches are fetched concurrently,
+            // but they are validated and persisted strictly in order.
+            let mut batches = futures::stream::iter(
+                (next_from..=to_block_inclusive).step_by(extractor.rpc_batch_size),
+            )
+            .map(|from| async move {
+                let evm_blocks = extractor.fetch_new_blocks(from, to_block_inclusive).await?;
+                let receipts = extractor.fetch_receipts(&evm_blocks).await?;
+                anyhow::Ok((evm_blocks, receipts))
+            })
+            .buffered(extractor.max_parallel_batches.max(1));
 
-            if let Some(last_new_block) = evm_blocks.last() {
-                next_from = last_new_block.number.as_u64() + 1;
-            }
+            while let Some(batch) = batches.next().await {
+                let (evm_blocks, receipts) = batch?;
 
-            self.validate(&evm_blocks).await?;
+                let Some(last_new_block) = evm_blocks.last() else {
+                    anyhow::bail!("No blocks returned starting from block {next_from}");
+                };
+                let batch_end = last_new_block.number.as_u64() + 1;
+                let expected_batch_end =
+                    (to_block_inclusive + 1).min(next_from + extractor.rpc_batch_size as u64);
 
-            self.persist_data(evm_blocks).await?;
+                extractor.validate(&evm_blocks).await?;
+                let blocks = extractor.persist_data(evm_blocks, &receipts).await?;
+                extractor.publish_stored_blocks(blocks, receipts);
+                metrics::set_latest_block(batch_end - 1);
+
+                next_from = batch_end;
+
+                // The batches fetched in the meantime don't follow a short batch,
+                // so fetching restarts from the first missing block.
+                if batch_end != expected_batch_end {
+                    break;
+                }
+            }
         }
 
         if let Some(block_info) = block_info {
@@ -157,6 +364,118 @@ impl<C: Client, DB: DatabaseClient> BlockExtractor<C, DB> {
         })
     }
 
+    /// Collects the blocks added to the EVMC after the latest block in the database.
+    /// Returns whether new blocks were extracted.
+    pub async fn collect_new_blocks(&mut self) -> anyhow::Result<bool> {
+        let end_block = tokio::time::timeout(
+            Duration::from_secs(self.request_time_out_secs),
+            self.client.get_block_number(),
+        )
+        .await??;
+        metrics::set_remote_head(end_block);
+
+        let latest_block = self.blockchain.get_latest_block_number().await?;
+        if let Some(latest_block) = latest_block {
+            metrics::set_latest_block(latest_block);
+        }
+        let start_block = latest_block.map(|b| b + 1).unwrap_or_default();
+
+        if start_block > end_block {
+            debug!("No new blocks after block {end_block}");
+            return Ok(false);
+        }
+
+        match self.collect_all(start_block, end_block).await? {
+            BlockExtractCollectOutcome::BlocksExtracted { .. } => Ok(true),
+            BlockExtractCollectOutcome::BlocksNotExtracted => Ok(false),
+        }
+    }
+
+    /// Repairs the blocks from `from_block_inclusive` to `to_block_inclusive`:
+    /// - the transactions, receipts and logs in the range whose block is missing are deleted;
+    /// - the missing blocks in the range, including the blocks of the deleted transactions,
+    ///   are refetched from the EVMC and stored.
+    ///
+    /// Refetched blocks must link to the stored blocks around them, otherwise the process fails.
+    /// Returns the inclusive ranges of blocks that were refetched.
+    pub async fn backfill(
+        &self,
+        from_block_inclusive: u64,
+        to_block_inclusive: u64,
+    ) -> anyhow::Result<Vec<RangeInclusive<u64>>> {
+        let orphan_block_numbers = self
+            .blockchain
+            .get_orphan_transaction_block_numbers(from_block_inclusive, to_block_inclusive)
+            .await?;
+        if !orphan_block_numbers.is_empty() {
+            warn!(
+                "Found transactions of {} missing blocks. Deleting them",
+                orphan_block_numbers.len()
+            );
+            self.blockchain
+                .delete_block_transactions(&orphan_block_numbers)
+                .await?;
+        }
+
+        // The blocks of the orphan transactions are found as missing blocks from now on
+        let missing_ranges = self
+            .blockchain
+            .get_missing_block_ranges(from_block_inclusive, to_block_inclusive)
+            .await?;
+
+        for range in &missing_ranges {
+            self.backfill_range(*range.start(), *range.end()).await?;
+        }
+
+        Ok(missing_ranges)
+    }
+
+    /// Fetches and stores the blocks of a range which is missing in the database.
+    async fn backfill_range(
+        &self,
+        from_block_inclusive: u64,
+        to_block_inclusive: u64,
+    ) -> anyhow::Result<()> {
+        info!("Backfilling blocks from {from_block_inclusive} to {to_block_inclusive}");
+
+        let mut previous_block = match from_block_inclusive.checked_sub(1) {
+            Some(block_number) => self.blockchain.find_block_by_number(block_number).await?,
+            None => None,
+        };
+        let next_block = self
+            .blockchain
+            .find_block_by_number(to_block_inclusive + 1)
+            .await?;
+
+        let mut next_from = from_block_inclusive;
+        while next_from <= to_block_inclusive {
+            let evm_blocks = self.fetch_new_blocks(next_from, to_block_inclusive).await?;
+            let Some(last_new_block) = evm_blocks.last() else {
+                anyhow::bail!("No blocks returned starting from block {next_from}");
+            };
+            let last_new_block = last_new_block.clone();
+
+            self.verify_integrity(&evm_blocks)?;
+            validate_chain(previous_block, &evm_blocks)?;
+            if last_new_block.number.as_u64() == to_block_inclusive {
+                if let Some(next_block) = &next_block {
+                    validate_chain(
+                        Some(last_new_block.clone()),
+                        std::slice::from_ref(next_block),
+                    )?;
+                }
+            }
+
+            let receipts = self.fetch_receipts(&evm_blocks).await?;
+            self.persist_data(evm_blocks, &receipts).await?;
+
+            next_from = last_new_block.number.as_u64() + 1;
+            previous_block = Some(last_new_block.into());
+        }
+
+        Ok(())
+    }
+
     /// Fetch new blocks from the EVM client.
     async fn fetch_new_blocks(
         &self,
@@ -171,18 +490,46 @@ impl<C: Client, DB: DatabaseClient> BlockExtractor<C, DB> {
         let block_numbers = blocks_batch
             .into_iter()
             .map(|block| BlockNumber::Number(block.into()));
+        let started_at = Instant::now();
         let evm_blocks = tokio::time::timeout(
             Duration::from_secs(request_time_out_secs),
             self.client
                 .get_full_blocks_by_number(block_numbers, batch_size),
         )
         .await??;
+        metrics::record_batch_fetch("blocks", started_at.elapsed());
         Ok(evm_blocks)
     }
 
+    /// Fetch the receipts of all the transactions in the given blocks from the EVM client.
+    async fn fetch_receipts(
+        &self,
+        evm_blocks: &[did::Block<did::Transaction>],
+    ) -> Result<Vec<did::TransactionReceipt>, anyhow::Error> {
+        let tx_hashes = evm_blocks
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .map(|tx| tx.hash.clone())
+            .collect::<Vec<_>>();
+
+        if tx_hashes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let started_at = Instant::now();
+        let receipts = tokio::time::timeout(
+            Duration::from_secs(self.request_time_out_secs),
+            self.client
+                .get_receipts_by_hash(tx_hashes, self.rpc_batch_size),
+        )
+        .await??;
+        metrics::record_batch_fetch("receipts", started_at.elapsed());
+        Ok(receipts)
+    }
+
     /// Validate chain consistency, including new blocks sequence.
     async fn validate(
-        &mut self,
+        &self,
         evm_blocks: &[did::Block<did::Transaction>],
     ) -> Result<(), anyhow::Error> {
         let latest_storage_block_number = self.blockchain.get_latest_block_number().await?;
@@ -191,7 +538,9 @@ impl<C: Client, DB: DatabaseClient> BlockExtractor<C, DB> {
             None => None,
         };
 
-        let validation_result = Self::validate_chain(latest_block, evm_blocks);
+        let validation_result = self
+            .verify_integrity(evm_blocks)
+            .and_then(|()| validate_chain(latest_block, evm_blocks));
         if let Err(e) = validation_result {
             self.process_validation_error(&e).await?;
             return Err(e.into());
@@ -200,11 +549,25 @@ impl<C: Client, DB: DatabaseClient> BlockExtractor<C, DB> {
         Ok(())
     }
 
-    /// Store the given blocks in database.
+    /// Verifies the integrity of the fetched blocks, if enabled
+    fn verify_integrity(
+        &self,
+        evm_blocks: &[did::Block<did::Transaction>],
+    ) -> Result<(), ChainError> {
+        if self.verify_integrity {
+            verify_blocks_integrity(evm_blocks)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Store the given blocks and receipts in database.
+    /// Returns the stored blocks, without their transactions.
     async fn persist_data(
-        &mut self,
+        &self,
         evm_blocks: Vec<did::Block<did::Transaction>>,
-    ) -> Result<(), anyhow::Error> {
+        receipts: &[did::TransactionReceipt],
+    ) -> Result<Vec<did::Block<did::H256>>, anyhow::Error> {
         let all_transactions = evm_blocks
             .iter()
             .flat_map(|block| &block.transactions)
@@ -216,22 +579,74 @@ impl<C: Client, DB: DatabaseClient> BlockExtractor<C, DB> {
             .map(|block| block.into())
             .collect::<Vec<did::Block<did::H256>>>();
 
+        let started_at = Instant::now();
         self.blockchain
-            .insert_block_data(&blocks, &all_transactions)
+            .insert_block_data(&blocks, &all_transactions, receipts)
             .await?;
+        metrics::record_db_write("insert_block_data", started_at.elapsed());
 
-        Ok(())
+        Ok(blocks)
+    }
+
+    /// Publishes the new blocks stored in the database with the logs of their receipts
+    fn publish_stored_blocks(
+        &self,
+        blocks: Vec<did::Block<did::H256>>,
+        receipts: Vec<did::TransactionReceipt>,
+    ) {
+        if let Some(chain_events) = &self.chain_events {
+            let logs = receipts
+                .into_iter()
+                .flat_map(|receipt| receipt.logs)
+                .collect();
+            chain_events.publish(ChainEvent::BlocksStored { blocks, logs });
+        }
+    }
+
+    /// Returns the logs of the blocks discarded by the reorganization, marked as removed
+    async fn removed_logs(
+        &self,
+        reorg: &ChainReorg,
+    ) -> anyhow::Result<Vec<did::transaction::TransactionReceiptLog>> {
+        let query = LogsQuery {
+            block_filter: LogsBlockFilter::Range {
+                from: reorg.first_discarded_block,
+                to: reorg.first_discarded_block + reorg.depth - 1,
+            },
+            addresses: vec![],
+            topics: vec![],
+            limit: None,
+        };
+        let mut logs = self.blockchain.get_logs(&query).await?;
+        for log in &mut logs {
+            log.removed = true;
+        }
+
+        Ok(logs)
     }
 
     /// Collects last certified block
     async fn collect_last_certified_block(&self) -> anyhow::Result<()> {
         let certified_block = self.client.get_last_certified_block().await?;
+        let certified_block = CertifiedBlock {
+            data: certified_block.data,
+            witness: certified_block.witness,
+            certificate: certified_block.certificate,
+        };
+
+        if let Some(verifier) = &self.certificate_verifier {
+            if let Err(e) = verifier.verify(&certified_block) {
+                warn!(
+                    "Invalid certificate for block {}: {e}. The certified block is not stored",
+                    certified_block.data.number.as_u64()
+                );
+                metrics::record_invalid_certificate();
+                return Ok(());
+            }
+        }
+
         self.blockchain
-            .insert_certified_block_data(CertifiedBlock {
-                data: certified_block.data,
-                witness: certified_block.witness,
-                certificate: certified_block.certificate,
-            })
+            .insert_certified_block_data(certified_block)
             .await?;
 
         Ok(())
@@ -291,93 +706,212 @@ impl<C: Client, DB: DatabaseClient> BlockExtractor<C, DB> {
         Ok(())
     }
 
-    /// This function:
-    /// - checks if the `new_blocks` sequence have correct hashes.
-    /// - checks if `latest_block_in_storage.hash == new_blocks[0].prev_block_hash`.
-    fn validate_chain<T1, T2>(
-        latest_block_in_storage: Option<did::Block<T1>>,
-        new_blocks: &[did::Block<T2>],
-    ) -> Result<(), ChainError> {
-        // if there are no blocks in storage, we don't need parent hash of
-        // first new block
-        let to_skip = if latest_block_in_storage.is_none() {
-            1
-        } else {
-            0
+    /// Finds the stored blocks which are not part of the EVMC blockchain anymore,
+    /// walking back from the latest stored block to the common ancestor of the two chains.
+    /// Returns `None` if all the stored blocks are part of the EVMC blockchain,
+    /// and an error if the common ancestor is deeper than `max_reorg_depth` blocks.
+    async fn find_chain_reorg(&self) -> anyhow::Result<Option<ChainReorg>> {
+        let Some(latest_block_number) = self.blockchain.get_latest_block_number().await? else {
+            return Ok(None);
         };
-        let new_blocks_parent_hashes = new_blocks.iter().map(|b| &b.parent_hash).skip(to_skip);
+        let earliest_block_number = self.blockchain.get_earliest_block_number().await?;
+        let remote_latest_block_number = tokio::time::timeout(
+            Duration::from_secs(self.request_time_out_secs),
+            self.client.get_block_number(),
+        )
+        .await??;
+        metrics::set_remote_head(remote_latest_block_number);
+
+        let mut first_discarded_block = None;
+        let mut new_hashes = BTreeMap::new();
+        let mut common_ancestor_found = false;
+
+        // Up to `max_reorg_depth` blocks are discarded, so the block before them is scanned too
+        let lowest_block_number =
+            earliest_block_number.max(latest_block_number.saturating_sub(self.max_reorg_depth));
+        for block_number in (lowest_block_number..=latest_block_number).rev() {
+            // Missing blocks are discarded only if they follow the common ancestor
+            let Ok(stored_block) = self.blockchain.get_block_by_number(block_number).await else {
+                continue;
+            };
+
+            if block_number <= remote_latest_block_number {
+                let remote_block = tokio::time::timeout(
+                    Duration::from_secs(self.request_time_out_secs),
+                    self.client
+                        .get_block_by_number(BlockNumber::Number(block_number.into())),
+                )
+                .await??;
+
+                if remote_block.hash == stored_block.hash {
+                    debug!("common ancestor found at block {block_number}");
+                    common_ancestor_found = true;
+                    break;
+                }
 
-        let latest_block_hash = latest_block_in_storage.map(|b| b.hash);
-        let all_blocks_hashes = latest_block_hash
-            .iter()
-            .chain(new_blocks.iter().map(|b| &b.hash));
+                new_hashes.insert(block_number, remote_block.hash);
+            }
 
-        let inconsistency = all_blocks_hashes
-            .zip(new_blocks_parent_hashes)
-            .enumerate()
-            .find(|(_, (block_hash, next_block_parent))| block_hash != next_block_parent);
+            first_discarded_block = Some(block_number);
+        }
 
-        match inconsistency {
-            Some((0, _)) if latest_block_hash.is_some() => Err(ChainError::InconsistentStorage),
-            Some(_) => Err(ChainError::InconsistentSequence),
-            None => Ok(()),
+        let reorg = first_discarded_block.map(|first_discarded_block| ChainReorg {
+            first_discarded_block,
+            depth: latest_block_number - first_discarded_block + 1,
+            new_hashes,
+        });
+
+        // The common ancestor is among the scanned blocks, unless all the stored blocks are discarded
+        let ancestor_not_scanned =
+            !common_ancestor_found && lowest_block_number > earliest_block_number;
+        if ancestor_not_scanned
+            || reorg
+                .as_ref()
+                .is_some_and(|reorg| reorg.depth > self.max_reorg_depth)
+        {
+            anyhow::bail!(
+                "No common ancestor with the EVMC blockchain in the latest {} stored blocks",
+                self.max_reorg_depth
+            );
         }
+
+        Ok(reorg)
     }
 
     /// Processes result of blocks sequnce validation:
-    /// - If error is in blocks sequence, do nothing
-    /// - If error in storage, discards all blocks after the safe block.
+    /// - If error is in blocks sequence or in the content of a block, do nothing
+    /// - If error in storage, discards the stored blocks after the common ancestor
+    ///   of the stored and the EVMC blockchains.
     async fn process_validation_error(&self, validation_error: &ChainError) -> anyhow::Result<()> {
         match validation_error {
             ChainError::InconsistentSequence => {
                 log::warn!("inconsistent blocks sequnce fetched");
             }
-            ChainError::InconsistentStorage => {
-                // Discard all blocks after the safe blocks
-                if let Some(first_block_to_discard) = self
-                    .blockchain
-                    .get_block_info()
-                    .await?
-                    .map(|info| info.safe_block_number + 1)
-                {
-                    log::warn!("Discarding blockchain tail starting with {first_block_to_discard}");
-
-                    self.blockchain
-                        .discard_blocks_from(first_block_to_discard, "inconsistent")
-                        .await?;
-                }
+            ChainError::InvalidBlockContent { .. } => {
+                log::warn!("{validation_error}. The fetched blocks are not stored");
             }
+            ChainError::InconsistentStorage => match self.find_chain_reorg().await? {
+                Some(reorg) => {
+                    log::warn!(
+                        "Chain reorganization of depth {} found. Discarding blockchain tail starting with {}",
+                        reorg.depth,
+                        reorg.first_discarded_block
+                    );
+
+                    // The logs are read before they are deleted with their blocks
+                    let removed_logs = match &self.chain_events {
+                        Some(_) => self.removed_logs(&reorg).await?,
+                        None => vec![],
+                    };
+
+                    let started_at = Instant::now();
+                    self.blockchain.discard_reorged_blocks(&reorg).await?;
+                    metrics::record_db_write("discard_blocks", started_at.elapsed());
+                    metrics::record_reorg(reorg.depth);
+                    metrics::set_latest_block(reorg.first_discarded_block.saturating_sub(1));
+
+                    if let Some(chain_events) = &self.chain_events {
+                        chain_events.publish(ChainEvent::LogsRemoved(removed_logs));
+                    }
+                }
+                None => {
+                    log::warn!(
+                        "inconsistent block in storage, but the stored blocks match the EVMC"
+                    );
+                }
+            },
         }
 
         Ok(())
     }
 }
 
+/// This function:
+/// - checks if the `new_blocks` sequence have correct hashes.
+/// - checks if `latest_block_in_storage.hash == new_blocks[0].prev_block_hash`.
+pub(crate) fn validate_chain<T1, T2>(
+    latest_block_in_storage: Option<did::Block<T1>>,
+    new_blocks: &[did::Block<T2>],
+) -> Result<(), ChainError> {
+    // if there are no blocks in storage, we don't need parent hash of
+    // first new block
+    let to_skip = if latest_block_in_storage.is_none() {
+        1
+    } else {
+        0
+    };
+    let new_blocks_parent_hashes = new_blocks.iter().map(|b| &b.parent_hash).skip(to_skip);
+
+    let latest_block_hash = latest_block_in_storage.map(|b| b.hash);
+    let all_blocks_hashes = latest_block_hash
+        .iter()
+        .chain(new_blocks.iter().map(|b| &b.hash));
+
+    let inconsistency = all_blocks_hashes
+        .zip(new_blocks_parent_hashes)
+        .enumerate()
+        .find(|(_, (block_hash, next_block_parent))| block_hash != next_block_parent);
+
+    match inconsistency {
+        Some((0, _)) if latest_block_hash.is_some() => Err(ChainError::InconsistentStorage),
+        Some(_) => Err(ChainError::InconsistentSequence),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the hash and the transactions root of each block
+/// are the ones computed from its header and its transactions.
+pub(crate) fn verify_blocks_integrity(
+    blocks: &[did::Block<did::Transaction>],
+) -> Result<(), ChainError> {
+    for block in blocks {
+        let invalid_content = |reason: String| ChainError::InvalidBlockContent {
+            block_number: block.number.as_u64(),
+            reason,
+        };
+
+        let block_hash = calculate_block_hash(block);
+        if block_hash != block.hash {
+            return Err(invalid_content(format!(
+                "hash {} but the header hashes to {block_hash}",
+                block.hash
+            )));
+        }
+
+        let transactions_root = calculate_transactions_root(&block.transactions)
+            .map_err(|e| invalid_content(format!("can't encode the transactions: {e}")))?;
+        if transactions_root != block.transactions_root {
+            return Err(invalid_content(format!(
+                "transactions root {} but the transactions have root {transactions_root}",
+                block.transactions_root
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
-enum ChainError {
+pub(crate) enum ChainError {
     #[error("inconsistent block in storage")]
     InconsistentStorage,
     #[error("inconsistent block in new blocks sequence")]
     InconsistentSequence,
+    #[error("block {block_number} doesn't match its content: {reason}")]
+    InvalidBlockContent { block_number: u64, reason: String },
 }
 
 #[cfg(test)]
 mod tests {
     use did::keccak;
-    use ethereum_json_rpc_client::reqwest::ReqwestClient;
 
     use super::*;
-    use crate::database::postgres_db_client::PostgresDbClient;
 
     #[test]
     fn test_validate_chain_without_blocks_in_storage() {
         let latest_block_in_storage = Option::<did::Block<did::H256>>::None;
         let sequence = generate_valid_blocks_sequence(10, did::H256::default());
-        BlockExtractor::<ReqwestClient, PostgresDbClient>::validate_chain(
-            latest_block_in_storage,
-            &sequence,
-        )
-        .unwrap();
+        validate_chain(latest_block_in_storage, &sequence).unwrap();
     }
 
     #[test]
@@ -388,11 +922,7 @@ mod tests {
         let hash = block.hash.clone();
         let latest_block_in_storage = Some(block);
         let sequence = generate_valid_blocks_sequence(10, hash);
-        BlockExtractor::<ReqwestClient, PostgresDbClient>::validate_chain(
-            latest_block_in_storage,
-            &sequence,
-        )
-        .unwrap();
+        validate_chain(latest_block_in_storage, &sequence).unwrap();
     }
 
     #[test]
@@ -403,11 +933,7 @@ mod tests {
         let latest_block_in_storage = Some(block);
         let invalid_parent_hash = keccak::keccak_hash(&[1, 2, 3]);
         let sequence = generate_valid_blocks_sequence(10, invalid_parent_hash);
-        let err = BlockExtractor::<ReqwestClient, PostgresDbClient>::validate_chain(
-            latest_block_in_storage,
-            &sequence,
-        )
-        .unwrap_err();
+        let err = validate_chain(latest_block_in_storage, &sequence).unwrap_err();
         assert!(matches!(err, ChainError::InconsistentStorage))
     }
 
@@ -423,14 +949,49 @@ mod tests {
         // break the sequnce
         sequence[5].parent_hash = keccak::keccak_hash(&[1, 2, 3, 4]);
 
-        let err = BlockExtractor::<ReqwestClient, PostgresDbClient>::validate_chain(
-            latest_block_in_storage,
-            &sequence,
-        )
-        .unwrap_err();
+        let err = validate_chain(latest_block_in_storage, &sequence).unwrap_err();
         assert!(matches!(err, ChainError::InconsistentSequence))
     }
 
+    #[test]
+    fn test_verify_blocks_integrity() {
+        let mut tx = did::Transaction {
+            gas_price: Some(1u64.into()),
+            transaction_type: Some(0u64.into()),
+            v: 27u64.into(),
+            r: 1u64.into(),
+            s: 1u64.into(),
+            ..Default::default()
+        };
+        tx.hash = tx.slow_hash().unwrap().0;
+
+        let mut block = did::Block {
+            number: 5u64.into(),
+            transactions: vec![tx.hash.clone()],
+            transactions_root: calculate_transactions_root(std::slice::from_ref(&tx)).unwrap(),
+            ..Default::default()
+        };
+        block.hash = calculate_block_hash(&block);
+        let block = block.into_full_block(vec![tx]).unwrap();
+        verify_blocks_integrity(std::slice::from_ref(&block)).unwrap();
+
+        let mut invalid_hash = block.clone();
+        invalid_hash.gas_used = 21000u64.into();
+        let err = verify_blocks_integrity(&[block.clone(), invalid_hash]).unwrap_err();
+        assert!(matches!(
+            err,
+            ChainError::InvalidBlockContent {
+                block_number: 5,
+                ..
+            }
+        ));
+
+        let mut invalid_transactions = block;
+        invalid_transactions.transactions[0].nonce = 1u64.into();
+        let err = verify_blocks_integrity(&[invalid_transactions]).unwrap_err();
+        assert!(matches!(err, ChainError::InvalidBlockContent { .. }));
+    }
+
     fn generate_valid_blocks_sequence(
         len: usize,
         parent_hash: did::H256,
@@ -1 +1,3 @@
+pub mod archive;
 pub mod block_extractor;
+pub mod retention;
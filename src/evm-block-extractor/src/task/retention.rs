@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use log::*;
+
+use crate::database::DatabaseClient;
+
+/// Retention policy of the blocks in the database.
+/// When both the number of blocks and the number of days are set,
+/// the blocks kept by either of them are not pruned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Number of latest blocks to keep
+    pub keep_blocks: Option<u64>,
+    /// Number of days of blocks to keep, by block timestamp
+    pub keep_days: Option<u64>,
+    /// Keep the headers of the pruned blocks, deleting only their transactions, receipts and logs
+    pub headers_only: bool,
+    /// Maximum number of blocks pruned by a single database transaction
+    pub batch_size: u64,
+}
+
+/// Prunes the blocks and the discarded blocks falling outside the retention policy,
+/// in batches of at most `policy.batch_size` blocks.
+pub async fn start_pruning<DB>(db_client: Arc<DB>, policy: &RetentionPolicy) -> anyhow::Result<()>
+where
+    DB: DatabaseClient,
+{
+    if policy.keep_blocks.is_none() && policy.keep_days.is_none() {
+        return Ok(());
+    }
+
+    let Some(latest_block) = db_client.get_latest_block_number().await? else {
+        info!("No blocks to prune");
+        return Ok(());
+    };
+    let earliest_block = db_client.get_earliest_block_number().await?;
+
+    let mut first_kept_block = latest_block + 1;
+    let mut discarded_before: Option<DateTime<Utc>> = None;
+
+    if let Some(keep_blocks) = policy.keep_blocks {
+        let first_block = (latest_block + 1).saturating_sub(keep_blocks);
+        first_kept_block = first_kept_block.min(first_block);
+
+        // Discarded blocks are kept as long as the blocks produced after them.
+        // The first kept block may be missing, so the next stored one is used.
+        if first_block > earliest_block {
+            if let Some(block) = db_client.find_first_block_from(first_block).await? {
+                let timestamp = block.timestamp.0.saturating_to::<i64>();
+                discarded_before = DateTime::from_timestamp(timestamp, 0);
+            }
+        }
+    }
+
+    if let Some(keep_days) = policy.keep_days {
+        let cutoff = i64::try_from(keep_days)
+            .ok()
+            .and_then(TimeDelta::try_days)
+            .and_then(|keep| Utc::now().checked_sub_signed(keep))
+            .ok_or_else(|| anyhow::anyhow!("Retention of {keep_days} days is out of range"))?;
+        let first_block = first_block_since(
+            db_client.as_ref(),
+            earliest_block,
+            latest_block,
+            cutoff.timestamp().max(0) as u64,
+        )
+        .await?;
+        first_kept_block = first_kept_block.min(first_block);
+
+        discarded_before = Some(match discarded_before {
+            Some(discarded_before) => discarded_before.min(cutoff),
+            None => cutoff,
+        });
+    }
+
+    let batch_size = policy.batch_size.max(1);
+    while let Some(last_pruned_block) = db_client
+        .prune_blocks_before(first_kept_block, batch_size, policy.headers_only)
+        .await?
+    {
+        debug!("Pruned blocks up to {last_pruned_block}");
+    }
+
+    if let Some(discarded_before) = discarded_before {
+        loop {
+            let pruned = db_client
+                .prune_discarded_blocks(discarded_before, batch_size)
+                .await?;
+            if pruned < batch_size {
+                break;
+            }
+        }
+    }
+
+    info!("Pruned blocks before {first_kept_block}");
+
+    Ok(())
+}
+
+/// Returns the number of the first block between `earliest_block` and `latest_block`
+/// with a timestamp not older than `timestamp`, or `latest_block + 1` if all of them are older.
+/// Block timestamps never decrease, so the block is found by binary search.
+/// The search tolerates missing blocks, by probing the first stored block from each middle point.
+async fn first_block_since<DB>(
+    db_client: &DB,
+    earliest_block: u64,
+    latest_block: u64,
+    timestamp: u64,
+) -> anyhow::Result<u64>
+where
+    DB: DatabaseClient,
+{
+    let (mut low, mut high) = (earliest_block, latest_block + 1);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        // The latest block is stored, so a block is always found
+        let block = db_client
+            .find_first_block_from(middle)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No block found from {middle}"))?;
+        if block.timestamp.0.saturating_to::<u64>() < timestamp {
+            low = block.number.as_u64() + 1;
+        } else {
+            high = middle;
+        }
+    }
+
+    Ok(low)
+}
@@ -0,0 +1,13 @@
+-----------------------------------------
+-- Begin - EVM_TRANSACTION_RECEIPT -
+-----------------------------------------
+
+create table EVM_TRANSACTION_RECEIPT (
+    ID char(66) primary key, -- 64 is the length of a H256 in hex, plus 0x
+    DATA JSONB,
+    BLOCK_NUMBER bigint
+);
+
+CREATE INDEX EVM_TRANSACTION_RECEIPT_INDEX_BLOCK_NUMBER ON EVM_TRANSACTION_RECEIPT( BLOCK_NUMBER );
+
+-- End - EVM_TRANSACTION_RECEIPT -
@@ -0,0 +1,48 @@
+-----------------------------------------
+-- Begin - EVM_LOG -
+-----------------------------------------
+
+create table EVM_LOG (
+    TRANSACTION_HASH char(66) not null, -- 64 is the length of a H256 in hex, plus 0x
+    LOG_INDEX bigint not null, -- index of the log within the transaction
+    TRANSACTION_INDEX bigint not null,
+    BLOCK_NUMBER bigint not null,
+    BLOCK_HASH char(66) not null,
+    ADDRESS char(42) not null, -- 40 is the length of a H160 in hex, plus 0x
+    TOPIC0 char(66),
+    TOPIC1 char(66),
+    TOPIC2 char(66),
+    TOPIC3 char(66),
+    DATA JSONB,
+    primary key (TRANSACTION_HASH, LOG_INDEX)
+);
+
+CREATE INDEX EVM_LOG_INDEX_BLOCK_NUMBER ON EVM_LOG( BLOCK_NUMBER );
+CREATE INDEX EVM_LOG_INDEX_BLOCK_HASH ON EVM_LOG( BLOCK_HASH );
+CREATE INDEX EVM_LOG_INDEX_ADDRESS ON EVM_LOG( ADDRESS );
+CREATE INDEX EVM_LOG_INDEX_TOPIC0 ON EVM_LOG( TOPIC0 );
+CREATE INDEX EVM_LOG_INDEX_TOPIC1 ON EVM_LOG( TOPIC1 );
+CREATE INDEX EVM_LOG_INDEX_TOPIC2 ON EVM_LOG( TOPIC2 );
+CREATE INDEX EVM_LOG_INDEX_TOPIC3 ON EVM_LOG( TOPIC3 );
+
+-- Fill the table with the logs of the already stored receipts
+insert into EVM_LOG (
+    TRANSACTION_HASH, LOG_INDEX, TRANSACTION_INDEX, BLOCK_NUMBER, BLOCK_HASH,
+    ADDRESS, TOPIC0, TOPIC1, TOPIC2, TOPIC3, DATA
+)
+select
+    r.ID,
+    l.IDX - 1,
+    ('x' || lpad(substr(r.DATA->>'transactionIndex', 3), 16, '0'))::bit(64)::bigint,
+    r.BLOCK_NUMBER,
+    r.DATA->>'blockHash',
+    l.LOG->>'address',
+    l.LOG->'topics'->>0,
+    l.LOG->'topics'->>1,
+    l.LOG->'topics'->>2,
+    l.LOG->'topics'->>3,
+    l.LOG
+from EVM_TRANSACTION_RECEIPT r,
+    jsonb_array_elements(r.DATA->'logs') with ordinality as l(LOG, IDX);
+
+-- End - EVM_LOG -
@@ -0,0 +1,11 @@
+-----------------------------------------
+-- Begin - EVM_BLOCK hash -
+-----------------------------------------
+
+alter table EVM_BLOCK add column HASH char(66); -- 64 is the length of a H256 in hex, plus 0x
+
+update EVM_BLOCK set HASH = DATA->>'hash';
+
+CREATE UNIQUE INDEX EVM_BLOCK_INDEX_HASH ON EVM_BLOCK( HASH );
+
+-- End - EVM_BLOCK hash -
@@ -0,0 +1,8 @@
+-----------------------------------------
+-- Begin - DISCARDED_EVM_BLOCK reorg -
+-----------------------------------------
+
+alter table DISCARDED_EVM_BLOCK add column REORG_DEPTH bigint; -- number of blocks replaced by the reorg
+alter table DISCARDED_EVM_BLOCK add column NEW_HASH char(66); -- 64 is the length of a H256 in hex, plus 0x
+
+-- End - DISCARDED_EVM_BLOCK reorg -
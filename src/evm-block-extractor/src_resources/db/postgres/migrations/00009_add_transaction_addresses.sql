@@ -0,0 +1,24 @@
+-----------------------------------------
+-- Begin - EVM_TRANSACTION addresses -
+-----------------------------------------
+
+alter table EVM_TRANSACTION add column TRANSACTION_INDEX bigint;
+alter table EVM_TRANSACTION add column FROM_ADDRESS char(42); -- 40 is the length of a H160 in hex, plus 0x
+alter table EVM_TRANSACTION add column TO_ADDRESS char(42); -- null for contract creations
+alter table EVM_TRANSACTION add column CONTRACT_ADDRESS char(42); -- address of the contract created by the transaction
+
+update EVM_TRANSACTION set
+    TRANSACTION_INDEX = ('x' || lpad(substr(DATA->>'transactionIndex', 3), 16, '0'))::bit(64)::bigint,
+    FROM_ADDRESS = DATA->>'from',
+    TO_ADDRESS = DATA->>'to';
+
+-- The address of the created contracts is in their receipts
+update EVM_TRANSACTION t set CONTRACT_ADDRESS = r.DATA->>'contractAddress'
+from EVM_TRANSACTION_RECEIPT r
+where r.ID = t.ID and t.TO_ADDRESS is null;
+
+CREATE INDEX EVM_TRANSACTION_INDEX_FROM_ADDRESS ON EVM_TRANSACTION( FROM_ADDRESS, BLOCK_NUMBER, TRANSACTION_INDEX );
+CREATE INDEX EVM_TRANSACTION_INDEX_TO_ADDRESS ON EVM_TRANSACTION( TO_ADDRESS, BLOCK_NUMBER, TRANSACTION_INDEX );
+CREATE INDEX EVM_TRANSACTION_INDEX_CONTRACT_ADDRESS ON EVM_TRANSACTION( CONTRACT_ADDRESS, BLOCK_NUMBER, TRANSACTION_INDEX );
+
+-- End - EVM_TRANSACTION addresses -
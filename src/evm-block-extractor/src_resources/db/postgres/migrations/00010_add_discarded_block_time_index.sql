@@ -0,0 +1,7 @@
+-----------------------------------------
+-- Begin - DISCARDED_EVM_BLOCK time index -
+-----------------------------------------
+
+CREATE INDEX DISCARDED_EVM_BLOCK_INDEX_DISCARDED_AT ON DISCARDED_EVM_BLOCK( DISCARDED_AT, ID );
+
+-- End - DISCARDED_EVM_BLOCK time index -
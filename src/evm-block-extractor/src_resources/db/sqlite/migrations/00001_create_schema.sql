@@ -0,0 +1,111 @@
+-----------------------------
+-- Begin - EVM_BLOCK -
+-----------------------------
+
+create table EVM_BLOCK (
+    ID INTEGER primary key,
+    HASH char(66), -- 64 is the length of a H256 in hex, plus 0x
+    DATA TEXT
+);
+
+CREATE UNIQUE INDEX EVM_BLOCK_INDEX_HASH ON EVM_BLOCK( HASH );
+
+-- End - EVM_BLOCK -
+
+
+-----------------------------------------
+-- Begin - EVM_TRANSACTION -
+-----------------------------------------
+
+create table EVM_TRANSACTION (
+    ID char(66) primary key, -- 64 is the length of a H256 in hex, plus 0x
+    DATA TEXT,
+    BLOCK_NUMBER INTEGER
+);
+
+CREATE INDEX EVM_TRANSACTION_INDEX_BLOCK_NUMBER ON EVM_TRANSACTION( BLOCK_NUMBER );
+
+-- End - EVM_TRANSACTION -
+
+
+-----------------------------------------
+-- Begin - EVM_TRANSACTION_RECEIPT -
+-----------------------------------------
+
+create table EVM_TRANSACTION_RECEIPT (
+    ID char(66) primary key, -- 64 is the length of a H256 in hex, plus 0x
+    DATA TEXT,
+    BLOCK_NUMBER INTEGER
+);
+
+CREATE INDEX EVM_TRANSACTION_RECEIPT_INDEX_BLOCK_NUMBER ON EVM_TRANSACTION_RECEIPT( BLOCK_NUMBER );
+
+-- End - EVM_TRANSACTION_RECEIPT -
+
+
+-----------------------------------------
+-- Begin - EVM_LOG -
+-----------------------------------------
+
+create table EVM_LOG (
+    TRANSACTION_HASH char(66), -- 64 is the length of a H256 in hex, plus 0x
+    LOG_INDEX INTEGER,
+    TRANSACTION_INDEX INTEGER,
+    BLOCK_NUMBER INTEGER,
+    BLOCK_HASH char(66),
+    ADDRESS char(42), -- 40 is the length of a H160 in hex, plus 0x
+    TOPIC0 char(66),
+    TOPIC1 char(66),
+    TOPIC2 char(66),
+    TOPIC3 char(66),
+    DATA TEXT,
+    primary key (TRANSACTION_HASH, LOG_INDEX)
+);
+
+CREATE INDEX EVM_LOG_INDEX_BLOCK_NUMBER ON EVM_LOG( BLOCK_NUMBER );
+CREATE INDEX EVM_LOG_INDEX_BLOCK_HASH ON EVM_LOG( BLOCK_HASH );
+CREATE INDEX EVM_LOG_INDEX_ADDRESS ON EVM_LOG( ADDRESS );
+CREATE INDEX EVM_LOG_INDEX_TOPIC0 ON EVM_LOG( TOPIC0 );
+CREATE INDEX EVM_LOG_INDEX_TOPIC1 ON EVM_LOG( TOPIC1 );
+CREATE INDEX EVM_LOG_INDEX_TOPIC2 ON EVM_LOG( TOPIC2 );
+CREATE INDEX EVM_LOG_INDEX_TOPIC3 ON EVM_LOG( TOPIC3 );
+
+-- End - EVM_LOG -
+
+
+-----------------------------------------
+-- Begin - EVM_KEY_VALUE_DATA -
+-----------------------------------------
+
+create table EVM_KEY_VALUE_DATA (
+    KEY TEXT primary key,
+    DATA TEXT
+);
+
+-- End - EVM_KEY_VALUE_DATA -
+
+
+-----------------------------
+-- Begin - CERTIFIED_EVM_BLOCK -
+-----------------------------
+
+create table CERTIFIED_EVM_BLOCK (
+    ID INTEGER primary key,
+    CERTIFIED_RESPONSE TEXT
+);
+
+-- End - CERTIFIED_EVM_BLOCK -
+
+
+-----------------------------
+-- Begin - DISCARDED_EVM_BLOCK -
+-----------------------------
+
+create table DISCARDED_EVM_BLOCK (
+    ID char(66) primary key, -- 64 is the length of a H256 in hex, plus 0x
+    DATA TEXT,
+    REASON TEXT,
+    DISCARDED_AT TEXT
+);
+
+-- End - DISCARDED_EVM_BLOCK -
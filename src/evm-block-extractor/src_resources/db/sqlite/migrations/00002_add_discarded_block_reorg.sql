@@ -0,0 +1,8 @@
+-----------------------------------------
+-- Begin - DISCARDED_EVM_BLOCK reorg -
+-----------------------------------------
+
+alter table DISCARDED_EVM_BLOCK add column REORG_DEPTH INTEGER; -- number of blocks replaced by the reorg
+alter table DISCARDED_EVM_BLOCK add column NEW_HASH TEXT;
+
+-- End - DISCARDED_EVM_BLOCK reorg -
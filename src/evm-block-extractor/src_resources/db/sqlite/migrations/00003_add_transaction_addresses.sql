@@ -0,0 +1,34 @@
+-----------------------------------------
+-- Begin - EVM_TRANSACTION addresses -
+-----------------------------------------
+
+alter table EVM_TRANSACTION add column TRANSACTION_INDEX INTEGER;
+alter table EVM_TRANSACTION add column FROM_ADDRESS char(42); -- 40 is the length of a H160 in hex, plus 0x
+alter table EVM_TRANSACTION add column TO_ADDRESS char(42); -- null for contract creations
+alter table EVM_TRANSACTION add column CONTRACT_ADDRESS char(42); -- address of the contract created by the transaction
+
+update EVM_TRANSACTION set
+    FROM_ADDRESS = json_extract(DATA, '$.from'),
+    TO_ADDRESS = json_extract(DATA, '$.to');
+
+-- SQLite does not parse hex numbers: the transaction index, up to the 16 hex digits of a u64, is summed digit by digit
+update EVM_TRANSACTION set TRANSACTION_INDEX = (
+    with recursive DIGITS(P) as (select 0 union all select P + 1 from DIGITS where P < 15)
+    select sum(
+        (instr('0123456789abcdef', lower(substr(json_extract(DATA, '$.transactionIndex'), -1 - P, 1))) - 1) << (4 * P)
+    )
+    from DIGITS
+    where P < length(json_extract(DATA, '$.transactionIndex')) - 2
+);
+
+-- The address of the created contracts is in their receipts
+update EVM_TRANSACTION set CONTRACT_ADDRESS = (
+    select json_extract(r.DATA, '$.contractAddress') from EVM_TRANSACTION_RECEIPT r where r.ID = EVM_TRANSACTION.ID
+)
+where TO_ADDRESS is null;
+
+CREATE INDEX EVM_TRANSACTION_INDEX_FROM_ADDRESS ON EVM_TRANSACTION( FROM_ADDRESS, BLOCK_NUMBER, TRANSACTION_INDEX );
+CREATE INDEX EVM_TRANSACTION_INDEX_TO_ADDRESS ON EVM_TRANSACTION( TO_ADDRESS, BLOCK_NUMBER, TRANSACTION_INDEX );
+CREATE INDEX EVM_TRANSACTION_INDEX_CONTRACT_ADDRESS ON EVM_TRANSACTION( CONTRACT_ADDRESS, BLOCK_NUMBER, TRANSACTION_INDEX );
+
+-- End - EVM_TRANSACTION addresses -
@@ -1,23 +1,36 @@
 use std::sync::Arc;
 
 use evm_block_extractor::config::Database;
-use evm_block_extractor::database::postgres_db_client::PostgresDbClient;
+use evm_block_extractor::database::any_db_client::AnyDbClient;
+use tempfile::TempDir;
 use testcontainers::testcontainers::ContainerAsync;
 use testcontainers::testcontainers::runners::AsyncRunner;
 
 mod tests;
 
-async fn test_with_clients<T: AsyncFn(Arc<PostgresDbClient>) -> ()>(test: T) {
+async fn test_with_clients<T: AsyncFn(Arc<AnyDbClient>) -> ()>(test: T) {
     let _ = env_logger::Builder::new().parse_filters("info").try_init();
     println!("----------------------------------");
     println!("Running test with PostgresDbClient");
     println!("----------------------------------");
     let (postgres_client, _node) = new_postgres_db_client().await;
     test(postgres_client).await;
+
+    println!("--------------------------------");
+    println!("Running test with SqliteDbClient");
+    println!("--------------------------------");
+    let (sqlite_client, _dir) = new_sqlite_db_client().await;
+    test(sqlite_client).await;
+
+    println!("----------------------------------");
+    println!("Running test with InMemoryDbClient");
+    println!("----------------------------------");
+    let in_memory_client = Database::InMemory.build_client().await.unwrap();
+    test(in_memory_client).await;
 }
 
 async fn new_postgres_db_client() -> (
-    Arc<PostgresDbClient>,
+    Arc<AnyDbClient>,
     ContainerAsync<testcontainers::postgres::Postgres>,
 ) {
     let node = testcontainers::postgres::Postgres::default()
@@ -27,12 +40,27 @@ async fn new_postgres_db_client() -> (
 
     let db = Database::Postgres {
         username: "postgres".to_string(),
-        password: "postgres".to_string(),
+        password: Some("postgres".to_string()),
+        password_file: None,
         database_name: "postgres".to_string(),
         database_url: "127.0.0.1".to_owned(),
         database_port: node.get_host_port_ipv4(5432).await.unwrap(),
         require_ssl: false,
+        pool: Default::default(),
+        action: None,
     };
 
     (db.build_client().await.unwrap(), node)
 }
+
+async fn new_sqlite_db_client() -> (Arc<AnyDbClient>, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+
+    let db = Database::Sqlite {
+        database_path: dir.path().join("evm_block_extractor.db"),
+        pool: Default::default(),
+        action: None,
+    };
+
+    (db.build_client().await.unwrap(), dir)
+}
@@ -0,0 +1,428 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use alloy::rlp::Decodable;
+use did::block::calculate_block_hash;
+use did::{Block, H160, H256, Transaction, TransactionReceipt};
+use evm_block_extractor::config::Database;
+use evm_block_extractor::database::any_db_client::AnyDbClient;
+use evm_block_extractor::database::{AccountBalance, DatabaseClient};
+use evm_block_extractor::task::archive::{
+    ArchiveChunk, ArchiveFormat, ArchiveManifest, ArchivedBlock, MANIFEST_FILE_NAME, start_export,
+    start_import,
+};
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use crate::test_with_clients;
+use crate::tests::block_extractor_it::{
+    generate_correct_block_sequence, generate_forked_block_sequence,
+};
+
+/// Reads an archive file, checking its digest, and returns its decompressed content
+fn read_chunk(dir: &Path, chunk: &ArchiveChunk) -> Vec<u8> {
+    let compressed = std::fs::read(dir.join(&chunk.file_name)).unwrap();
+    assert_eq!(
+        alloy::hex::encode(Sha256::digest(&compressed)),
+        chunk.sha256
+    );
+
+    let mut content = vec![];
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut content)
+        .unwrap();
+    content
+}
+
+#[tokio::test]
+async fn test_export_jsonl_archive() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..10, Default::default(), 2);
+        let receipts: Vec<TransactionReceipt> = blocks
+            .iter()
+            .flat_map(|block| {
+                block
+                    .transactions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, tx)| TransactionReceipt {
+                        transaction_hash: tx.hash.clone(),
+                        transaction_index: (index as u64).into(),
+                        block_hash: block.hash.clone(),
+                        block_number: block.number,
+                        ..Default::default()
+                    })
+            })
+            .collect();
+        let txs: Vec<Transaction> = blocks
+            .iter()
+            .flat_map(|b| &b.transactions)
+            .cloned()
+            .collect();
+        let stored_blocks: Vec<Block<H256>> = blocks.iter().cloned().map(Into::into).collect();
+        db_client
+            .insert_block_data(&stored_blocks, &txs, &receipts)
+            .await
+            .unwrap();
+
+        let genesis_balances = vec![AccountBalance {
+            address: H160::from_slice(&[1; 20]),
+            balance: 42_u64.into(),
+        }];
+        db_client
+            .insert_genesis_balances(&genesis_balances)
+            .await
+            .unwrap();
+        db_client.insert_chain_id(355113).await.unwrap();
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let manifest = start_export(
+            db_client.clone(),
+            Some(2),
+            None,
+            output_dir.path(),
+            ArchiveFormat::Jsonl,
+            3,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(manifest.format, ArchiveFormat::Jsonl);
+        assert_eq!(manifest.chain_id, Some(355113));
+        assert_eq!(manifest.from_block, 2);
+        assert_eq!(manifest.to_block, 9);
+        assert_eq!(manifest.last_block_hash, blocks[9].hash);
+        assert_eq!(manifest.genesis_balances, Some(genesis_balances));
+        let chunk_ranges: Vec<_> = manifest
+            .chunks
+            .iter()
+            .map(|chunk| (chunk.from_block, chunk.to_block))
+            .collect();
+        assert_eq!(chunk_ranges, vec![(2, 4), (5, 7), (8, 9)]);
+
+        let manifest_file = std::fs::read(output_dir.path().join(MANIFEST_FILE_NAME)).unwrap();
+        let manifest_from_file: ArchiveManifest = serde_json::from_slice(&manifest_file).unwrap();
+        assert_eq!(manifest_from_file, manifest);
+
+        let mut exported_blocks = vec![];
+        for chunk in &manifest.chunks {
+            let content = read_chunk(output_dir.path(), chunk);
+            for line in BufReader::new(content.as_slice()).lines() {
+                let archived_block: ArchivedBlock = serde_json::from_str(&line.unwrap()).unwrap();
+                exported_blocks.push(archived_block);
+            }
+        }
+
+        assert_eq!(exported_blocks.len(), 8);
+        for (archived_block, block) in exported_blocks.iter().zip(&blocks[2..]) {
+            assert_eq!(&archived_block.block, block);
+            let block_receipts: Vec<_> = receipts
+                .iter()
+                .filter(|receipt| receipt.block_number == block.number)
+                .cloned()
+                .collect();
+            assert_eq!(archived_block.receipts, block_receipts);
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_export_rlp_archive() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..5, Default::default(), 0);
+        let stored_blocks: Vec<Block<H256>> = blocks.iter().cloned().map(Into::into).collect();
+        db_client
+            .insert_block_data(&stored_blocks, &[], &[])
+            .await
+            .unwrap();
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let manifest = start_export(
+            db_client.clone(),
+            None,
+            None,
+            output_dir.path(),
+            ArchiveFormat::Rlp,
+            10,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(manifest.from_block, 0);
+        assert_eq!(manifest.to_block, 4);
+        assert_eq!(manifest.chunks.len(), 1);
+
+        let content = read_chunk(output_dir.path(), &manifest.chunks[0]);
+        let mut buf = content.as_slice();
+        for block in &blocks {
+            let decoded_block = Block::<Transaction>::decode(&mut buf).unwrap();
+            assert_eq!(decoded_block.number, block.number);
+            assert_eq!(decoded_block.parent_hash, block.parent_hash);
+            assert_eq!(
+                calculate_block_hash(&decoded_block),
+                calculate_block_hash(block)
+            );
+        }
+        assert!(buf.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_export_fails_on_missing_blocks() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks: Vec<Block<H256>> = generate_correct_block_sequence(0..5, Default::default(), 0)
+            .into_iter()
+            .filter(|block| block.number.as_u64() != 2)
+            .map(Into::into)
+            .collect();
+        db_client
+            .insert_block_data(&blocks, &[], &[])
+            .await
+            .unwrap();
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let result = start_export(
+            db_client.clone(),
+            None,
+            None,
+            output_dir.path(),
+            ArchiveFormat::Jsonl,
+            10,
+        )
+        .await;
+        assert!(result.is_err());
+    })
+    .await;
+}
+
+/// Stores the given blocks in a new in memory database and exports them
+async fn export_blocks(
+    blocks: &[Block<Transaction>],
+    format: ArchiveFormat,
+    blocks_per_file: u64,
+) -> (ArchiveManifest, TempDir) {
+    let source_client = Database::InMemory.build_client().await.unwrap();
+    source_client.init(None, true).await.unwrap();
+
+    let txs: Vec<Transaction> = blocks
+        .iter()
+        .flat_map(|b| &b.transactions)
+        .cloned()
+        .collect();
+    let receipts: Vec<TransactionReceipt> = txs
+        .iter()
+        .map(|tx| TransactionReceipt {
+            transaction_hash: tx.hash.clone(),
+            transaction_index: tx.transaction_index.unwrap_or_default(),
+            block_hash: tx.block_hash.clone().unwrap_or_default(),
+            block_number: tx.block_number.unwrap_or_default(),
+            ..Default::default()
+        })
+        .collect();
+    let stored_blocks: Vec<Block<H256>> = blocks.iter().cloned().map(Into::into).collect();
+    source_client
+        .insert_block_data(&stored_blocks, &txs, &receipts)
+        .await
+        .unwrap();
+    source_client.insert_chain_id(355113).await.unwrap();
+
+    let archive_dir = tempfile::tempdir().unwrap();
+    let manifest = start_export(
+        source_client,
+        None,
+        None,
+        archive_dir.path(),
+        format,
+        blocks_per_file,
+    )
+    .await
+    .unwrap();
+
+    (manifest, archive_dir)
+}
+
+/// Checks that the blocks in the database match the given ones
+async fn assert_blocks_stored(db_client: &AnyDbClient, blocks: &[Block<Transaction>]) {
+    for block in blocks {
+        let stored_block = db_client
+            .get_full_block_by_number(block.number.as_u64())
+            .await
+            .unwrap();
+        assert_eq!(&stored_block, block);
+    }
+}
+
+#[tokio::test]
+async fn test_import_jsonl_archive() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..10, Default::default(), 2);
+        let (manifest, archive_dir) = export_blocks(&blocks, ArchiveFormat::Jsonl, 3).await;
+        assert_eq!(manifest.chunks.len(), 4);
+
+        let imported_blocks = start_import(db_client.clone(), archive_dir.path())
+            .await
+            .unwrap();
+
+        assert_eq!(imported_blocks, 10);
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+        assert_eq!(db_client.get_chain_id().await.unwrap(), Some(355113));
+        assert_blocks_stored(&db_client, &blocks).await;
+
+        let receipts = db_client.get_block_receipts(4).await.unwrap();
+        let receipt_hashes: Vec<_> = receipts
+            .iter()
+            .map(|receipt| receipt.transaction_hash.clone())
+            .collect();
+        assert_eq!(receipt_hashes.len(), 2);
+        for tx in &blocks[4].transactions {
+            assert!(receipt_hashes.contains(&tx.hash));
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_import_resumes_from_latest_block() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..10, Default::default(), 1);
+        let (_manifest, archive_dir) = export_blocks(&blocks, ArchiveFormat::Jsonl, 4).await;
+
+        let stored_blocks: Vec<Block<H256>> = blocks[..5].iter().cloned().map(Into::into).collect();
+        let txs: Vec<Transaction> = blocks[..5]
+            .iter()
+            .flat_map(|b| &b.transactions)
+            .cloned()
+            .collect();
+        db_client
+            .insert_block_data(&stored_blocks, &txs, &[])
+            .await
+            .unwrap();
+
+        let imported_blocks = start_import(db_client.clone(), archive_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(imported_blocks, 5);
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+        assert_blocks_stored(&db_client, &blocks).await;
+
+        // Importing again is a no-op
+        let imported_blocks = start_import(db_client.clone(), archive_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(imported_blocks, 0);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_import_rlp_archive() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        // The RLP archive does not hold the block hashes: they are recomputed from the headers
+        let mut blocks = generate_correct_block_sequence(0..6, Default::default(), 0);
+        for i in 0..blocks.len() {
+            if i > 0 {
+                blocks[i].parent_hash = blocks[i - 1].hash.clone();
+            }
+            blocks[i].hash = calculate_block_hash(&blocks[i]);
+        }
+        let (_manifest, archive_dir) = export_blocks(&blocks, ArchiveFormat::Rlp, 4).await;
+
+        let imported_blocks = start_import(db_client.clone(), archive_dir.path())
+            .await
+            .unwrap();
+
+        assert_eq!(imported_blocks, 6);
+        for block in &blocks {
+            let stored_block = db_client
+                .get_block_by_number(block.number.as_u64())
+                .await
+                .unwrap();
+            assert_eq!(stored_block.hash, block.hash);
+            assert_eq!(stored_block.parent_hash, block.parent_hash);
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_import_fails_on_corrupted_archive_file() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..9, Default::default(), 0);
+        let (manifest, archive_dir) = export_blocks(&blocks, ArchiveFormat::Jsonl, 3).await;
+
+        let corrupted_file = archive_dir.path().join(&manifest.chunks[1].file_name);
+        let mut content = std::fs::read(&corrupted_file).unwrap();
+        let last = content.len() - 1;
+        content[last] ^= 0xff;
+        std::fs::write(&corrupted_file, content).unwrap();
+
+        let result = start_import(db_client.clone(), archive_dir.path()).await;
+        assert!(result.is_err());
+
+        // The blocks before the corrupted file are imported
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(2));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_import_fails_on_manifest_hash_mismatch_before_importing() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..6, Default::default(), 0);
+        let (mut manifest, archive_dir) = export_blocks(&blocks, ArchiveFormat::Jsonl, 3).await;
+
+        manifest.last_block_hash = blocks[4].hash.clone();
+        let manifest_file =
+            std::fs::File::create(archive_dir.path().join(MANIFEST_FILE_NAME)).unwrap();
+        serde_json::to_writer(manifest_file, &manifest).unwrap();
+
+        let result = start_import(db_client.clone(), archive_dir.path()).await;
+        assert!(result.is_err());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), None);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_import_fails_if_archive_does_not_extend_stored_chain() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..6, Default::default(), 0);
+        let (_manifest, archive_dir) = export_blocks(&blocks, ArchiveFormat::Jsonl, 3).await;
+
+        let forked_blocks: Vec<Block<H256>> =
+            generate_forked_block_sequence(0..3, Default::default(), 0)
+                .into_iter()
+                .map(Into::into)
+                .collect();
+        db_client
+            .insert_block_data(&forked_blocks, &[], &[])
+            .await
+            .unwrap();
+
+        let result = start_import(db_client.clone(), archive_dir.path()).await;
+        assert!(result.is_err());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(2));
+    })
+    .await;
+}
@@ -3,24 +3,37 @@ use std::future::Future;
 use std::ops::Range;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 
+use candid::Principal;
+use did::block::calculate_block_hash;
+use did::certified::test_utils::LocalRootKey;
 use did::evm_state::EvmGlobalState;
 use did::rpc::error::Error;
 use did::rpc::id::Id;
 use did::rpc::params::Params;
 use did::rpc::request::{Request, RpcRequest};
 use did::rpc::response::{Failure, Response, RpcResponse, Success};
+use did::transaction::TransactionReceiptLog;
 use did::{
-    BlockConfirmationData, BlockConfirmationResult, BlockNumber, BlockchainBlockInfo, H160, keccak,
+    BlockConfirmationData, BlockConfirmationResult, BlockNumber, BlockchainBlockInfo, H160, H256,
+    TransactionReceipt, keccak,
 };
 use ethereum_json_rpc_client::reqwest::ReqwestClient;
 use ethereum_json_rpc_client::{CertifiedResult, Client, EthJsonRpcClient, JsonRpcResult};
+use evm_block_extractor::certificate::CertificateVerifier;
+use evm_block_extractor::config::{Database, ExtractorArgs};
 use evm_block_extractor::database::{AccountBalance, DatabaseClient};
 use evm_block_extractor::server;
-use evm_block_extractor::task::block_extractor::{BlockExtractCollectOutcome, BlockExtractor};
+use evm_block_extractor::subscription::{ChainEvent, ChainEvents};
+use evm_block_extractor::task::block_extractor::{
+    BlockExtractCollectOutcome, BlockExtractor, follow_chain,
+};
 use serde::de::DeserializeOwned;
 
 use crate::test_with_clients;
+use crate::tests::failover_it::TestClient;
 
 #[tokio::test]
 async fn test_extractor_collect_blocks() {
@@ -35,10 +48,12 @@ async fn test_extractor_collect_blocks() {
         let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
         let request_time_out_secs = 10;
         let rpc_batch_size = 10;
+        let max_parallel_batches = 4;
         let mut extractor = BlockExtractor::new(
             mock_evm_client.clone(),
             request_time_out_secs,
             rpc_batch_size,
+            max_parallel_batches,
             db_client.clone(),
         );
 
@@ -127,17 +142,77 @@ async fn test_extractor_collect_blocks() {
                     assert_eq!(tx.block_hash, tx.block_hash);
                 }
             }
+
+            // Check receipts
+            {
+                let receipts = db_client.get_block_receipts(block_num).await.unwrap();
+                assert_eq!(receipts.len(), source_block.transactions.len());
+                for (receipt, tx) in receipts.iter().zip(&source_block.transactions) {
+                    assert_eq!(receipt.transaction_hash, tx.hash);
+                    assert_eq!(receipt.block_hash, source_block.hash);
+                    assert_eq!(receipt.block_number.as_u64(), block_num);
+                }
+            }
         }
     })
     .await;
 }
 
+#[tokio::test]
+async fn test_extractor_verifies_certified_blocks() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let root_key = LocalRootKey::from_seed(b"test");
+        let canister_id = Principal::from_slice(&[0, 0, 0, 0, 1, 48, 0, 4, 1, 1]);
+        let verifier = CertificateVerifier::new(root_key.public_key(), canister_id);
+        let blocks = generate_correct_block_sequence(0..10, Default::default(), 1);
+
+        // Blocks certified with an invalid certificate are not stored
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks[..5].to_vec());
+        let mut extractor = BlockExtractor::new(
+            Arc::new(EthJsonRpcClient::new(mock_client)),
+            10,
+            10,
+            1,
+            db_client.clone(),
+        )
+        .with_certificate_verifier(Some(verifier.clone()));
+        extractor.collect_all(0, 4).await.unwrap();
+
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(4));
+        assert!(db_client.get_last_certified_block_data().await.is_err());
+
+        // Blocks certified with a valid certificate are stored
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks.clone())
+            .with_block_certifier(root_key, canister_id);
+        let mut extractor = BlockExtractor::new(
+            Arc::new(EthJsonRpcClient::new(mock_client)),
+            10,
+            10,
+            1,
+            db_client.clone(),
+        )
+        .with_certificate_verifier(Some(verifier.clone()));
+        extractor.collect_all(5, 9).await.unwrap();
+
+        let certified_block = db_client.get_last_certified_block_data().await.unwrap();
+        assert_eq!(certified_block.data.hash, blocks[9].hash);
+        assert!(verifier.verify(&certified_block).is_ok());
+    })
+    .await;
+}
+
 const CHAIN_ID: u64 = 42;
+/// Result of every `eth_call` to the mock client
+pub const ETH_CALL_RESULT: &str = "0x2a";
 
 #[derive(Clone)]
 pub struct MockClient {
     evm_global_state: EvmGlobalState,
     blocks: BTreeMap<u64, did::Block<did::Transaction>>,
+    /// Root key and canister id certifying the blocks; the certificates are invalid if missing
+    block_certifier: Option<(LocalRootKey, Principal)>,
 }
 
 impl MockClient {
@@ -145,6 +220,7 @@ impl MockClient {
         Self {
             evm_global_state,
             blocks: BTreeMap::new(),
+            block_certifier: None,
         }
     }
 
@@ -156,9 +232,16 @@ impl MockClient {
         Self {
             evm_global_state,
             blocks: blocks.into_iter().map(|b| (b.number.as_u64(), b)).collect(),
+            block_certifier: None,
         }
     }
 
+    /// Certify the last block with a valid certificate signed by `root_key`
+    pub fn with_block_certifier(mut self, root_key: LocalRootKey, canister_id: Principal) -> Self {
+        self.block_certifier = Some((root_key, canister_id));
+        self
+    }
+
     fn process_single_call(&self, call: Request) -> Response {
         match call.method.as_str() {
             "ic_getEvmGlobalState" => Response::Success(Success {
@@ -168,18 +251,23 @@ impl MockClient {
             }),
             "eth_getBlockByNumber" => {
                 let number: BlockNumber = Self::get_from_vec(&call.params, 0);
+                let full_transactions: bool = Self::get_from_vec(&call.params, 1);
                 let block = match number {
                     BlockNumber::Latest | BlockNumber::Finalized | BlockNumber::Safe => {
                         self.blocks.last_key_value().map(|(_, v)| v.clone())
                     }
-                    BlockNumber::Earliest => self.blocks.last_key_value().map(|(_, v)| v.clone()),
+                    BlockNumber::Earliest => self.blocks.first_key_value().map(|(_, v)| v.clone()),
                     BlockNumber::Pending => unimplemented!(),
                     BlockNumber::Number(n) => self.blocks.get(&n.as_u64()).cloned(),
                 };
                 match block {
                     Some(block) => Response::Success(Success {
                         jsonrpc: None,
-                        result: serde_json::to_value(block).unwrap(),
+                        result: if full_transactions {
+                            serde_json::to_value(block).unwrap()
+                        } else {
+                            serde_json::to_value(did::Block::<H256>::from(block)).unwrap()
+                        },
                         id: call.id,
                     }),
                     None => Response::Failure(Failure {
@@ -189,6 +277,40 @@ impl MockClient {
                     }),
                 }
             }
+            "eth_getTransactionReceipt" => {
+                let hash: H256 = Self::get_from_vec(&call.params, 0);
+                let receipt = self.blocks.values().find_map(|block| {
+                    block
+                        .transactions
+                        .iter()
+                        .position(|tx| tx.hash == hash)
+                        .map(|index| {
+                            let tx = &block.transactions[index];
+                            TransactionReceipt {
+                                transaction_hash: tx.hash.clone(),
+                                transaction_index: (index as u64).into(),
+                                block_hash: block.hash.clone(),
+                                block_number: block.number,
+                                from: tx.from.clone(),
+                                to: tx.to.clone(),
+                                status: Some(1u64.into()),
+                                ..Default::default()
+                            }
+                        })
+                });
+                match receipt {
+                    Some(receipt) => Response::Success(Success {
+                        jsonrpc: None,
+                        result: serde_json::to_value(receipt).unwrap(),
+                        id: call.id,
+                    }),
+                    None => Response::Failure(Failure {
+                        jsonrpc: None,
+                        error: Error::invalid_params("transaction not found"),
+                        id: call.id,
+                    }),
+                }
+            }
             "ic_getLastCertifiedBlock" => {
                 let data = self
                     .blocks
@@ -196,22 +318,48 @@ impl MockClient {
                     .map(|(_, v)| v)
                     .cloned()
                     .unwrap_or_else(|| did::Block::default().into_full_block(vec![]).unwrap());
-                Response::Success(Success {
-                    jsonrpc: None,
-                    result: serde_json::to_value(&CertifiedResult {
+                let result = match &self.block_certifier {
+                    Some((root_key, canister_id)) => {
+                        serde_json::to_value(root_key.certify_block(canister_id, data.into()))
+                    }
+                    None => serde_json::to_value(&CertifiedResult {
                         data,
                         witness: vec![4, 5, 6u8],
                         certificate: vec![7, 8, 9],
-                    })
-                    .unwrap(),
+                    }),
+                };
+                Response::Success(Success {
+                    jsonrpc: None,
+                    result: result.unwrap(),
                     id: call.id,
                 })
             }
+            "eth_blockNumber" => Response::Success(Success {
+                jsonrpc: None,
+                result: serde_json::to_value(did::U64::from(
+                    self.blocks
+                        .last_key_value()
+                        .map(|(k, _)| *k)
+                        .unwrap_or_default(),
+                ))
+                .unwrap(),
+                id: call.id,
+            }),
             "eth_chainId" => Response::Success(Success {
                 jsonrpc: None,
                 result: serde_json::to_value(CHAIN_ID.to_string()).unwrap(),
                 id: call.id,
             }),
+            "eth_call" => Response::Success(Success {
+                jsonrpc: None,
+                result: serde_json::to_value(ETH_CALL_RESULT).unwrap(),
+                id: call.id,
+            }),
+            "eth_estimateGas" => Response::Failure(Failure {
+                jsonrpc: None,
+                error: Error::invalid_params("gas estimation failed"),
+                id: call.id,
+            }),
             "ic_sendConfirmBlock" => Response::Success(Success {
                 jsonrpc: None,
                 result: serde_json::to_value(BlockConfirmationResult::Confirmed).unwrap(),
@@ -334,6 +482,255 @@ async fn test_mock_client_returns_evm_state() {
     );
 }
 
+#[tokio::test]
+async fn test_extractor_collects_only_new_blocks() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..31, Default::default(), 2);
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks);
+        let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
+        let mut extractor = BlockExtractor::new(mock_evm_client, 10, 10, 1, db_client.clone());
+
+        assert!(extractor.collect_new_blocks().await.unwrap());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(30));
+
+        assert!(!extractor.collect_new_blocks().await.unwrap());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(30));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_extractor_follows_the_chain() {
+    test_with_clients(async move |db_client| {
+        let blocks = generate_correct_block_sequence(0..31, Default::default(), 2);
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks);
+        let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
+
+        let config = follow_config();
+        let follow_handle = tokio::spawn(follow_chain(
+            config,
+            db_client.clone(),
+            mock_evm_client,
+            ChainEvents::default(),
+        ));
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while db_client.get_latest_block_number().await.unwrap() != Some(30) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the extractor should reach the head of the chain");
+
+        follow_handle.abort();
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_extractor_follows_the_chain_after_an_evmc_outage_at_startup() {
+    test_with_clients(async move |db_client| {
+        let blocks = generate_correct_block_sequence(0..11, Default::default(), 2);
+        let client = TestClient {
+            available: Arc::new(AtomicBool::new(false)),
+            mock: MockClient::with_blocks(EvmGlobalState::Enabled, blocks),
+        };
+        let evm_client = Arc::new(EthJsonRpcClient::new(client.clone()));
+
+        let follow_handle = tokio::spawn(follow_chain(
+            follow_config(),
+            db_client.clone(),
+            evm_client,
+            ChainEvents::default(),
+        ));
+
+        // The extractor keeps retrying while the EVMC is down
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!follow_handle.is_finished());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), None);
+
+        client.set_available(true);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while db_client.get_latest_block_number().await.unwrap() != Some(10) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the extractor should reach the head of the chain");
+
+        follow_handle.abort();
+    })
+    .await;
+}
+
+/// Configuration of an extractor following the chain with short poll intervals
+fn follow_config() -> ExtractorArgs {
+    ExtractorArgs {
+        config: None,
+        server_address: Default::default(),
+        max_sync_lag_blocks: 100,
+        metrics_address: None,
+        remote_rpc_urls: vec![],
+        rpc_failover_cooldown_secs: 30,
+        evm_canister_id: None,
+        ic_root_key: None,
+        verify_block_integrity: false,
+        max_reorg_depth: 1000,
+        proxy_methods: vec![],
+        request_time_out_secs: 10,
+        rpc_batch_size: 10,
+        max_parallel_batches: 2,
+        log_filter: Default::default(),
+        command: Database::InMemory,
+        reset_db_on_state_change: false,
+        block_extractor_job_interval_seconds: 120,
+        follow: true,
+        follow_min_poll_interval_millis: 10,
+        follow_max_poll_interval_millis: 100,
+        follow_max_error_backoff_secs: 1,
+        retention_blocks: None,
+        retention_days: None,
+        retention_headers_only: false,
+        retention_batch_size: 1000,
+        retention_job_interval_seconds: 3600,
+    }
+}
+
+#[tokio::test]
+async fn test_extractor_backfills_missing_blocks() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..31, Default::default(), 2);
+
+        // Store the blocks with a few gaps, and the transactions of block 25 without the block
+        let missing_blocks = [5, 6, 7, 20, 25];
+        let stored_blocks: Vec<did::Block<H256>> = blocks
+            .iter()
+            .filter(|b| !missing_blocks.contains(&b.number.as_u64()))
+            .cloned()
+            .map(Into::into)
+            .collect();
+        let stored_txs: Vec<_> = blocks
+            .iter()
+            .filter(|b| !missing_blocks[..4].contains(&b.number.as_u64()))
+            .flat_map(|b| &b.transactions)
+            .cloned()
+            .collect();
+        db_client
+            .insert_block_data(&stored_blocks, &stored_txs, &[])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            db_client
+                .get_orphan_transaction_block_numbers(0, 30)
+                .await
+                .unwrap(),
+            vec![25]
+        );
+
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks.clone());
+        let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
+        let extractor = BlockExtractor::new(mock_evm_client, 10, 10, 1, db_client.clone());
+
+        let repaired_ranges = extractor.backfill(0, 30).await.unwrap();
+        assert_eq!(repaired_ranges, vec![5..=7, 20..=20, 25..=25]);
+
+        assert!(
+            db_client
+                .get_missing_block_ranges(0, 30)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            db_client
+                .get_orphan_transaction_block_numbers(0, 30)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        for block in &blocks {
+            let stored_block = db_client
+                .get_full_block_by_number(block.number.as_u64())
+                .await
+                .unwrap();
+            assert_eq!(&stored_block, block);
+        }
+
+        // Nothing left to repair
+        assert!(extractor.backfill(0, 30).await.unwrap().is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_extractor_backfill_leaves_orphans_outside_the_range() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        let blocks = generate_correct_block_sequence(0..31, Default::default(), 2);
+
+        // Store the transactions of blocks 5 and 25 without the blocks
+        let missing_blocks = [5, 25];
+        let stored_blocks: Vec<did::Block<H256>> = blocks
+            .iter()
+            .filter(|b| !missing_blocks.contains(&b.number.as_u64()))
+            .cloned()
+            .map(Into::into)
+            .collect();
+        let stored_txs: Vec<_> = blocks
+            .iter()
+            .flat_map(|b| &b.transactions)
+            .cloned()
+            .collect();
+        db_client
+            .insert_block_data(&stored_blocks, &stored_txs, &[])
+            .await
+            .unwrap();
+
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks.clone());
+        let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
+        let extractor = BlockExtractor::new(mock_evm_client, 10, 10, 1, db_client.clone());
+
+        let repaired_ranges = extractor.backfill(0, 10).await.unwrap();
+        assert_eq!(repaired_ranges, vec![5..=5]);
+
+        // The orphan transactions of block 25 are outside the backfilled range
+        assert_eq!(
+            db_client
+                .get_orphan_transaction_block_numbers(0, 30)
+                .await
+                .unwrap(),
+            vec![25]
+        );
+        for tx in &blocks[25].transactions {
+            assert!(
+                db_client
+                    .find_transaction(tx.hash.clone())
+                    .await
+                    .unwrap()
+                    .is_some()
+            );
+        }
+
+        let repaired_ranges = extractor.backfill(20, 30).await.unwrap();
+        assert_eq!(repaired_ranges, vec![25..=25]);
+        assert!(
+            db_client
+                .get_orphan_transaction_block_numbers(0, 30)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn test_extractor_does_not_collect_blocks_if_evm_is_disabled() {
     test_with_clients(async move |db_client| {
@@ -343,7 +740,7 @@ async fn test_extractor_does_not_collect_blocks_if_evm_is_disabled() {
 
         let evm_client = Arc::new(EthJsonRpcClient::new(client));
 
-        let mut extractor = BlockExtractor::new(evm_client.clone(), 10, 10, db_client.clone());
+        let mut extractor = BlockExtractor::new(evm_client.clone(), 10, 10, 1, db_client.clone());
 
         let result = extractor.collect_all(100, 1000).await.unwrap();
 
@@ -366,7 +763,7 @@ async fn test_extractor_does_not_collect_blocks_if_evm_is_staging() {
 
         let evm_client = Arc::new(EthJsonRpcClient::new(client));
 
-        let mut extractor = BlockExtractor::new(evm_client.clone(), 10, 10, db_client.clone());
+        let mut extractor = BlockExtractor::new(evm_client.clone(), 10, 10, 1, db_client.clone());
 
         let result = extractor.collect_all(100, 1000).await.unwrap();
 
@@ -393,10 +790,12 @@ async fn test_extractor_validate_and_recover_blockchain() {
 
         let request_time_out_secs = 10;
         let rpc_batch_size = 10;
+        let max_parallel_batches = 1;
         let mut extractor = BlockExtractor::new(
             mock_evm_client,
             request_time_out_secs,
             rpc_batch_size,
+            max_parallel_batches,
             db_client.clone(),
         );
 
@@ -415,12 +814,12 @@ async fn test_extractor_validate_and_recover_blockchain() {
             _ => panic!("Expected BlocksExtracted"),
         }
 
-        // Add several broken blocks to DB
+        // Add several blocks of a fork to DB
         let block_before_broken = end_block;
         let first_broken_block = end_block + 1;
-        let last_broken_block = end_block + 5;
-        let broken_blocks = generate_correct_block_sequence(
-            first_broken_block..last_broken_block,
+        let last_broken_block = end_block + 4;
+        let broken_blocks = generate_forked_block_sequence(
+            first_broken_block..last_broken_block + 1,
             init_blocks_last_hash.clone(),
             10,
         );
@@ -429,39 +828,46 @@ async fn test_extractor_validate_and_recover_blockchain() {
             .flat_map(|b| &b.transactions)
             .cloned()
             .collect();
-        let broken_blocks: Vec<_> = broken_blocks.into_iter().map(Into::into).collect();
+        let broken_blocks: Vec<did::Block<H256>> =
+            broken_blocks.into_iter().map(Into::into).collect();
         db_client
-            .insert_block_data(&broken_blocks, &txs)
+            .insert_block_data(&broken_blocks, &txs, &[])
             .await
             .unwrap();
 
-        let start_block = last_broken_block + 1;
-        let end_block = last_broken_block + 10;
-        let blocks = generate_correct_block_sequence(
-            start_block..end_block + 1,
-            keccak::keccak_hash(&[1, 2]),
-            10,
-        );
-        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks);
+        // The EVMC chain replaced the forked blocks
+        let blocks =
+            generate_correct_block_sequence(start_block..end_block + 16, Default::default(), 10);
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks.clone());
         let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
         let mut extractor = BlockExtractor::new(
             mock_evm_client,
             request_time_out_secs,
             rpc_batch_size,
+            max_parallel_batches,
             db_client.clone(),
         );
-        let collect_result = extractor.collect_all(start_block, end_block).await;
+        let collect_result = extractor
+            .collect_all(last_broken_block + 1, end_block + 15)
+            .await;
         assert!(collect_result.is_err());
 
-        // check the blockchain state recovered: invalid blocks discarded
+        // check the blockchain state recovered: only the forked blocks discarded
         for block in &broken_blocks {
-            let block_result = db_client.get_block_by_number(block.number.as_u64()).await;
+            let block_number = block.number.as_u64();
+            let block_result = db_client.get_block_by_number(block_number).await;
             assert!(block_result.is_err());
 
             let block_result = db_client
                 .get_discarded_block_by_hash(block.hash.clone())
                 .await
                 .unwrap();
+            assert_eq!(block_result.reason, "reorg");
+            assert_eq!(block_result.reorg_depth, Some(4));
+            assert_eq!(
+                block_result.new_hash,
+                Some(blocks[(block_number - start_block) as usize].hash.clone())
+            );
 
             for tx_hash in &block.transactions {
                 let tx_result = db_client.get_transaction(tx_hash.clone()).await;
@@ -481,16 +887,6 @@ async fn test_extractor_validate_and_recover_blockchain() {
 
         let start_block = block_before_broken + 1;
         let end_block = start_block + 10;
-        let blocks =
-            generate_correct_block_sequence(start_block..end_block + 1, init_blocks_last_hash, 10);
-        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks);
-        let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
-        let mut extractor = BlockExtractor::new(
-            mock_evm_client,
-            request_time_out_secs,
-            rpc_batch_size,
-            db_client.clone(),
-        );
         extractor.collect_all(start_block, end_block).await.unwrap();
         let last_block = db_client.get_latest_block_number().await.unwrap();
         assert_eq!(last_block, Some(end_block));
@@ -498,6 +894,198 @@ async fn test_extractor_validate_and_recover_blockchain() {
     .await;
 }
 
+#[tokio::test]
+async fn test_extractor_recovers_from_reorg_without_block_info() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        // Store the blocks without block info, the last 3 of them from a fork
+        let blocks = generate_correct_block_sequence(0..15, Default::default(), 2);
+        let forked_blocks = generate_forked_block_sequence(10..13, blocks[9].hash.clone(), 2);
+        let stored_blocks: Vec<did::Block<H256>> = blocks[..10]
+            .iter()
+            .chain(&forked_blocks)
+            .cloned()
+            .map(Into::into)
+            .collect();
+        let stored_txs: Vec<_> = blocks[..10]
+            .iter()
+            .chain(&forked_blocks)
+            .flat_map(|b| &b.transactions)
+            .cloned()
+            .collect();
+        db_client
+            .insert_block_data(&stored_blocks, &stored_txs, &[])
+            .await
+            .unwrap();
+        assert!(db_client.get_block_info().await.unwrap().is_none());
+
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks.clone());
+        let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
+        let mut extractor = BlockExtractor::new(mock_evm_client, 10, 10, 1, db_client.clone());
+
+        assert!(extractor.collect_new_blocks().await.is_err());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+
+        assert!(extractor.collect_new_blocks().await.unwrap());
+        for block in &blocks {
+            let stored_block = db_client
+                .get_full_block_by_number(block.number.as_u64())
+                .await
+                .unwrap();
+            assert_eq!(&stored_block, block);
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_extractor_does_not_discard_blocks_beyond_the_max_reorg_depth() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        // The last 3 stored blocks are from a fork
+        let blocks = generate_correct_block_sequence(0..15, Default::default(), 2);
+        let forked_blocks = generate_forked_block_sequence(10..13, blocks[9].hash.clone(), 2);
+        let stored_blocks: Vec<did::Block<H256>> = blocks[..10]
+            .iter()
+            .chain(&forked_blocks)
+            .cloned()
+            .map(Into::into)
+            .collect();
+        db_client
+            .insert_block_data(&stored_blocks, &[], &[])
+            .await
+            .unwrap();
+
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks.clone());
+        let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
+
+        let mut extractor =
+            BlockExtractor::new(mock_evm_client.clone(), 10, 10, 1, db_client.clone())
+                .with_max_reorg_depth(2);
+        assert!(extractor.collect_new_blocks().await.is_err());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(12));
+        for block in &forked_blocks {
+            let stored_block = db_client
+                .get_block_by_number(block.number.as_u64())
+                .await
+                .unwrap();
+            assert_eq!(stored_block.hash, block.hash);
+        }
+
+        let mut extractor = BlockExtractor::new(mock_evm_client, 10, 10, 1, db_client.clone())
+            .with_max_reorg_depth(3);
+        assert!(extractor.collect_new_blocks().await.is_err());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+
+        assert!(extractor.collect_new_blocks().await.unwrap());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(14));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_extractor_publishes_stored_blocks_and_removed_logs() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        // Store the blocks, the last 3 of them from a fork, with a log for each transaction
+        let blocks = generate_correct_block_sequence(0..15, Default::default(), 2);
+        let forked_blocks = generate_forked_block_sequence(10..13, blocks[9].hash.clone(), 2);
+        let stored_blocks: Vec<did::Block<H256>> = blocks[..10]
+            .iter()
+            .chain(&forked_blocks)
+            .cloned()
+            .map(Into::into)
+            .collect();
+        let stored_txs: Vec<_> = blocks[..10]
+            .iter()
+            .chain(&forked_blocks)
+            .flat_map(|b| &b.transactions)
+            .cloned()
+            .collect();
+        let stored_receipts: Vec<_> = stored_blocks
+            .iter()
+            .flat_map(|block| {
+                block
+                    .transactions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, tx_hash)| TransactionReceipt {
+                        transaction_hash: tx_hash.clone(),
+                        transaction_index: (index as u64).into(),
+                        block_hash: block.hash.clone(),
+                        block_number: block.number,
+                        logs: vec![TransactionReceiptLog {
+                            transaction_hash: tx_hash.clone(),
+                            transaction_index: (index as u64).into(),
+                            block_hash: block.hash.clone(),
+                            block_number: block.number,
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    })
+            })
+            .collect();
+        db_client
+            .insert_block_data(&stored_blocks, &stored_txs, &stored_receipts)
+            .await
+            .unwrap();
+
+        let chain_events = ChainEvents::default();
+        let mut events = chain_events.subscribe();
+
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks.clone());
+        let mock_evm_client = Arc::new(EthJsonRpcClient::new(mock_client));
+        let mut extractor = BlockExtractor::new(mock_evm_client, 10, 10, 1, db_client.clone())
+            .with_chain_events(chain_events);
+
+        // The logs of the discarded blocks are published as removed
+        assert!(extractor.collect_new_blocks().await.is_err());
+        let event = events.try_recv().unwrap();
+        let ChainEvent::LogsRemoved(removed_logs) = event.as_ref() else {
+            panic!("unexpected event {event:?}");
+        };
+        assert!(removed_logs.iter().all(|log| log.removed));
+        let mut removed_blocks = removed_logs
+            .iter()
+            .map(|log| log.block_hash.clone())
+            .collect::<Vec<_>>();
+        removed_blocks.dedup();
+        assert_eq!(
+            removed_blocks,
+            forked_blocks
+                .iter()
+                .map(|b| b.hash.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(removed_logs.len(), 6);
+
+        assert!(extractor.collect_new_blocks().await.unwrap());
+        let event = events.try_recv().unwrap();
+        let ChainEvent::BlocksStored {
+            blocks: stored_blocks,
+            logs,
+        } = event.as_ref()
+        else {
+            panic!("unexpected event {event:?}");
+        };
+        assert_eq!(
+            stored_blocks,
+            &blocks[10..]
+                .iter()
+                .cloned()
+                .map(did::Block::<H256>::from)
+                .collect::<Vec<_>>()
+        );
+        // The receipts of the EVMC have no logs
+        assert!(logs.is_empty());
+        assert!(events.try_recv().is_err());
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn test_extractor_skips_incorrect_sequence_of_new_blocks() {
     test_with_clients(async move |db_client| {
@@ -513,10 +1101,12 @@ async fn test_extractor_skips_incorrect_sequence_of_new_blocks() {
 
         let request_time_out_secs = 10;
         let rpc_batch_size = 10;
+        let max_parallel_batches = 1;
         let mut extractor = BlockExtractor::new(
             mock_evm_client,
             request_time_out_secs,
             rpc_batch_size,
+            max_parallel_batches,
             db_client.clone(),
         );
 
@@ -554,6 +1144,7 @@ async fn test_extractor_skips_incorrect_sequence_of_new_blocks() {
             mock_evm_client,
             request_time_out_secs,
             rpc_batch_size,
+            max_parallel_batches,
             db_client.clone(),
         );
 
@@ -594,6 +1185,7 @@ async fn test_extractor_skips_incorrect_sequence_of_new_blocks() {
             mock_evm_client,
             request_time_out_secs,
             rpc_batch_size,
+            max_parallel_batches,
             db_client.clone(),
         );
         extractor.collect_all(start_block, end_block).await.unwrap();
@@ -603,6 +1195,68 @@ async fn test_extractor_skips_incorrect_sequence_of_new_blocks() {
     .await;
 }
 
+#[tokio::test]
+async fn test_extractor_verifies_block_integrity() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, true).await.unwrap();
+
+        // Blocks hashing to their hashes, without transactions
+        let mut blocks = generate_correct_block_sequence(0..10, Default::default(), 0);
+        for i in 0..blocks.len() {
+            if i > 0 {
+                blocks[i].parent_hash = blocks[i - 1].hash.clone();
+            }
+            blocks[i].hash = calculate_block_hash(&blocks[i]);
+        }
+
+        let mut tampered_blocks = generate_correct_block_sequence(10..15, Default::default(), 0);
+        for i in 0..tampered_blocks.len() {
+            tampered_blocks[i].parent_hash = match i {
+                0 => blocks[9].hash.clone(),
+                _ => tampered_blocks[i - 1].hash.clone(),
+            };
+            tampered_blocks[i].hash = calculate_block_hash(&tampered_blocks[i]);
+        }
+        tampered_blocks[2].gas_used = 21000u64.into();
+
+        let mock_client = MockClient::with_blocks(
+            EvmGlobalState::Enabled,
+            blocks.iter().chain(&tampered_blocks).cloned(),
+        );
+        let mut extractor = BlockExtractor::new(
+            Arc::new(EthJsonRpcClient::new(mock_client)),
+            10,
+            5,
+            1,
+            db_client.clone(),
+        )
+        .with_integrity_verification(true);
+
+        extractor.collect_all(0, 9).await.unwrap();
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+
+        // The batch with the tampered block is not stored
+        assert!(extractor.collect_all(10, 14).await.is_err());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+        assert!(db_client.get_block_by_number(10).await.is_err());
+
+        // The blocks without valid hashes are rejected as well
+        let blocks = generate_correct_block_sequence(10..15, blocks[9].hash.clone(), 0);
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks);
+        let mut extractor = BlockExtractor::new(
+            Arc::new(EthJsonRpcClient::new(mock_client)),
+            10,
+            5,
+            1,
+            db_client.clone(),
+        )
+        .with_integrity_verification(true);
+        assert!(extractor.collect_all(10, 14).await.is_err());
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn test_server_returns_blocks_according_to_tags() {
     test_with_clients(async move |db_client| {
@@ -617,7 +1271,10 @@ async fn test_server_returns_blocks_according_to_tags() {
             .cloned()
             .collect();
         let blocks: Vec<_> = blocks.into_iter().map(Into::into).collect();
-        db_client.insert_block_data(&blocks, &txs).await.unwrap();
+        db_client
+            .insert_block_data(&blocks, &txs, &[])
+            .await
+            .unwrap();
         let block_info = BlockchainBlockInfo {
             earliest_block_number: 0,
             latest_block_number: 1000,
@@ -631,9 +1288,17 @@ async fn test_server_returns_blocks_according_to_tags() {
         let client = Arc::new(EthJsonRpcClient::new(MockClient::new(
             EvmGlobalState::Enabled,
         )));
-        let _server = server::server_start(addr, db_client.clone(), client)
-            .await
-            .unwrap();
+        let server = server::server_start(
+            addr,
+            db_client.clone(),
+            client,
+            100,
+            None,
+            &[],
+            ChainEvents::default(),
+        )
+        .await
+        .unwrap();
         let extractor_client = EthJsonRpcClient::new(ReqwestClient::new(format!("http://{addr}")));
 
         let block = extractor_client
@@ -660,6 +1325,13 @@ async fn test_server_returns_blocks_according_to_tags() {
             .unwrap();
         assert_eq!(block.number.as_u64(), block_info.finalized_block_number);
 
+        // pending block is not extracted, so the latest block in storage is returned.
+        let block = extractor_client
+            .get_block_by_number(BlockNumber::Pending)
+            .await
+            .unwrap();
+        assert_eq!(block.number.as_u64(), block_numbers.end - 1);
+
         // when safe and finalized blocks are not extracted, server should return latest block in storage.
         let block_info = BlockchainBlockInfo {
             earliest_block_number: 0,
@@ -681,6 +1353,31 @@ async fn test_server_returns_blocks_according_to_tags() {
             .await
             .unwrap();
         assert_eq!(block.number.as_u64(), block_numbers.end - 1);
+
+        // when pending block is extracted, it should be returned.
+        let block_info = BlockchainBlockInfo {
+            earliest_block_number: 10,
+            latest_block_number: 1000,
+            safe_block_number: 1000,
+            finalized_block_number: 1000,
+            pending_block_number: 50,
+        };
+        db_client.set_block_info(block_info.clone()).await.unwrap();
+
+        let block = extractor_client
+            .get_block_by_number(BlockNumber::Pending)
+            .await
+            .unwrap();
+        assert_eq!(block.number.as_u64(), block_info.pending_block_number);
+
+        // earliest block is taken from block info when it is extracted.
+        let block = extractor_client
+            .get_block_by_number(BlockNumber::Earliest)
+            .await
+            .unwrap();
+        assert_eq!(block.number.as_u64(), block_info.earliest_block_number);
+
+        server::server_stop(server).await.unwrap();
     })
     .await
 }
@@ -703,9 +1400,17 @@ async fn test_extractor_forwards_confirm_block_requests() {
         let client = Arc::new(EthJsonRpcClient::new(MockClient::new(
             EvmGlobalState::Enabled,
         )));
-        let _server = server::server_start(addr, db_client.clone(), client)
-            .await
-            .unwrap();
+        let server = server::server_start(
+            addr,
+            db_client.clone(),
+            client,
+            100,
+            None,
+            &[],
+            ChainEvents::default(),
+        )
+        .await
+        .unwrap();
         let extractor_client = EthJsonRpcClient::new(ReqwestClient::new(format!("http://{addr}")));
 
         // Should not be forwarded
@@ -727,11 +1432,13 @@ async fn test_extractor_forwards_confirm_block_requests() {
             .await
             .unwrap();
         assert_eq!(confirm_result, BlockConfirmationResult::Confirmed);
+
+        server::server_stop(server).await.unwrap();
     })
     .await
 }
 
-fn generate_correct_block_sequence(
+pub fn generate_correct_block_sequence(
     ids: Range<u64>,
     parent_hash: did::H256,
     txs_per_block: usize,
@@ -774,3 +1481,22 @@ fn generate_correct_block_sequence(
 
     blocks_with_txs
 }
+
+/// Generates a block sequence with the same numbers and transactions
+/// of `generate_correct_block_sequence`, but different block hashes
+pub fn generate_forked_block_sequence(
+    ids: Range<u64>,
+    parent_hash: did::H256,
+    txs_per_block: usize,
+) -> Vec<did::Block<did::Transaction>> {
+    let mut blocks = generate_correct_block_sequence(ids, parent_hash, txs_per_block);
+
+    for i in 0..blocks.len() {
+        blocks[i].hash = i32_to_h256(-(blocks[i].number.as_u64() as i32));
+        if i > 0 {
+            blocks[i].parent_hash = blocks[i - 1].hash.clone();
+        }
+    }
+
+    blocks
+}
@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+
+use evm_block_extractor::config::{Database, ExtractorArgs, PoolArgs};
+use tempfile::TempDir;
+
+/// Writes the configuration file and returns the directory holding it, with its path
+fn write_config(content: &str) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, content).unwrap();
+    (dir, path)
+}
+
+#[test]
+fn test_config_file_sets_the_arguments() {
+    let (_dir, path) = write_config(
+        r#"
+        rpc_url = "http://127.0.0.1:8545"
+        server_address = "127.0.0.1:9000"
+        rpc_batch_size = 50
+        follow = true
+        proxy_methods = ["eth_call", "eth_estimateGas"]
+
+        [sqlite]
+        database_path = "/tmp/extractor.db"
+        max_connections = 4
+        "#,
+    );
+
+    let config = ExtractorArgs::load_from([
+        "evm-block-extractor".into(),
+        "--config".into(),
+        path.into_os_string(),
+    ])
+    .unwrap();
+
+    assert_eq!(config.remote_rpc_urls, vec!["http://127.0.0.1:8545"]);
+    assert_eq!(config.server_address, "127.0.0.1:9000");
+    assert_eq!(config.rpc_batch_size, 50);
+    assert!(config.follow);
+    assert_eq!(config.proxy_methods, vec!["eth_call", "eth_estimateGas"]);
+    // Not in the configuration file
+    assert_eq!(config.max_parallel_batches, 1);
+
+    let Database::Sqlite {
+        database_path,
+        pool,
+        action,
+    } = config.command
+    else {
+        panic!("unexpected database {:?}", config.command);
+    };
+    assert_eq!(database_path, PathBuf::from("/tmp/extractor.db"));
+    assert_eq!(
+        pool,
+        PoolArgs {
+            max_connections: 4,
+            ..Default::default()
+        }
+    );
+    assert!(action.is_none());
+}
+
+#[test]
+fn test_command_line_overrides_the_config_file() {
+    let (_dir, path) = write_config(
+        r#"
+        rpc_url = "http://127.0.0.1:8545"
+        server_address = "127.0.0.1:9000"
+
+        [postgres]
+        username = "postgres"
+        password_file = "/run/secrets/postgres"
+        database_name = "extractor"
+        database_url = "127.0.0.1"
+        "#,
+    );
+    let config_arg = format!("--config={}", path.display());
+
+    let config = ExtractorArgs::load_from([
+        "evm-block-extractor",
+        config_arg.as_str(),
+        "--server-address",
+        "0.0.0.0:8080",
+        "--postgres",
+        "--database-port",
+        "6543",
+        "--max-connections",
+        "20",
+    ])
+    .unwrap();
+
+    assert_eq!(config.server_address, "0.0.0.0:8080");
+    assert_eq!(config.remote_rpc_urls, vec!["http://127.0.0.1:8545"]);
+
+    let Database::Postgres {
+        username,
+        password,
+        password_file,
+        database_name,
+        database_port,
+        pool,
+        ..
+    } = config.command
+    else {
+        panic!("unexpected database {:?}", config.command);
+    };
+    assert_eq!(username, "postgres");
+    assert!(password.is_none());
+    assert_eq!(password_file, Some(PathBuf::from("/run/secrets/postgres")));
+    assert_eq!(database_name, "extractor");
+    assert_eq!(database_port, 6543);
+    assert_eq!(pool.max_connections, 20);
+
+    // The database on the command line replaces the one in the configuration file
+    let config =
+        ExtractorArgs::load_from(["evm-block-extractor", config_arg.as_str(), "--in-memory"])
+            .unwrap();
+    assert!(matches!(config.command, Database::InMemory));
+}
+
+#[test]
+fn test_invalid_config_file_is_rejected() {
+    let (_dir, path) = write_config(
+        r#"
+        rpc_url = "http://127.0.0.1:8545"
+        unknown_key = 1
+
+        [in_memory]
+        "#,
+    );
+    let config_arg = format!("--config={}", path.display());
+    assert!(ExtractorArgs::load_from(["evm-block-extractor", config_arg.as_str()]).is_err());
+
+    let (_dir, path) = write_config(
+        r#"
+        rpc_url = "http://127.0.0.1:8545"
+
+        [in_memory]
+
+        [sqlite]
+        database_path = "/tmp/extractor.db"
+        "#,
+    );
+    let config_arg = format!("--config={}", path.display());
+    assert!(ExtractorArgs::load_from(["evm-block-extractor", config_arg.as_str()]).is_err());
+
+    let config_arg = "--config=/this/file/does/not/exist.toml";
+    assert!(ExtractorArgs::load_from(["evm-block-extractor", config_arg, "--in-memory"]).is_err());
+}
@@ -1,6 +1,11 @@
-use did::{Block, H160, H256, Transaction, U64, U256};
-use evm_block_extractor::database::postgres_db_client::PostgresDbClient;
-use evm_block_extractor::database::{AccountBalance, CertifiedBlock, DatabaseClient};
+use chrono::{TimeDelta, Utc};
+use did::transaction::TransactionReceiptLog;
+use did::{Block, H160, H256, Transaction, TransactionReceipt, U64, U256};
+use evm_block_extractor::database::any_db_client::AnyDbClient;
+use evm_block_extractor::database::{
+    AccountBalance, AddressTransactionsQuery, CertifiedBlock, DatabaseClient, LogsBlockFilter,
+    LogsQuery, TransactionDirection, TransactionPosition, created_contract_address,
+};
 use rand::random;
 
 use crate::test_with_clients;
@@ -49,7 +54,10 @@ async fn test_batch_insertion_of_blocks_and_transactions_retrieval() {
             }
         }
 
-        db_client.insert_block_data(&blocks, &txn).await.unwrap();
+        db_client
+            .insert_block_data(&blocks, &txn, &[])
+            .await
+            .unwrap();
 
         let block = db_client.get_full_block_by_number(1).await.unwrap();
 
@@ -98,7 +106,7 @@ async fn test_retrieval_of_latest_and_oldest_block_number() {
             };
 
             db_client
-                .insert_block_data(&[dummy_block], &[])
+                .insert_block_data(&[dummy_block], &[], &[])
                 .await
                 .unwrap();
         }
@@ -124,7 +132,7 @@ async fn test_init_idempotency() {
 
         assert!(
             db_client
-                .insert_block_data(&[dummy_block], &[])
+                .insert_block_data(&[dummy_block], &[], &[])
                 .await
                 .is_err()
         );
@@ -141,7 +149,7 @@ async fn test_init_idempotency() {
 
         assert!(
             db_client
-                .insert_block_data(&[dummy_block], &[])
+                .insert_block_data(&[dummy_block], &[], &[])
                 .await
                 .is_ok()
         );
@@ -187,7 +195,10 @@ async fn test_retrieval_of_transactions_with_blocks() {
 
         blocks[4].transactions = txn.iter().map(|tx| tx.hash.clone()).collect();
 
-        db_client.insert_block_data(&blocks, &txn).await.unwrap();
+        db_client
+            .insert_block_data(&blocks, &txn, &[])
+            .await
+            .unwrap();
 
         let block = db_client.get_block_by_number(1).await.unwrap();
 
@@ -211,6 +222,96 @@ async fn test_retrieval_of_transactions_with_blocks() {
     .await;
 }
 
+#[tokio::test]
+async fn test_retrieval_of_blocks_by_range() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        let mut blocks = Vec::new();
+        let mut txs = Vec::new();
+        for i in 1..=10_u64 {
+            let mut block: Block<H256> = Block {
+                number: i.into(),
+                hash: alloy::primitives::B256::random().into(),
+                ..Default::default()
+            };
+
+            // Blocks with an even number have as many transactions as their number
+            if i % 2 == 0 {
+                for index in 0..i {
+                    let tx = Transaction {
+                        hash: alloy::primitives::B256::random().into(),
+                        block_number: Some(i.into()),
+                        block_hash: Some(block.hash.clone()),
+                        transaction_index: Some(index.into()),
+                        ..Default::default()
+                    };
+                    block.transactions.push(tx.hash.clone());
+                    txs.push(tx);
+                }
+            }
+
+            blocks.push(block);
+        }
+
+        db_client
+            .insert_block_data(&blocks, &txs, &[])
+            .await
+            .unwrap();
+
+        let range = db_client.get_blocks_by_range(3, 6).await.unwrap();
+        assert_eq!(range, blocks[2..6]);
+
+        let full_range = db_client.get_full_blocks_by_range(3, 6).await.unwrap();
+        assert_eq!(full_range.len(), 4);
+        for (full_block, block) in full_range.iter().zip(&blocks[2..6]) {
+            assert_eq!(full_block.hash, block.hash);
+            assert_eq!(
+                full_block
+                    .transactions
+                    .iter()
+                    .map(|tx| tx.hash.clone())
+                    .collect::<Vec<_>>(),
+                block.transactions
+            );
+        }
+
+        // Blocks not in the database are skipped
+        let range = db_client.get_blocks_by_range(9, 20).await.unwrap();
+        assert_eq!(range, blocks[8..]);
+        let full_range = db_client.get_full_blocks_by_range(9, 20).await.unwrap();
+        assert_eq!(full_range.len(), 2);
+        assert_eq!(full_range[1].transactions.len(), 10);
+
+        assert!(
+            db_client
+                .get_blocks_by_range(11, 20)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            db_client
+                .get_full_blocks_by_range(6, 5)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let block = db_client.find_block_by_number(3).await.unwrap();
+        assert_eq!(block.as_ref(), blocks.get(2));
+        assert!(db_client.find_block_by_number(11).await.unwrap().is_none());
+
+        // The first block from a number is the next stored one
+        let first_block = db_client.find_first_block_from(0).await.unwrap();
+        assert_eq!(first_block.as_ref(), blocks.first());
+        let first_block = db_client.find_first_block_from(10).await.unwrap();
+        assert_eq!(first_block.as_ref(), blocks.last());
+        assert!(db_client.find_first_block_from(11).await.unwrap().is_none());
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn test_deletion_and_creation_of_table_when_earliest_blocks_are_different() {
     test_with_clients(async move |db_client| {
@@ -234,7 +335,7 @@ async fn test_deletion_and_creation_of_table_when_earliest_blocks_are_different(
 
         assert!(
             db_client
-                .insert_block_data(&[block_one.clone().into()], &[])
+                .insert_block_data(&[block_one.clone().into()], &[], &[])
                 .await
                 .is_ok()
         );
@@ -257,7 +358,7 @@ async fn test_deletion_and_creation_of_table_when_earliest_blocks_are_different(
         // Add a block
         assert!(
             db_client
-                .insert_block_data(&[block_two.clone().into()], &[])
+                .insert_block_data(&[block_two.clone().into()], &[], &[])
                 .await
                 .is_ok()
         );
@@ -289,6 +390,7 @@ async fn test_deletion_and_clearing_of_database() {
                     hash: alloy::primitives::B256::random().into(),
                     ..Default::default()
                 }],
+                &[],
             )
             .await
             .unwrap();
@@ -328,7 +430,7 @@ async fn test_check_if_same_block_hash() {
         };
 
         db_client
-            .insert_block_data(&[dummy_block.clone()], &[])
+            .insert_block_data(&[dummy_block.clone()], &[], &[])
             .await
             .unwrap();
 
@@ -355,6 +457,61 @@ async fn test_check_if_same_block_hash() {
     .await;
 }
 
+#[tokio::test]
+async fn test_retrieval_of_block_by_hash() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        let dummy_block: Block<H256> = Block {
+            number: alloy::primitives::U64::from(1).into(),
+            hash: alloy::primitives::B256::random().into(),
+            ..Default::default()
+        };
+
+        db_client
+            .insert_block_data(&[dummy_block.clone()], &[], &[])
+            .await
+            .unwrap();
+
+        let block = db_client
+            .get_block_by_hash(dummy_block.hash.clone())
+            .await
+            .unwrap();
+        assert_eq!(block, dummy_block);
+
+        let unknown_hash: H256 = alloy::primitives::B256::random().into();
+        assert!(
+            db_client
+                .find_block_by_hash(unknown_hash.clone())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(db_client.get_block_by_hash(unknown_hash).await.is_err());
+
+        db_client
+            .discard_blocks_from(1, "test reason")
+            .await
+            .unwrap();
+
+        assert!(
+            db_client
+                .find_block_by_hash(dummy_block.hash.clone())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let discarded = db_client
+            .find_discarded_block_by_hash(dummy_block.hash.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(discarded.block.hash, dummy_block.hash);
+        assert_eq!(discarded.reason, "test reason");
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn test_insertion_of_blocks_with_txs() {
     test_with_clients(async move |db_client| {
@@ -374,7 +531,7 @@ async fn test_insertion_of_blocks_with_txs() {
         };
 
         db_client
-            .insert_block_data(&[dummy_block.clone()], &[dummy_txn.clone()])
+            .insert_block_data(&[dummy_block.clone()], &[dummy_txn.clone()], &[])
             .await
             .unwrap();
 
@@ -389,6 +546,535 @@ async fn test_insertion_of_blocks_with_txs() {
     .await;
 }
 
+#[tokio::test]
+async fn test_insertion_and_retrieval_of_receipts() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        const TRANSACTIONS_PER_BLOCK: u64 = 5;
+
+        let mut block: Block<H256> = Block {
+            number: alloy::primitives::U64::from(1).into(),
+            hash: alloy::primitives::B256::random().into(),
+            ..Default::default()
+        };
+
+        let mut txs = vec![];
+        let mut receipts = vec![];
+        for i in 0..TRANSACTIONS_PER_BLOCK {
+            let tx = Transaction {
+                hash: alloy::primitives::B256::random().into(),
+                block_number: Some(1_u64.into()),
+                block_hash: Some(block.hash.clone()),
+                transaction_index: Some(i.into()),
+                ..Default::default()
+            };
+            receipts.push(TransactionReceipt {
+                transaction_hash: tx.hash.clone(),
+                transaction_index: i.into(),
+                block_hash: block.hash.clone(),
+                block_number: 1_u64.into(),
+                status: Some(U64::from(1_u64)),
+                ..Default::default()
+            });
+            block.transactions.push(tx.hash.clone());
+            txs.push(tx);
+        }
+
+        // insert the receipts in reverse order to check they are sorted by index
+        let reversed_receipts = receipts.iter().rev().cloned().collect::<Vec<_>>();
+        db_client
+            .insert_block_data(&[block], &txs, &reversed_receipts)
+            .await
+            .unwrap();
+
+        for receipt in &receipts {
+            let stored = db_client
+                .find_transaction_receipt(receipt.transaction_hash.clone())
+                .await
+                .unwrap();
+            assert_eq!(stored.as_ref(), Some(receipt));
+        }
+
+        let block_receipts = db_client.get_block_receipts(1).await.unwrap();
+        assert_eq!(block_receipts, receipts);
+
+        assert!(db_client.get_block_receipts(2).await.unwrap().is_empty());
+
+        let range_receipts = db_client.get_receipts_by_range(0, 2).await.unwrap();
+        assert_eq!(range_receipts, receipts);
+        assert!(
+            db_client
+                .get_receipts_by_range(2, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            db_client
+                .find_transaction_receipt(alloy::primitives::B256::random().into())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Receipts should be removed with the discarded blocks
+        db_client
+            .discard_blocks_from(1, "test reason")
+            .await
+            .unwrap();
+        assert!(db_client.get_block_receipts(1).await.unwrap().is_empty());
+        assert!(
+            db_client
+                .find_transaction_receipt(receipts[0].transaction_hash.clone())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_insertion_and_filtering_of_logs() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        let address_1 = H160::from(alloy::primitives::Address::random());
+        let address_2 = H160::from(alloy::primitives::Address::random());
+        let topic_1 = H256::from(alloy::primitives::B256::random());
+        let topic_2 = H256::from(alloy::primitives::B256::random());
+
+        let mut blocks = vec![];
+        let mut txs = vec![];
+        let mut receipts = vec![];
+        for block_number in 1..=3_u64 {
+            let mut block: Block<H256> = Block {
+                number: block_number.into(),
+                hash: alloy::primitives::B256::random().into(),
+                ..Default::default()
+            };
+
+            let tx = Transaction {
+                hash: alloy::primitives::B256::random().into(),
+                block_number: Some(block_number.into()),
+                block_hash: Some(block.hash.clone()),
+                transaction_index: Some(0_u64.into()),
+                ..Default::default()
+            };
+
+            // Each transaction emits one log from each address
+            let logs = [(&address_1, &topic_1), (&address_2, &topic_2)]
+                .into_iter()
+                .enumerate()
+                .map(|(log_index, (address, topic))| TransactionReceiptLog {
+                    address: address.clone(),
+                    topics: vec![topic.clone(), topic_1.clone()],
+                    transaction_hash: tx.hash.clone(),
+                    block_number: block_number.into(),
+                    block_hash: block.hash.clone(),
+                    transaction_index: 0_u64.into(),
+                    log_index: U256::from(log_index as u64),
+                    ..Default::default()
+                })
+                .collect();
+
+            receipts.push(TransactionReceipt {
+                transaction_hash: tx.hash.clone(),
+                block_hash: block.hash.clone(),
+                block_number: block_number.into(),
+                logs,
+                ..Default::default()
+            });
+            block.transactions.push(tx.hash.clone());
+            blocks.push(block);
+            txs.push(tx);
+        }
+
+        db_client
+            .insert_block_data(&blocks, &txs, &receipts)
+            .await
+            .unwrap();
+
+        let query = LogsQuery {
+            block_filter: LogsBlockFilter::Range { from: 1, to: 3 },
+            addresses: vec![],
+            topics: vec![],
+            limit: Some(100),
+        };
+
+        // All logs, ordered
+        let logs = db_client.get_logs(&query).await.unwrap();
+        let expected_logs = receipts
+            .iter()
+            .flat_map(|r| r.logs.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(logs, expected_logs);
+
+        // Block range
+        let logs = db_client
+            .get_logs(&LogsQuery {
+                block_filter: LogsBlockFilter::Range { from: 2, to: 2 },
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs, receipts[1].logs);
+
+        // Block hash
+        let logs = db_client
+            .get_logs(&LogsQuery {
+                block_filter: LogsBlockFilter::Hash(blocks[2].hash.clone()),
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs, receipts[2].logs);
+
+        // Address
+        let logs = db_client
+            .get_logs(&LogsQuery {
+                addresses: vec![address_2.clone()],
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 3);
+        assert!(logs.iter().all(|log| log.address == address_2));
+
+        // Topics by position
+        let logs = db_client
+            .get_logs(&LogsQuery {
+                topics: vec![vec![topic_1.clone()]],
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 3);
+        assert!(logs.iter().all(|log| log.address == address_1));
+
+        let logs = db_client
+            .get_logs(&LogsQuery {
+                topics: vec![vec![], vec![topic_1.clone()]],
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 6);
+
+        let logs = db_client
+            .get_logs(&LogsQuery {
+                topics: vec![
+                    vec![topic_1.clone(), topic_2.clone()],
+                    vec![topic_2.clone()],
+                ],
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert!(logs.is_empty());
+
+        // Limit
+        let logs = db_client
+            .get_logs(&LogsQuery {
+                limit: Some(4),
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs, expected_logs[..4]);
+
+        // No limit
+        let logs = db_client
+            .get_logs(&LogsQuery {
+                limit: None,
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs, expected_logs);
+
+        // Logs should be removed with the discarded blocks
+        db_client
+            .discard_blocks_from(2, "test reason")
+            .await
+            .unwrap();
+        let logs = db_client.get_logs(&query).await.unwrap();
+        assert_eq!(logs, receipts[0].logs);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_insertion_and_filtering_of_transactions_by_address() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        let address_1 = H160::from(alloy::primitives::Address::random());
+        let address_2 = H160::from(alloy::primitives::Address::random());
+
+        let mut blocks = vec![];
+        let mut txs = vec![];
+        for block_number in 1..=3_u64 {
+            let mut block: Block<H256> = Block {
+                number: block_number.into(),
+                hash: alloy::primitives::B256::random().into(),
+                ..Default::default()
+            };
+
+            // A transfer from address 1 to address 2, then a contract deployed by address 2
+            let transfer = Transaction {
+                hash: alloy::primitives::B256::random().into(),
+                block_number: Some(block_number.into()),
+                block_hash: Some(block.hash.clone()),
+                transaction_index: Some(0_u64.into()),
+                from: address_1.clone(),
+                to: Some(address_2.clone()),
+                ..Default::default()
+            };
+            let deployment = Transaction {
+                hash: alloy::primitives::B256::random().into(),
+                block_number: Some(block_number.into()),
+                block_hash: Some(block.hash.clone()),
+                transaction_index: Some(1_u64.into()),
+                from: address_2.clone(),
+                to: None,
+                nonce: block_number.into(),
+                ..Default::default()
+            };
+
+            for tx in [transfer, deployment] {
+                block.transactions.push(tx.hash.clone());
+                txs.push(tx);
+            }
+            blocks.push(block);
+        }
+
+        db_client
+            .insert_block_data(&blocks, &txs, &[])
+            .await
+            .unwrap();
+
+        let query = AddressTransactionsQuery {
+            address: address_1.clone(),
+            direction: TransactionDirection::Any,
+            from_block: 1,
+            to_block: 3,
+            descending: false,
+            after: None,
+            limit: 100,
+        };
+
+        // Sent transactions
+        let transactions = db_client.get_transactions_by_address(&query).await.unwrap();
+        assert_eq!(
+            transactions,
+            vec![txs[0].clone(), txs[2].clone(), txs[4].clone()]
+        );
+
+        let transactions = db_client
+            .get_transactions_by_address(&AddressTransactionsQuery {
+                direction: TransactionDirection::To,
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert!(transactions.is_empty());
+
+        // Both sent and received transactions, in both orders
+        let query = AddressTransactionsQuery {
+            address: address_2.clone(),
+            ..query
+        };
+        let transactions = db_client.get_transactions_by_address(&query).await.unwrap();
+        assert_eq!(transactions, txs);
+
+        let transactions = db_client
+            .get_transactions_by_address(&AddressTransactionsQuery {
+                descending: true,
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(transactions, txs.iter().rev().cloned().collect::<Vec<_>>());
+
+        let transactions = db_client
+            .get_transactions_by_address(&AddressTransactionsQuery {
+                direction: TransactionDirection::From,
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            transactions,
+            vec![txs[1].clone(), txs[3].clone(), txs[5].clone()]
+        );
+
+        // Block range
+        let transactions = db_client
+            .get_transactions_by_address(&AddressTransactionsQuery {
+                from_block: 2,
+                to_block: 2,
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(transactions, txs[2..4]);
+
+        // Pagination
+        let transactions = db_client
+            .get_transactions_by_address(&AddressTransactionsQuery {
+                limit: 3,
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(transactions, txs[..3]);
+
+        let after = TransactionPosition::of(&transactions[2]);
+        let transactions = db_client
+            .get_transactions_by_address(&AddressTransactionsQuery {
+                after: Some(after),
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(transactions, txs[3..]);
+
+        let transactions = db_client
+            .get_transactions_by_address(&AddressTransactionsQuery {
+                after: Some(after),
+                descending: true,
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(transactions, vec![txs[1].clone(), txs[0].clone()]);
+
+        // The contract creation is a transaction to the created contract
+        let contract_address = created_contract_address(&txs[3]).unwrap();
+        let transactions = db_client
+            .get_transactions_by_address(&AddressTransactionsQuery {
+                address: contract_address,
+                direction: TransactionDirection::To,
+                ..query.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(transactions, vec![txs[3].clone()]);
+
+        // Transactions should be removed with the discarded blocks
+        db_client
+            .discard_blocks_from(2, "test reason")
+            .await
+            .unwrap();
+        let transactions = db_client.get_transactions_by_address(&query).await.unwrap();
+        assert_eq!(transactions, txs[..2]);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_missing_blocks_and_orphan_transactions() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        assert_eq!(
+            db_client.get_missing_block_ranges(0, 9).await.unwrap(),
+            vec![0..=9]
+        );
+
+        let blocks: Vec<Block<H256>> = [2u64, 3, 5, 8]
+            .into_iter()
+            .map(|i| Block {
+                number: U64::from(i),
+                hash: alloy::primitives::B256::random().into(),
+                ..Default::default()
+            })
+            .collect();
+        let transactions: Vec<Transaction> = [3u64, 4, 4, 9]
+            .into_iter()
+            .map(|i| Transaction {
+                hash: alloy::primitives::B256::random().into(),
+                block_number: Some(U64::from(i)),
+                ..Default::default()
+            })
+            .collect();
+        let receipts = [TransactionReceipt {
+            transaction_hash: transactions[1].hash.clone(),
+            block_number: U64::from(4u64),
+            ..Default::default()
+        }];
+
+        db_client
+            .insert_block_data(&blocks, &transactions, &receipts)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            db_client.get_missing_block_ranges(0, 9).await.unwrap(),
+            vec![0..=1, 4..=4, 6..=7, 9..=9]
+        );
+        assert_eq!(
+            db_client.get_missing_block_ranges(2, 5).await.unwrap(),
+            vec![4..=4]
+        );
+        assert_eq!(
+            db_client.get_missing_block_ranges(3, 3).await.unwrap(),
+            vec![]
+        );
+
+        assert_eq!(
+            db_client
+                .get_orphan_transaction_block_numbers(0, 9)
+                .await
+                .unwrap(),
+            vec![4, 9]
+        );
+        assert_eq!(
+            db_client
+                .get_orphan_transaction_block_numbers(0, 5)
+                .await
+                .unwrap(),
+            vec![4]
+        );
+
+        db_client.delete_block_transactions(&[4, 9]).await.unwrap();
+
+        assert!(
+            db_client
+                .get_orphan_transaction_block_numbers(0, 9)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        for tx in &transactions[1..] {
+            assert!(
+                db_client
+                    .find_transaction(tx.hash.clone())
+                    .await
+                    .unwrap()
+                    .is_none()
+            );
+        }
+        assert!(
+            db_client
+                .find_transaction_receipt(transactions[1].hash.clone())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            db_client
+                .find_transaction(transactions[0].hash.clone())
+                .await
+                .unwrap()
+                .is_some()
+        );
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn test_insertion_of_blocks_with_no_txs() {
     test_with_clients(async move |db_client| {
@@ -401,7 +1087,7 @@ async fn test_insertion_of_blocks_with_no_txs() {
         };
 
         db_client
-            .insert_block_data(&[dummy_block.clone()], &[])
+            .insert_block_data(&[dummy_block.clone()], &[], &[])
             .await
             .unwrap();
 
@@ -427,7 +1113,7 @@ async fn test_insertion_of_txs_with_no_blocks() {
         };
 
         db_client
-            .insert_block_data(&[], &[dummy_txn.clone()])
+            .insert_block_data(&[], &[dummy_txn.clone()], &[])
             .await
             .unwrap();
 
@@ -654,7 +1340,10 @@ async fn test_blockchain_tail_discard_and_get_discarded_entries() {
             }
         }
 
-        db_client.insert_block_data(&blocks, &txn).await.unwrap();
+        db_client
+            .insert_block_data(&blocks, &txn, &[])
+            .await
+            .unwrap();
 
         let certified_block = CertifiedBlock {
             certificate: vec![1, 2, 3],
@@ -716,7 +1405,7 @@ async fn test_blockchain_tail_discard_and_get_discarded_entries() {
 }
 
 async fn check_blocks_with_txs_storage_state(
-    db_client: &PostgresDbClient,
+    db_client: &AnyDbClient,
     blocks: &[Block<did::H256>],
     storage_state: StorageState,
 ) -> bool {
@@ -751,3 +1440,119 @@ impl StorageState {
         }
     }
 }
+
+#[tokio::test]
+async fn test_pruning_of_blocks_and_discarded_blocks() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        let mut blocks = vec![];
+        let mut txs = vec![];
+        for i in 1..=5_u64 {
+            let tx = Transaction {
+                hash: alloy::primitives::B256::random().into(),
+                block_number: Some(i.into()),
+                transaction_index: Some(0_u64.into()),
+                ..Default::default()
+            };
+            blocks.push(Block::<H256> {
+                number: i.into(),
+                hash: alloy::primitives::B256::random().into(),
+                transactions: vec![tx.hash.clone()],
+                ..Default::default()
+            });
+            txs.push(tx);
+        }
+
+        db_client
+            .insert_block_data(&blocks, &txs, &[])
+            .await
+            .unwrap();
+
+        // Headers only: the blocks are kept without their transactions
+        assert_eq!(
+            db_client.prune_blocks_before(3, 10, true).await.unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            db_client.prune_blocks_before(3, 10, true).await.unwrap(),
+            None
+        );
+        assert!(
+            db_client
+                .get_block_by_number(1)
+                .await
+                .unwrap()
+                .transactions
+                .is_empty()
+        );
+        assert!(
+            db_client
+                .find_transaction(txs[1].hash.clone())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 1);
+
+        // Bounded batches
+        assert_eq!(
+            db_client.prune_blocks_before(4, 2, false).await.unwrap(),
+            Some(2)
+        );
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 3);
+        assert_eq!(
+            db_client.prune_blocks_before(4, 2, false).await.unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            db_client.prune_blocks_before(4, 2, false).await.unwrap(),
+            None
+        );
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 4);
+        assert!(
+            db_client
+                .find_transaction(txs[2].hash.clone())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            db_client
+                .find_transaction(txs[3].hash.clone())
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // Discarded blocks
+        db_client
+            .discard_blocks_from(5, "test reason")
+            .await
+            .unwrap();
+        let an_hour_ago = Utc::now() - TimeDelta::hours(1);
+        assert_eq!(
+            db_client
+                .prune_discarded_blocks(an_hour_ago, 10)
+                .await
+                .unwrap(),
+            0
+        );
+        let in_an_hour = Utc::now() + TimeDelta::hours(1);
+        assert_eq!(
+            db_client
+                .prune_discarded_blocks(in_an_hour, 10)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(
+            db_client
+                .find_discarded_block_by_hash(blocks[4].hash.clone())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    })
+    .await;
+}
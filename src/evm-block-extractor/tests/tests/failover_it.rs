@@ -0,0 +1,194 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use did::evm_state::EvmGlobalState;
+use did::rpc::request::RpcRequest;
+use did::rpc::response::RpcResponse;
+use did::{BlockNumber, H256};
+use ethereum_json_rpc_client::reqwest::reqwest::StatusCode;
+use ethereum_json_rpc_client::{Client, EthJsonRpcClient, JsonRpcError, JsonRpcResult};
+use evm_block_extractor::database::DatabaseClient;
+use evm_block_extractor::database::any_db_client::AnyDbClient;
+use evm_block_extractor::failover::{Endpoint, FailoverClient};
+
+use crate::test_with_clients;
+use crate::tests::block_extractor_it::{
+    MockClient, generate_correct_block_sequence, generate_forked_block_sequence,
+};
+
+/// A mock endpoint which can be taken down
+#[derive(Clone)]
+pub struct TestClient {
+    pub available: Arc<AtomicBool>,
+    pub mock: MockClient,
+}
+
+impl TestClient {
+    pub fn set_available(&self, available: bool) {
+        self.available.store(available, Ordering::Relaxed);
+    }
+}
+
+impl Client for TestClient {
+    fn send_rpc_request(
+        &self,
+        request: RpcRequest,
+    ) -> Pin<Box<dyn Future<Output = JsonRpcResult<RpcResponse>> + Send>> {
+        if self.available.load(Ordering::Relaxed) {
+            self.mock.send_rpc_request(request)
+        } else {
+            Box::pin(async {
+                Err(JsonRpcError::Http {
+                    code: StatusCode::SERVICE_UNAVAILABLE,
+                    text: String::new(),
+                })
+            })
+        }
+    }
+}
+
+fn endpoint(
+    url: &str,
+    available: bool,
+    blocks: Vec<did::Block<did::Transaction>>,
+) -> Endpoint<TestClient> {
+    Endpoint {
+        url: url.to_string(),
+        client: TestClient {
+            available: Arc::new(AtomicBool::new(available)),
+            mock: MockClient::with_blocks(EvmGlobalState::Enabled, blocks),
+        },
+    }
+}
+
+/// Stores the first 10 blocks of `blocks` and the chain id of the mock client
+async fn store_blocks(db_client: &AnyDbClient, blocks: &[did::Block<did::Transaction>]) {
+    db_client.init(None, true).await.unwrap();
+
+    let stored_blocks: Vec<did::Block<H256>> =
+        blocks[..10].iter().cloned().map(Into::into).collect();
+    let stored_txs: Vec<_> = blocks[..10]
+        .iter()
+        .flat_map(|b| &b.transactions)
+        .cloned()
+        .collect();
+    db_client
+        .insert_block_data(&stored_blocks, &stored_txs, &[])
+        .await
+        .unwrap();
+    db_client.insert_chain_id(42).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_failover_to_an_endpoint_serving_the_same_blockchain() {
+    test_with_clients(async move |db_client| {
+        let blocks = generate_correct_block_sequence(0..15, Default::default(), 2);
+        store_blocks(&db_client, &blocks).await;
+        let forked_blocks = blocks[..8]
+            .iter()
+            .cloned()
+            .chain(generate_forked_block_sequence(
+                8..15,
+                blocks[7].hash.clone(),
+                2,
+            ))
+            .collect::<Vec<_>>();
+
+        let client = EthJsonRpcClient::new(
+            FailoverClient::new(
+                vec![
+                    endpoint("down", false, blocks.clone()),
+                    endpoint("forked", true, forked_blocks.clone()),
+                    endpoint("same", true, blocks.clone()),
+                ],
+                db_client.clone(),
+                Duration::from_secs(60),
+            )
+            .unwrap(),
+        );
+
+        // The forked endpoint is skipped
+        assert_eq!(client.get_block_number().await.unwrap(), 14);
+        let block = client
+            .get_block_by_number(BlockNumber::Number(9u64.into()))
+            .await
+            .unwrap();
+        assert_eq!(block.hash, blocks[9].hash);
+
+        // No endpoint serves the stored blockchain
+        let client = EthJsonRpcClient::new(
+            FailoverClient::new(
+                vec![
+                    endpoint("down", false, blocks.clone()),
+                    endpoint("forked", true, forked_blocks),
+                ],
+                db_client.clone(),
+                Duration::from_secs(60),
+            )
+            .unwrap(),
+        );
+        assert!(client.get_block_number().await.is_err());
+
+        // The EVMC answering with an error is not a failure of the endpoint
+        let client = EthJsonRpcClient::new(
+            FailoverClient::new(
+                vec![
+                    endpoint("empty", true, vec![]),
+                    endpoint("same", true, blocks.clone()),
+                ],
+                db_client.clone(),
+                Duration::from_secs(60),
+            )
+            .unwrap(),
+        );
+        assert!(
+            client
+                .get_block_by_number(BlockNumber::Number(9u64.into()))
+                .await
+                .is_err()
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_failed_endpoints_are_skipped_during_the_cooldown() {
+    test_with_clients(async move |db_client| {
+        let blocks = generate_correct_block_sequence(0..15, Default::default(), 2);
+        store_blocks(&db_client, &blocks).await;
+
+        for (cooldown, recovered) in [(Duration::from_secs(60), false), (Duration::ZERO, true)] {
+            let first = endpoint("first", true, blocks.clone());
+            let second = endpoint("second", true, blocks.clone());
+            let client = EthJsonRpcClient::new(
+                FailoverClient::new(
+                    vec![first.clone(), second.clone()],
+                    db_client.clone(),
+                    cooldown,
+                )
+                .unwrap(),
+            );
+
+            first.client.set_available(false);
+            assert_eq!(client.get_block_number().await.unwrap(), 14);
+
+            // The first endpoint is selected again only after the cooldown
+            first.client.set_available(true);
+            second.client.set_available(false);
+            assert_eq!(client.get_block_number().await.is_ok(), recovered);
+        }
+
+        assert!(
+            FailoverClient::<TestClient, AnyDbClient>::new(
+                vec![],
+                db_client.clone(),
+                Duration::ZERO
+            )
+            .is_err()
+        );
+    })
+    .await;
+}
@@ -1,3 +1,7 @@
+pub mod archive_it;
 pub mod block_extractor_it;
+pub mod config_it;
 pub mod database_client_it;
+pub mod failover_it;
+pub mod retention_it;
 pub mod server_it;
@@ -0,0 +1,275 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use did::evm_state::EvmGlobalState;
+use did::{Block, H256, Transaction, TransactionReceipt};
+use ethereum_json_rpc_client::EthJsonRpcClient;
+use evm_block_extractor::config::ExtractorArgs;
+use evm_block_extractor::database::DatabaseClient;
+use evm_block_extractor::database::any_db_client::AnyDbClient;
+use evm_block_extractor::subscription::ChainEvents;
+use evm_block_extractor::task::block_extractor::start_extractor;
+use evm_block_extractor::task::retention::{RetentionPolicy, start_pruning};
+
+use crate::test_with_clients;
+use crate::tests::block_extractor_it::{MockClient, generate_correct_block_sequence};
+
+const BLOCK_COUNT: u64 = 10;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Stores `BLOCK_COUNT` blocks with two transactions each, produced one day apart;
+/// the last block is one minute old.
+/// Returns the blocks with their transactions.
+async fn store_daily_blocks(db_client: &AnyDbClient) -> Vec<Block<Transaction>> {
+    store_daily_blocks_without(db_client, &[]).await
+}
+
+/// Stores the blocks of [`store_daily_blocks`], except for the `missing` ones.
+/// Returns the stored blocks with their transactions.
+async fn store_daily_blocks_without(
+    db_client: &AnyDbClient,
+    missing: &[u64],
+) -> Vec<Block<Transaction>> {
+    db_client.init(None, false).await.unwrap();
+
+    let now = Utc::now().timestamp() as u64;
+    let mut blocks = vec![];
+    let mut txs = vec![];
+    let mut receipts = vec![];
+    for i in (0..BLOCK_COUNT).filter(|i| !missing.contains(i)) {
+        let mut block: Block<H256> = Block {
+            number: i.into(),
+            hash: alloy::primitives::B256::random().into(),
+            timestamp: (now - 60 - (BLOCK_COUNT - 1 - i) * SECONDS_PER_DAY).into(),
+            ..Default::default()
+        };
+
+        for index in 0..2_u64 {
+            let tx = Transaction {
+                hash: alloy::primitives::B256::random().into(),
+                block_number: Some(i.into()),
+                block_hash: Some(block.hash.clone()),
+                transaction_index: Some(index.into()),
+                ..Default::default()
+            };
+            receipts.push(TransactionReceipt {
+                transaction_hash: tx.hash.clone(),
+                transaction_index: index.into(),
+                block_hash: block.hash.clone(),
+                block_number: i.into(),
+                ..Default::default()
+            });
+            block.transactions.push(tx.hash.clone());
+            txs.push(tx);
+        }
+
+        blocks.push(block);
+    }
+
+    db_client
+        .insert_block_data(&blocks, &txs, &receipts)
+        .await
+        .unwrap();
+
+    let mut txs = txs.into_iter();
+    blocks
+        .into_iter()
+        .map(|block| {
+            let block_txs = txs.by_ref().take(block.transactions.len()).collect();
+            block.into_full_block(block_txs).unwrap()
+        })
+        .collect()
+}
+
+/// Asserts that the blocks before `first_kept_block` have no transactions and receipts,
+/// while the following blocks are stored with all of them
+async fn assert_pruned_before(
+    db_client: &AnyDbClient,
+    blocks: &[Block<Transaction>],
+    first_kept_block: u64,
+) {
+    for block in blocks {
+        let pruned = block.number.as_u64() < first_kept_block;
+        for tx in &block.transactions {
+            let stored_tx = db_client.find_transaction(tx.hash.clone()).await.unwrap();
+            assert_eq!(stored_tx.is_none(), pruned);
+            let stored_receipt = db_client
+                .find_transaction_receipt(tx.hash.clone())
+                .await
+                .unwrap();
+            assert_eq!(stored_receipt.is_none(), pruned);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_pruning_keeps_the_latest_blocks() {
+    test_with_clients(async move |db_client| {
+        let blocks = store_daily_blocks(&db_client).await;
+
+        let policy = RetentionPolicy {
+            keep_blocks: Some(4),
+            keep_days: None,
+            headers_only: false,
+            batch_size: 4,
+        };
+        start_pruning(db_client.clone(), &policy).await.unwrap();
+
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 6);
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+        assert!(db_client.get_block_by_number(5).await.is_err());
+        assert_pruned_before(&db_client, &blocks, 6).await;
+
+        // Pruning again changes nothing
+        start_pruning(db_client.clone(), &policy).await.unwrap();
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 6);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_pruning_keeps_the_blocks_of_the_latest_days() {
+    test_with_clients(async move |db_client| {
+        let blocks = store_daily_blocks(&db_client).await;
+
+        // Blocks 7, 8 and 9 are less than three days old
+        let policy = RetentionPolicy {
+            keep_blocks: None,
+            keep_days: Some(3),
+            headers_only: false,
+            batch_size: 2,
+        };
+        start_pruning(db_client.clone(), &policy).await.unwrap();
+
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 7);
+        assert_pruned_before(&db_client, &blocks, 7).await;
+
+        // The blocks kept by either limit are not pruned
+        let policy = RetentionPolicy {
+            keep_blocks: Some(1),
+            keep_days: Some(2),
+            ..policy
+        };
+        start_pruning(db_client.clone(), &policy).await.unwrap();
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 8);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_pruning_skips_the_missing_blocks() {
+    test_with_clients(async move |db_client| {
+        let blocks = store_daily_blocks_without(&db_client, &[4, 5, 6, 7]).await;
+
+        // Block 7 is the first one less than three days old, and the first one of the
+        // latest three blocks, but it is missing
+        let policy = RetentionPolicy {
+            keep_blocks: Some(3),
+            keep_days: Some(3),
+            headers_only: false,
+            batch_size: 2,
+        };
+        start_pruning(db_client.clone(), &policy).await.unwrap();
+
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 8);
+        assert_pruned_before(&db_client, &blocks, 8).await;
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_pruning_rejects_an_out_of_range_number_of_days() {
+    test_with_clients(async move |db_client| {
+        store_daily_blocks(&db_client).await;
+
+        let policy = RetentionPolicy {
+            keep_blocks: None,
+            keep_days: Some(u64::MAX),
+            headers_only: false,
+            batch_size: 2,
+        };
+        assert!(start_pruning(db_client.clone(), &policy).await.is_err());
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 0);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_pruning_keeps_the_headers_of_the_pruned_blocks() {
+    test_with_clients(async move |db_client| {
+        let blocks = store_daily_blocks(&db_client).await;
+
+        let policy = RetentionPolicy {
+            keep_blocks: Some(3),
+            keep_days: None,
+            headers_only: true,
+            batch_size: 3,
+        };
+        start_pruning(db_client.clone(), &policy).await.unwrap();
+
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 0);
+        assert_pruned_before(&db_client, &blocks, 7).await;
+
+        for block in &blocks {
+            let stored_block = db_client
+                .get_full_block_by_number(block.number.as_u64())
+                .await
+                .unwrap();
+            assert_eq!(stored_block.hash, block.hash);
+            if block.number.as_u64() < 7 {
+                assert!(stored_block.transactions.is_empty());
+            } else {
+                assert_eq!(stored_block.transactions, block.transactions);
+            }
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_extractor_restarts_after_pruning_the_genesis_block() {
+    test_with_clients(async move |db_client| {
+        let blocks = generate_correct_block_sequence(0..10, Default::default(), 2);
+        let config = ExtractorArgs::load_from([
+            "evm-block-extractor",
+            "--rpc-url",
+            "http://127.0.0.1:8545",
+            "--in-memory",
+        ])
+        .unwrap();
+
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks[..6].to_vec());
+        start_extractor(
+            config.clone(),
+            db_client.clone(),
+            Arc::new(EthJsonRpcClient::new(mock_client)),
+            ChainEvents::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(5));
+
+        let policy = RetentionPolicy {
+            keep_blocks: Some(2),
+            keep_days: None,
+            headers_only: false,
+            batch_size: 10,
+        };
+        start_pruning(db_client.clone(), &policy).await.unwrap();
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 4);
+
+        // The genesis block is gone, but the extractor still recognizes the chain
+        let mock_client = MockClient::with_blocks(EvmGlobalState::Enabled, blocks);
+        start_extractor(
+            config,
+            db_client.clone(),
+            Arc::new(EthJsonRpcClient::new(mock_client)),
+            ChainEvents::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(db_client.get_earliest_block_number().await.unwrap(), 4);
+        assert_eq!(db_client.get_latest_block_number().await.unwrap(), Some(9));
+    })
+    .await
+}
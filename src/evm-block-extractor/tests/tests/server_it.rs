@@ -1,29 +1,43 @@
 use std::sync::Arc;
 
 use alloy::primitives::{Address, B256};
+use candid::Principal;
+use chrono::{DateTime, TimeDelta, Utc};
+use did::certified::test_utils::LocalRootKey;
 use did::evm_state::EvmGlobalState;
 use did::rpc::id::Id;
 use did::rpc::params::Params;
 use did::rpc::request::{Request, RpcRequest};
 use did::rpc::response::{Response, RpcResponse};
 use did::rpc::version::Version;
-use did::{Block, BlockNumber, H160, H256, U64, U256};
-use ethereum_json_rpc_client::reqwest::ReqwestClient;
-use ethereum_json_rpc_client::{Client, EthJsonRpcClient};
-use evm_block_extractor::database::postgres_db_client::PostgresDbClient;
-use evm_block_extractor::database::{AccountBalance, CertifiedBlock, DatabaseClient};
-use evm_block_extractor::rpc::{EthImpl, EthServer, ICServer};
-use jsonrpsee::RpcModule;
+use did::transaction::TransactionReceiptLog;
+use did::{Block, BlockNumber, BlockchainBlockInfo, H160, H256, TransactionReceipt, U64, U256};
+use ethereum_json_rpc_client::reqwest::{ReqwestClient, reqwest};
+use ethereum_json_rpc_client::{Client, EthGetLogsParams, EthJsonRpcClient};
+use evm_block_extractor::certificate::CertificateVerifier;
+use evm_block_extractor::database::any_db_client::AnyDbClient;
+use evm_block_extractor::database::{
+    AccountBalance, CertifiedBlock, DatabaseClient, DiscardedBlock,
+};
+use evm_block_extractor::health::{HEALTH_PATH, READY_PATH, SyncStatus};
+use evm_block_extractor::rpc::{
+    AddressTransactionsPage, DiscardedBlocksPage, EthImpl, EthServer, ICServer,
+};
+use evm_block_extractor::server;
+use evm_block_extractor::subscription::{ChainEvent, ChainEvents};
+use jsonrpsee::core::client::{Subscription, SubscriptionClientT};
 use jsonrpsee::server::{Server, ServerHandle};
+use jsonrpsee::ws_client::WsClientBuilder;
+use jsonrpsee::{RpcModule, rpc_params};
 use rand::random;
 use serde_json::json;
 
 use crate::test_with_clients;
-use crate::tests::block_extractor_it::MockClient;
+use crate::tests::block_extractor_it::{ETH_CALL_RESULT, MockClient};
 
 const BLOCK_COUNT: u64 = 10;
 
-async fn with_filled_db<Func: AsyncFn(Arc<PostgresDbClient>) -> ()>(func: Func) {
+async fn with_filled_db<Func: AsyncFn(Arc<AnyDbClient>) -> ()>(func: Func) {
     test_with_clients(async |db_client| {
         db_client.init(None, false).await.unwrap();
 
@@ -37,12 +51,18 @@ async fn with_filled_db<Func: AsyncFn(Arc<PostgresDbClient>) -> ()>(func: Func)
             let dummy_block = Block::<H256> {
                 number: U64::from(i),
                 hash: H256::from(B256::random()),
-                transactions: vec![tx_hash],
+                transactions: vec![tx_hash.clone()],
+                ..Default::default()
+            };
+            let dummy_receipt = TransactionReceipt {
+                transaction_hash: tx_hash,
+                block_hash: dummy_block.hash.clone(),
+                block_number: i.into(),
                 ..Default::default()
             };
 
             db_client
-                .insert_block_data(&[dummy_block], &[dummy_transaction])
+                .insert_block_data(&[dummy_block], &[dummy_transaction], &[dummy_receipt])
                 .await
                 .unwrap();
         }
@@ -84,6 +104,73 @@ async fn test_get_blocks() {
     .await
 }
 
+#[tokio::test]
+async fn test_get_block_by_hash() {
+    with_filled_db(|db_client| async {
+        let (http_client, _port, handle) = new_server(db_client.clone(), None).await;
+
+        let get_block_by_hash = async |hash: H256, full_transactions: bool| {
+            http_client
+                .single_request::<serde_json::Value>(
+                    "eth_getBlockByHash".to_string(),
+                    Params::Array(vec![json!(hash), json!(full_transactions)]),
+                    Id::Number(1),
+                )
+                .await
+                .unwrap()
+        };
+
+        for i in 0u64..BLOCK_COUNT {
+            let block = http_client
+                .get_block_by_number(BlockNumber::Number(i.into()))
+                .await
+                .unwrap();
+
+            let block_by_hash: Block<H256> =
+                serde_json::from_value(get_block_by_hash(block.hash.clone(), false).await).unwrap();
+            assert_eq!(block_by_hash, block);
+
+            let full_block_by_hash: Block<did::Transaction> =
+                serde_json::from_value(get_block_by_hash(block.hash.clone(), true).await).unwrap();
+            assert_eq!(full_block_by_hash.number, i.into());
+            assert_eq!(
+                full_block_by_hash.transactions[0].hash,
+                block.transactions[0]
+            );
+        }
+
+        // Unknown blocks are returned as null
+        assert!(
+            get_block_by_hash(H256::from(B256::random()), false)
+                .await
+                .is_null()
+        );
+
+        // Discarded blocks are returned with the discard reason
+        let last_block = http_client
+            .get_block_by_number(BlockNumber::Number((BLOCK_COUNT - 1).into()))
+            .await
+            .unwrap();
+        db_client
+            .discard_blocks_from(BLOCK_COUNT - 1, "test reason")
+            .await
+            .unwrap();
+
+        let discarded = get_block_by_hash(last_block.hash.clone(), false).await;
+        assert_eq!(discarded["discardReason"], json!("test reason"));
+        assert!(discarded["discardedAt"].is_string());
+        let discarded_block: Block<H256> = serde_json::from_value(discarded).unwrap();
+        assert_eq!(discarded_block.hash, last_block.hash);
+        assert_eq!(discarded_block.transactions, last_block.transactions);
+
+        {
+            handle.stop().unwrap();
+            handle.stopped().await;
+        }
+    })
+    .await
+}
+
 #[tokio::test]
 async fn test_batched_request() {
     with_filled_db(|db_client| async {
@@ -132,6 +219,253 @@ async fn test_batched_request() {
     .await
 }
 
+#[tokio::test]
+async fn test_proxy_methods_are_forwarded_to_the_evmc() {
+    with_filled_db(|db_client| async {
+        let evm_client = Arc::new(EthJsonRpcClient::new(MockClient::new(
+            EvmGlobalState::Enabled,
+        )));
+
+        // The methods served by the extractor can't be forwarded
+        let served_methods = ["eth_getBlockByNumber".to_string()];
+        assert!(
+            server::server_start(
+                "127.0.0.1:0",
+                db_client.clone(),
+                evm_client.clone(),
+                100,
+                None,
+                &served_methods,
+                ChainEvents::default(),
+            )
+            .await
+            .is_err()
+        );
+
+        let proxy_methods = ["eth_call".to_string(), "eth_estimateGas".to_string()];
+        let (port, handle) = start_server(
+            db_client.clone(),
+            evm_client.clone(),
+            100,
+            None,
+            &proxy_methods,
+            ChainEvents::default(),
+        )
+        .await;
+
+        let http_client = ReqwestClient::new(format!("http://127.0.0.1:{port}"));
+        let request = |method: &str| Request {
+            jsonrpc: Some(Version::V2),
+            method: method.to_string(),
+            params: Params::Array(vec![json!({ "to": Address::ZERO }), json!("latest")]),
+            id: Id::String(method.to_string()),
+        };
+        let request = RpcRequest::Batch(vec![
+            request("eth_call"),
+            request("eth_estimateGas"),
+            request("eth_sendRawTransaction"),
+        ]);
+
+        let RpcResponse::Batch(results) = http_client.send_rpc_request(request).await.unwrap()
+        else {
+            panic!("unexpected return type")
+        };
+
+        match &results[..] {
+            [
+                Response::Success(call),
+                Response::Failure(estimate_gas),
+                Response::Failure(not_forwarded),
+            ] => {
+                assert_eq!(call.result, json!(ETH_CALL_RESULT));
+
+                // The errors of the EVMC are forwarded
+                assert_eq!(estimate_gas.error.code.code(), -32602);
+                assert_eq!(estimate_gas.error.message, "gas estimation failed");
+
+                assert_eq!(not_forwarded.error.code.code(), -32601);
+            }
+            _ => panic!("unexpected results {results:?}"),
+        }
+
+        {
+            handle.stop().unwrap();
+            handle.stopped().await;
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_subscriptions_are_notified_of_chain_events() {
+    with_filled_db(|db_client| async {
+        let evm_client = Arc::new(EthJsonRpcClient::new(MockClient::new(
+            EvmGlobalState::Enabled,
+        )));
+        let chain_events = ChainEvents::default();
+
+        let (port, handle) = start_server(
+            db_client.clone(),
+            evm_client.clone(),
+            100,
+            None,
+            &[],
+            chain_events.clone(),
+        )
+        .await;
+
+        let ws_client = WsClientBuilder::default()
+            .build(format!("ws://127.0.0.1:{port}"))
+            .await
+            .unwrap();
+        let address = H160::from_slice(&[1; 20]);
+        let mut new_heads: Subscription<serde_json::Value> = ws_client
+            .subscribe("eth_subscribe", rpc_params!["newHeads"], "eth_unsubscribe")
+            .await
+            .unwrap();
+        let mut logs: Subscription<TransactionReceiptLog> = ws_client
+            .subscribe(
+                "eth_subscribe",
+                rpc_params!["logs", json!({ "address": address })],
+                "eth_unsubscribe",
+            )
+            .await
+            .unwrap();
+
+        // Invalid subscriptions are rejected
+        let too_many_topics = json!({ "topics": [null, null, null, null, null] });
+        let result: Result<Subscription<serde_json::Value>, _> = ws_client
+            .subscribe(
+                "eth_subscribe",
+                rpc_params!["logs", too_many_topics],
+                "eth_unsubscribe",
+            )
+            .await;
+        assert!(result.is_err());
+        let result: Result<Subscription<serde_json::Value>, _> = ws_client
+            .subscribe("eth_subscribe", rpc_params!["syncing"], "eth_unsubscribe")
+            .await;
+        assert!(result.is_err());
+
+        let block = Block::<H256> {
+            number: BLOCK_COUNT.into(),
+            hash: H256::from(B256::random()),
+            transactions: vec![H256::from(B256::random())],
+            ..Default::default()
+        };
+        let matching_log = TransactionReceiptLog {
+            address: address.clone(),
+            block_number: BLOCK_COUNT.into(),
+            block_hash: block.hash.clone(),
+            ..Default::default()
+        };
+        let other_log = TransactionReceiptLog {
+            address: H160::from_slice(&[2; 20]),
+            ..matching_log.clone()
+        };
+        chain_events.publish(ChainEvent::BlocksStored {
+            blocks: vec![block.clone()],
+            logs: vec![other_log.clone(), matching_log.clone()],
+        });
+        let removed_log = TransactionReceiptLog {
+            removed: true,
+            ..matching_log.clone()
+        };
+        chain_events.publish(ChainEvent::LogsRemoved(vec![
+            TransactionReceiptLog {
+                removed: true,
+                ..other_log
+            },
+            removed_log.clone(),
+        ]));
+
+        // Only the header of the block is sent
+        let header = new_heads.next().await.unwrap().unwrap();
+        assert!(header.get("transactions").is_none());
+        let header: Block<H256> = serde_json::from_value(header).unwrap();
+        assert_eq!(
+            header,
+            Block {
+                transactions: vec![],
+                ..block
+            }
+        );
+        assert_eq!(logs.next().await.unwrap().unwrap(), matching_log);
+        assert_eq!(logs.next().await.unwrap().unwrap(), removed_log);
+
+        new_heads.unsubscribe().await.unwrap();
+        logs.unsubscribe().await.unwrap();
+
+        {
+            handle.stop().unwrap();
+            handle.stopped().await;
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_get_blocks_by_range() {
+    with_filled_db(|db_client| async {
+        let (_http_client, port, handle) = new_server(db_client, None).await;
+
+        let http_client = ReqwestClient::new(format!("http://127.0.0.1:{port}"));
+        let request = |id: &str, params: Vec<serde_json::Value>| Request {
+            jsonrpc: Some(Version::V2),
+            method: "ic_getBlocksByRange".to_string(),
+            params: Params::Array(params),
+            id: Id::String(id.to_string()),
+        };
+        let request = RpcRequest::Batch(vec![
+            request("hashes", vec![json!("0x2"), json!("0x5"), json!(false)]),
+            request(
+                "full",
+                vec![json!("earliest"), json!("latest"), json!(true)],
+            ),
+            request(
+                "too_large",
+                vec![json!("0x0"), json!("0x100000"), json!(false)],
+            ),
+            request("inverted", vec![json!("0x5"), json!("0x2"), json!(false)]),
+        ]);
+
+        let RpcResponse::Batch(results) = http_client.send_rpc_request(request).await.unwrap()
+        else {
+            panic!("unexpected return type")
+        };
+
+        match &results[..] {
+            [
+                Response::Success(hashes),
+                Response::Success(full),
+                Response::Failure(_),
+                Response::Failure(_),
+            ] => {
+                let blocks: Vec<Block<H256>> =
+                    serde_json::from_value(hashes.result.clone()).unwrap();
+                let block_numbers = blocks.iter().map(|b| b.number.as_u64()).collect::<Vec<_>>();
+                assert_eq!(block_numbers, vec![2, 3, 4, 5]);
+
+                let blocks: Vec<Block<did::Transaction>> =
+                    serde_json::from_value(full.result.clone()).unwrap();
+                assert_eq!(blocks.len(), BLOCK_COUNT as usize);
+                for (i, block) in blocks.iter().enumerate() {
+                    assert_eq!(block.number.as_u64(), i as u64);
+                    assert_eq!(block.transactions.len(), 1);
+                    assert_eq!(block.transactions[0].block_number, Some(block.number));
+                }
+            }
+            _ => panic!("unexpected results"),
+        }
+
+        {
+            handle.stop().unwrap();
+            handle.stopped().await;
+        }
+    })
+    .await
+}
+
 #[tokio::test]
 async fn test_get_genesis_accounts() {
     test_with_clients(async move |db_client| {
@@ -321,6 +655,65 @@ async fn test_get_last_certified_block() {
     .await
 }
 
+#[tokio::test]
+async fn test_get_last_certified_block_verifies_certificate() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        let root_key = LocalRootKey::from_seed(b"test");
+        let canister_id = Principal::from_slice(&[0, 0, 0, 0, 1, 48, 0, 4, 1, 1]);
+        let verifier = CertificateVerifier::new(root_key.public_key(), canister_id);
+
+        let block = Block::<H256> {
+            number: 1u64.into(),
+            hash: H256::from_slice(&[1; 32]),
+            ..Default::default()
+        };
+        let certified_block = root_key.certify_block(&canister_id, block);
+        db_client
+            .insert_certified_block_data(certified_block.clone())
+            .await
+            .unwrap();
+
+        let evm_client = Arc::new(EthJsonRpcClient::new(MockClient::new(
+            EvmGlobalState::Enabled,
+        )));
+        let (port, handle) = start_server(
+            db_client.clone(),
+            evm_client.clone(),
+            100,
+            Some(verifier.clone()),
+            &[],
+            ChainEvents::default(),
+        )
+        .await;
+        let http_client =
+            EthJsonRpcClient::new(ReqwestClient::new(format!("http://127.0.0.1:{port}")));
+
+        let served_block = http_client.get_last_certified_block().await.unwrap();
+        assert_eq!(served_block, certified_block);
+
+        // A stored block with an invalid certificate is not served
+        let forged_block = CertifiedBlock {
+            data: Block::<H256> {
+                number: 2u64.into(),
+                hash: H256::from_slice(&[2; 32]),
+                ..Default::default()
+            },
+            ..certified_block
+        };
+        db_client
+            .insert_certified_block_data(forged_block)
+            .await
+            .unwrap();
+
+        assert!(http_client.get_last_certified_block().await.is_err());
+
+        server::server_stop(handle).await.unwrap();
+    })
+    .await
+}
+
 #[tokio::test]
 async fn test_get_evm_global_state() {
     with_filled_db(|db_client| async {
@@ -346,8 +739,529 @@ async fn test_get_evm_global_state() {
     .await
 }
 
+#[tokio::test]
+async fn test_get_transactions() {
+    with_filled_db(|db_client| async {
+        let (http_client, port, handle) = new_server(db_client, None).await;
+
+        for i in 0u64..BLOCK_COUNT {
+            let block = http_client
+                .get_block_by_number(BlockNumber::Number(i.into()))
+                .await
+                .unwrap();
+            let tx_hash = block.transactions[0].clone();
+
+            let tx = http_client
+                .get_transaction_by_hash(tx_hash.clone())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(tx.hash, tx_hash);
+            assert_eq!(tx.block_number, Some(i.into()));
+
+            let receipt = http_client
+                .get_receipt_by_hash(tx_hash.clone())
+                .await
+                .unwrap();
+            assert_eq!(receipt.transaction_hash, tx_hash);
+            assert_eq!(receipt.block_hash, block.hash);
+            assert_eq!(receipt.block_number, i.into());
+        }
+
+        // Unknown transactions are returned as null
+        let tx = http_client
+            .get_transaction_by_hash(H256::from(B256::random()))
+            .await
+            .unwrap();
+        assert!(tx.is_none());
+
+        // Blocks which are not stored are returned as null
+        let missing_block = format!("{:#x}", BLOCK_COUNT + 10);
+
+        let http_client = ReqwestClient::new(format!("http://127.0.0.1:{port}"));
+        let request = RpcRequest::Batch(vec![
+            Request {
+                jsonrpc: Some(Version::V2),
+                method: "eth_getTransactionByBlockNumberAndIndex".to_string(),
+                params: Params::Array(vec![json!("0x5"), json!("0x0")]),
+                id: Id::Number(1),
+            },
+            Request {
+                jsonrpc: Some(Version::V2),
+                method: "eth_getTransactionByBlockNumberAndIndex".to_string(),
+                params: Params::Array(vec![json!("latest"), json!("0x1")]),
+                id: Id::Number(2),
+            },
+            Request {
+                jsonrpc: Some(Version::V2),
+                method: "eth_getBlockTransactionCountByNumber".to_string(),
+                params: Params::Array(vec![json!("0x5")]),
+                id: Id::Number(3),
+            },
+            Request {
+                jsonrpc: Some(Version::V2),
+                method: "eth_getTransactionReceipt".to_string(),
+                params: Params::Array(vec![json!(H256::from(B256::random()))]),
+                id: Id::Number(4),
+            },
+            Request {
+                jsonrpc: Some(Version::V2),
+                method: "eth_getTransactionByBlockNumberAndIndex".to_string(),
+                params: Params::Array(vec![json!(missing_block), json!("0x0")]),
+                id: Id::Number(5),
+            },
+            Request {
+                jsonrpc: Some(Version::V2),
+                method: "eth_getBlockTransactionCountByNumber".to_string(),
+                params: Params::Array(vec![json!(missing_block)]),
+                id: Id::Number(6),
+            },
+        ]);
+
+        let RpcResponse::Batch(results) = http_client.send_rpc_request(request).await.unwrap()
+        else {
+            panic!("unexpected return type")
+        };
+
+        match &results[..] {
+            [
+                Response::Success(tx_by_index),
+                Response::Success(missing_tx_by_index),
+                Response::Success(tx_count),
+                Response::Success(missing_receipt),
+                Response::Success(tx_of_missing_block),
+                Response::Success(tx_count_of_missing_block),
+            ] => {
+                let tx: did::Transaction =
+                    serde_json::from_value(tx_by_index.result.clone()).unwrap();
+                assert_eq!(tx.block_number, Some(5u64.into()));
+
+                assert!(missing_tx_by_index.result.is_null());
+
+                let tx_count: U64 = serde_json::from_value(tx_count.result.clone()).unwrap();
+                assert_eq!(tx_count, U64::from(1u64));
+
+                assert!(missing_receipt.result.is_null());
+
+                assert!(tx_of_missing_block.result.is_null());
+                assert!(tx_count_of_missing_block.result.is_null());
+            }
+            _ => panic!("unexpected results"),
+        }
+
+        {
+            handle.stop().unwrap();
+            handle.stopped().await;
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_get_logs() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        let address = H160::from(Address::random());
+        let topic = H256::from(B256::random());
+
+        for i in 0..BLOCK_COUNT {
+            let tx_hash = H256::from(B256::random());
+            let block_hash = H256::from(B256::random());
+            let transaction = did::Transaction {
+                hash: tx_hash.clone(),
+                block_number: Some(i.into()),
+                ..Default::default()
+            };
+            let block = Block::<H256> {
+                number: U64::from(i),
+                hash: block_hash.clone(),
+                transactions: vec![tx_hash.clone()],
+                ..Default::default()
+            };
+            let receipt = TransactionReceipt {
+                transaction_hash: tx_hash.clone(),
+                block_hash: block_hash.clone(),
+                block_number: i.into(),
+                logs: vec![TransactionReceiptLog {
+                    // logs from even blocks are emitted by `address`
+                    address: if i % 2 == 0 {
+                        address.clone()
+                    } else {
+                        H160::from(Address::random())
+                    },
+                    topics: vec![topic.clone()],
+                    transaction_hash: tx_hash,
+                    block_number: i.into(),
+                    block_hash,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+
+            db_client
+                .insert_block_data(&[block], &[transaction], &[receipt])
+                .await
+                .unwrap();
+        }
+
+        let (http_client, port, handle) = new_server(db_client, None).await;
+
+        let logs = http_client
+            .get_logs(EthGetLogsParams {
+                address: Some(vec![address.clone()]),
+                from_block: BlockNumber::Earliest,
+                to_block: BlockNumber::Latest,
+                topics: Some(vec![vec![topic.clone()]]),
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), BLOCK_COUNT as usize / 2);
+        for (log, block_number) in logs.iter().zip((0..BLOCK_COUNT).step_by(2)) {
+            assert_eq!(log.address(), Address::from(address.clone()));
+            assert_eq!(log.block_number, Some(block_number));
+        }
+
+        let logs = http_client
+            .get_logs(EthGetLogsParams {
+                address: None,
+                from_block: BlockNumber::Number(3u64.into()),
+                to_block: BlockNumber::Number(5u64.into()),
+                topics: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 3);
+
+        // `fromBlock` greater than `toBlock` is rejected
+        let result = http_client
+            .get_logs(EthGetLogsParams {
+                address: None,
+                from_block: BlockNumber::Number(5u64.into()),
+                to_block: BlockNumber::Number(3u64.into()),
+                topics: None,
+            })
+            .await;
+        assert!(result.is_err());
+
+        // Filters with both `blockHash` and `fromBlock` are rejected
+        let http_client = ReqwestClient::new(format!("http://127.0.0.1:{port}"));
+        let request = RpcRequest::Single(Request {
+            jsonrpc: Some(Version::V2),
+            method: "eth_getLogs".to_string(),
+            params: Params::Array(vec![json!({
+                "blockHash": H256::from(B256::random()),
+                "fromBlock": "earliest",
+            })]),
+            id: Id::String("eth_getLogs".to_string()),
+        });
+        let RpcResponse::Single(response) = http_client.send_rpc_request(request).await.unwrap()
+        else {
+            panic!("unexpected return type")
+        };
+        assert!(matches!(response, Response::Failure(_)));
+
+        {
+            handle.stop().unwrap();
+            handle.stopped().await;
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_get_transactions_by_address() {
+    test_with_clients(async move |db_client| {
+        db_client.init(None, false).await.unwrap();
+
+        let address = H160::from(Address::random());
+
+        for i in 0..BLOCK_COUNT {
+            let tx_hash = H256::from(B256::random());
+            // transactions of even blocks are sent by `address`, the others are sent to it
+            let (from, to) = if i % 2 == 0 {
+                (address.clone(), H160::from(Address::random()))
+            } else {
+                (H160::from(Address::random()), address.clone())
+            };
+            let transaction = did::Transaction {
+                hash: tx_hash.clone(),
+                block_number: Some(i.into()),
+                transaction_index: Some(0u64.into()),
+                from,
+                to: Some(to),
+                ..Default::default()
+            };
+            let block = Block::<H256> {
+                number: U64::from(i),
+                hash: H256::from(B256::random()),
+                transactions: vec![tx_hash],
+                ..Default::default()
+            };
+
+            db_client
+                .insert_block_data(&[block], &[transaction], &[])
+                .await
+                .unwrap();
+        }
+
+        let (_, port, handle) = new_server(db_client, None).await;
+        let http_client = ReqwestClient::new(format!("http://127.0.0.1:{port}"));
+
+        let get_page = async |filter: serde_json::Value| {
+            let request = RpcRequest::Single(Request {
+                jsonrpc: Some(Version::V2),
+                method: "ic_getTransactionsByAddress".to_string(),
+                params: Params::Array(vec![json!(address), filter]),
+                id: Id::String("ic_getTransactionsByAddress".to_string()),
+            });
+            match http_client.send_rpc_request(request).await.unwrap() {
+                RpcResponse::Single(Response::Success(success)) => {
+                    Ok(serde_json::from_value::<AddressTransactionsPage>(success.result).unwrap())
+                }
+                RpcResponse::Single(Response::Failure(failure)) => Err(failure),
+                _ => panic!("unexpected return type"),
+            }
+        };
+
+        // All the transactions, paginated
+        let mut block_numbers = vec![];
+        let mut after = None;
+        loop {
+            let page = get_page(json!({ "limit": 4, "after": after }))
+                .await
+                .unwrap();
+            block_numbers.extend(
+                page.transactions
+                    .iter()
+                    .map(|tx| tx.block_number.unwrap().as_u64()),
+            );
+            after = page.next;
+            if after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(block_numbers, (0..BLOCK_COUNT).collect::<Vec<_>>());
+
+        // Direction, block range and order
+        let page = get_page(json!({
+            "direction": "from",
+            "fromBlock": "0x3",
+            "toBlock": "0x7",
+            "descending": true,
+        }))
+        .await
+        .unwrap();
+        let block_numbers = page
+            .transactions
+            .iter()
+            .map(|tx| tx.block_number.unwrap().as_u64())
+            .collect::<Vec<_>>();
+        assert_eq!(block_numbers, vec![6, 4]);
+        assert!(page.next.is_none());
+
+        // Limits above the maximum page size are rejected
+        assert!(get_page(json!({ "limit": 1_000_000 })).await.is_err());
+
+        {
+            handle.stop().unwrap();
+            handle.stopped().await;
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_get_discarded_blocks() {
+    with_filled_db(|db_client| async {
+        let (http_client, _port, handle) = new_server(db_client.clone(), None).await;
+
+        const FIRST_DISCARDED_BLOCK: u64 = 5;
+        let mut discarded_hashes = vec![];
+        for i in FIRST_DISCARDED_BLOCK..BLOCK_COUNT {
+            let block = db_client.get_block_by_number(i).await.unwrap();
+            discarded_hashes.push(block.hash);
+        }
+        db_client
+            .discard_blocks_from(FIRST_DISCARDED_BLOCK, "test reason")
+            .await
+            .unwrap();
+
+        let get_discarded_block_by_hash = async |hash: &H256| {
+            http_client
+                .single_request::<Option<DiscardedBlock>>(
+                    "ic_getDiscardedBlockByHash".to_string(),
+                    Params::Array(vec![json!(hash)]),
+                    Id::Number(1),
+                )
+                .await
+                .unwrap()
+        };
+
+        let discarded = get_discarded_block_by_hash(&discarded_hashes[0])
+            .await
+            .unwrap();
+        assert_eq!(discarded.block.hash, discarded_hashes[0]);
+        assert_eq!(discarded.block.number, FIRST_DISCARDED_BLOCK.into());
+        assert_eq!(discarded.block.transactions.len(), 1);
+        assert_eq!(discarded.reason, "test reason");
+        assert!(
+            get_discarded_block_by_hash(&H256::from(B256::random()))
+                .await
+                .is_none()
+        );
+
+        let get_page = async |since: DateTime<Utc>, limit: usize, after: Option<H256>| {
+            http_client
+                .single_request::<DiscardedBlocksPage>(
+                    "ic_getDiscardedBlocks".to_string(),
+                    Params::Array(vec![json!(since), json!(limit), json!(after)]),
+                    Id::Number(1),
+                )
+                .await
+        };
+
+        // All the discarded blocks, paginated
+        let mut hashes = vec![];
+        let mut since = discarded.timestamp - TimeDelta::hours(1);
+        let mut after = None;
+        loop {
+            let page = get_page(since, 2, after).await.unwrap();
+            assert!(page.blocks.len() <= 2);
+            hashes.extend(
+                page.blocks
+                    .into_iter()
+                    .map(|discarded| discarded.block.hash),
+            );
+            let Some(next) = page.next else {
+                break;
+            };
+            since = next.since;
+            after = Some(next.after);
+        }
+        hashes.sort();
+        discarded_hashes.sort();
+        assert_eq!(hashes, discarded_hashes);
+
+        // No blocks discarded after the given time
+        let page = get_page(discarded.timestamp + TimeDelta::hours(1), 2, None)
+            .await
+            .unwrap();
+        assert!(page.blocks.is_empty());
+        assert!(page.next.is_none());
+
+        // Limits above the maximum page size are rejected
+        assert!(get_page(since, 1_000_000, None).await.is_err());
+
+        {
+            handle.stop().unwrap();
+            handle.stopped().await;
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_health_and_readiness_routes() {
+    with_filled_db(|db_client| async {
+        let evm_client = Arc::new(EthJsonRpcClient::new(MockClient::new(
+            EvmGlobalState::Enabled,
+        )));
+        let max_sync_lag_blocks = 5;
+
+        let (port, handle) = start_server(
+            db_client.clone(),
+            evm_client.clone(),
+            max_sync_lag_blocks,
+            None,
+            &[],
+            ChainEvents::default(),
+        )
+        .await;
+
+        let http_client = reqwest::Client::new();
+        let get = async |path: &str| {
+            http_client
+                .get(format!("http://127.0.0.1:{port}{path}"))
+                .send()
+                .await
+                .unwrap()
+        };
+
+        let response = get(HEALTH_PATH).await;
+        assert!(response.status().is_success());
+
+        // Without block info the remote head is unknown, so the lag is unknown too
+        let response = get(READY_PATH).await;
+        assert!(!response.status().is_success());
+
+        let mut block_info = BlockchainBlockInfo {
+            earliest_block_number: 0,
+            latest_block_number: BLOCK_COUNT - 1 + max_sync_lag_blocks,
+            safe_block_number: 0,
+            finalized_block_number: 0,
+            pending_block_number: 0,
+        };
+        db_client.set_block_info(block_info.clone()).await.unwrap();
+
+        let response = get(READY_PATH).await;
+        assert!(response.status().is_success());
+        let status: SyncStatus = response.json().await.unwrap();
+        assert_eq!(
+            status,
+            SyncStatus {
+                latest_block_number: BLOCK_COUNT - 1,
+                remote_latest_block_number: BLOCK_COUNT - 1 + max_sync_lag_blocks,
+                lag: max_sync_lag_blocks,
+            }
+        );
+
+        // Too far behind the EVMC
+        block_info.latest_block_number += 1;
+        db_client.set_block_info(block_info).await.unwrap();
+
+        let response = get(READY_PATH).await;
+        assert!(!response.status().is_success());
+
+        // The server is still alive
+        let response = get(HEALTH_PATH).await;
+        assert!(response.status().is_success());
+
+        server::server_stop(handle).await.unwrap();
+    })
+    .await
+}
+
+/// Starts the extractor server on a free local port.
+/// Returns the port and the handle of the server.
+async fn start_server(
+    db_client: Arc<AnyDbClient>,
+    evm_client: Arc<EthJsonRpcClient<impl Client + 'static>>,
+    max_sync_lag_blocks: u64,
+    certificate_verifier: Option<CertificateVerifier>,
+    proxy_methods: &[String],
+    chain_events: ChainEvents,
+) -> (u16, ServerHandle) {
+    loop {
+        let port = port_check::free_local_port().unwrap();
+        if let Ok(handle) = server::server_start(
+            &format!("127.0.0.1:{port}"),
+            db_client.clone(),
+            evm_client.clone(),
+            max_sync_lag_blocks,
+            certificate_verifier.clone(),
+            proxy_methods,
+            chain_events.clone(),
+        )
+        .await
+        {
+            return (port, handle);
+        }
+    }
+}
+
 async fn new_server(
-    db_client: Arc<PostgresDbClient>,
+    db_client: Arc<AnyDbClient>,
     evm_client: Option<Arc<EthJsonRpcClient<MockClient>>>,
 ) -> (EthJsonRpcClient<ReqwestClient>, u16, ServerHandle) {
     let evm_client = evm_client.unwrap_or_else(|| {
@@ -356,7 +1270,7 @@ async fn new_server(
         )))
     });
 
-    let eth = EthImpl::<MockClient, PostgresDbClient>::new(db_client, evm_client);
+    let eth = EthImpl::<MockClient, AnyDbClient>::new(db_client, evm_client);
     let mut module = RpcModule::new(());
     module.merge(EthServer::into_rpc(eth.clone())).unwrap();
     module.merge(ICServer::into_rpc(eth)).unwrap();